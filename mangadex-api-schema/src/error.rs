@@ -87,3 +87,165 @@ pub struct MangaDexError {
     // See: https://serde.rs/enum-representations.html
     pub context: Option<HashMap<String, String>>,
 }
+
+impl MangaDexError {
+    /// The error code, such as `not_found_http_exception`, sent in the title.
+    pub fn code(&self) -> Option<String> {
+        self.title
+            .as_ref()
+            .map(|title| title.trim().to_lowercase().replace([' ', '-'], "_"))
+    }
+
+    /// Decode the kind of the error from its status and error code.
+    pub fn kind(&self) -> ApiErrorKind {
+        let code = self.code().unwrap_or_default();
+        let context = |key: &str| {
+            self.context
+                .as_ref()
+                .and_then(|context| context.get(key))
+                .cloned()
+        };
+        let detail = self.detail.as_deref().unwrap_or_default().to_lowercase();
+        if code.contains("captcha_required") || (self.status == 403 && context("siteKey").is_some())
+        {
+            return ApiErrorKind::CaptchaRequired {
+                site_key: context("siteKey"),
+            };
+        }
+        if code.contains("captcha") {
+            return ApiErrorKind::InvalidCaptcha;
+        }
+        if code.contains("validation") {
+            return ApiErrorKind::Validation {
+                field: context("field").or_else(|| self.validated_field()),
+            };
+        }
+        if code.contains("upload_session_already_exists")
+            || (detail.contains("upload session") && detail.contains("already exist"))
+        {
+            return ApiErrorKind::UploadSessionExists;
+        }
+        match self.status {
+            400 => ApiErrorKind::BadRequest,
+            401 => ApiErrorKind::Unauthorized,
+            403 => ApiErrorKind::Forbidden,
+            404 => ApiErrorKind::NotFound,
+            409 => ApiErrorKind::Conflict,
+            413 => ApiErrorKind::PayloadTooLarge,
+            429 => ApiErrorKind::RateLimited,
+            500..=599 => ApiErrorKind::Server,
+            _ => ApiErrorKind::Unknown,
+        }
+    }
+
+    /// The field named in a `Error validating /field: ...` detail.
+    fn validated_field(&self) -> Option<String> {
+        let (field, _) = self
+            .detail
+            .as_deref()?
+            .strip_prefix("Error validating ")?
+            .split_once(':')?;
+        Some(field.trim().trim_start_matches('/').to_string())
+    }
+}
+
+impl MangaDexErrorResponse_ {
+    /// The kinds of the errors in the response.
+    pub fn kinds(&self) -> impl Iterator<Item = ApiErrorKind> + '_ {
+        self.errors.iter().map(MangaDexError::kind)
+    }
+
+    /// Check if one of the errors is of the `kind`.
+    pub fn has_kind(&self, kind: &ApiErrorKind) -> bool {
+        self.kinds().any(|other| other == *kind)
+    }
+}
+
+/// The kind of a [`MangaDexError`], decoded with [`MangaDexError::kind`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum ApiErrorKind {
+    /// The request is malformed (400).
+    BadRequest,
+    /// A field of the request is invalid (`validation_exception`).
+    Validation { field: Option<String> },
+    /// The captcha result is wrong or couldn't be verified (400).
+    InvalidCaptcha,
+    /// A captcha must be solved before retrying the request (`captcha_required_exception`).
+    ///
+    /// Solve it with the `site_key`, then send the result with the `X-Captcha-Result` header
+    /// or to `POST /captcha/solve`.
+    CaptchaRequired { site_key: Option<String> },
+    /// The session is missing or expired (401).
+    Unauthorized,
+    /// The user isn't allowed to do this (403).
+    Forbidden,
+    /// The resource doesn't exist (404).
+    NotFound,
+    /// The resource conflicts with an existing one (409).
+    Conflict,
+    /// The user already has an upload session.
+    UploadSessionExists,
+    /// The request body is too large (413).
+    PayloadTooLarge,
+    /// Too many requests were sent (429).
+    RateLimited,
+    /// The MangaDex servers failed (5xx).
+    Server,
+    /// Any other error.
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(status: u16, title: &str, detail: &str) -> MangaDexError {
+        MangaDexError {
+            status,
+            title: Some(title.to_string()),
+            detail: Some(detail.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn error_kinds_are_decoded_from_the_code() {
+        let mut captcha = error(403, "captcha_required_exception", "Captcha required");
+        captcha.context = Some(HashMap::from([(
+            "siteKey".to_string(),
+            "someSiteKey".to_string(),
+        )]));
+        assert_eq!(
+            captcha.kind(),
+            ApiErrorKind::CaptchaRequired {
+                site_key: Some("someSiteKey".to_string())
+            }
+        );
+        assert_eq!(
+            error(
+                400,
+                "validation_exception",
+                "Error validating /title: String value found, but an object is required"
+            )
+            .kind(),
+            ApiErrorKind::Validation {
+                field: Some("title".to_string())
+            }
+        );
+        assert_eq!(
+            error(400, "Bad Request", "An upload session already exists").kind(),
+            ApiErrorKind::UploadSessionExists
+        );
+        assert_eq!(
+            error(404, "not_found_http_exception", "Manga could not be found").kind(),
+            ApiErrorKind::NotFound
+        );
+        assert_eq!(
+            error(403, "forbidden_http_exception", "").kind(),
+            ApiErrorKind::Forbidden
+        );
+        assert_eq!(error(418, "teapot", "").kind(), ApiErrorKind::Unknown);
+    }
+}
@@ -130,6 +130,21 @@ impl<A> ApiObject<A> {
     pub fn find_first_relationships(&self, type_: RelationshipType) -> Option<&Relationship> {
         self.relationships.iter().find(|rel| rel.type_ == type_)
     }
+    /// Get the relationships of `type_` expanded with the `includes[]` parameter.
+    ///
+    /// The relationships without attributes are skipped.
+    pub fn expanded_relationships<T>(
+        &self,
+        type_: RelationshipType,
+    ) -> Vec<ApiObjectNoRelationships<T>>
+    where
+        ApiObjectNoRelationships<T>: TryFrom<Relationship>,
+    {
+        self.find_relationships(type_)
+            .into_iter()
+            .filter_map(|rel| rel.clone().try_into().ok())
+            .collect()
+    }
 }
 
 impl<T> From<ApiObject<T>> for ApiObjectNoRelationships<T> {
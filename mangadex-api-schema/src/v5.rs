@@ -3,6 +3,7 @@ pub mod at_home_server;
 pub mod auth_tokens;
 pub mod author;
 pub mod chapter;
+pub mod chapter_preference;
 pub mod check_token_response;
 pub mod check_username_available;
 pub mod cover;
@@ -23,6 +24,7 @@ pub mod manga_relation;
 pub mod oauth;
 pub mod ratings;
 pub mod refresh_token_response;
+mod relationships;
 pub mod report;
 pub mod scanlation_group;
 pub mod settings_template;
@@ -231,7 +233,8 @@ impl TryFrom<Relationship> for ApiObjectNoRelationships<UserAttributes> {
     fn try_from(value: Relationship) -> Result<Self, Self::Error> {
         if !(value.type_ == RelationshipType::User
             || value.type_ == RelationshipType::Member
-            || value.type_ == RelationshipType::Leader)
+            || value.type_ == RelationshipType::Leader
+            || value.type_ == RelationshipType::Creator)
         {
             return Err(RelationshipConversionError::InvalidInputRelationshipType {
                 input: RelationshipType::User,
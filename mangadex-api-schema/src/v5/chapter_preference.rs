@@ -0,0 +1,213 @@
+//! Pick one chapter per chapter number out of the translations of a manga.
+
+use std::collections::BTreeMap;
+
+use mangadex_api_types::{
+    ChapterNumber, Language, MangaDexDateTime, RelationshipType, VolumeNumber,
+};
+use uuid::Uuid;
+
+use crate::v5::ChapterObject;
+
+type Rank = (usize, usize, bool, bool, bool, Option<MangaDexDateTime>);
+
+/// Policy choosing one chapter per chapter number.
+///
+/// The candidates of a number are ranked by:
+///
+/// 1. their position in [`languages`](Self::languages), the other languages being excluded
+///    unless the list is empty,
+/// 2. the position of their best group in [`groups`](Self::groups), the chapters of the other
+///    groups coming after them,
+/// 3. the chapters readable on MangaDex, before the unavailable and the external ones,
+/// 4. the earliest publication, the chapters without publication date coming last.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ChapterPreference {
+    /// The accepted languages, by decreasing preference.
+    pub languages: Vec<Language>,
+    /// The preferred scanlation groups, by decreasing preference.
+    pub groups: Vec<Uuid>,
+    /// The scanlation groups whose chapters are never picked.
+    pub excluded_groups: Vec<Uuid>,
+    /// Pick one chapter per volume and chapter number instead of one per chapter number,
+    /// for the manga restarting the chapter numbers on each volume.
+    pub by_volume: bool,
+}
+
+impl ChapterPreference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `language` after the languages already added.
+    pub fn language(mut self, language: Language) -> Self {
+        self.languages.push(language);
+        self
+    }
+
+    /// Prefer `group` after the groups already added.
+    pub fn group(mut self, group: Uuid) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    pub fn exclude_group(mut self, group: Uuid) -> Self {
+        self.excluded_groups.push(group);
+        self
+    }
+
+    /// Pick one chapter per volume and chapter number, see [`by_volume`](Self::by_volume).
+    pub fn by_volume(mut self) -> Self {
+        self.by_volume = true;
+        self
+    }
+
+    /// Keep one chapter per chapter number, sorted by chapter number.
+    ///
+    /// With [`by_volume`](Self::by_volume), one chapter is kept per volume and chapter number,
+    /// sorted by volume then chapter number.
+    /// The unnumbered chapters are all kept, after the numbered ones.
+    pub fn canonical<I>(&self, chapters: I) -> Vec<ChapterObject>
+    where
+        I: IntoIterator<Item = ChapterObject>,
+    {
+        let mut picked: BTreeMap<
+            (Option<VolumeNumber>, ChapterNumber, Option<Uuid>),
+            ChapterObject,
+        > = BTreeMap::new();
+        for chapter in chapters {
+            if self.rank(&chapter).is_none() {
+                continue;
+            }
+            let volume = self
+                .by_volume
+                .then(|| VolumeNumber::from(chapter.attributes.volume.as_deref()));
+            let number = ChapterNumber::from(chapter.attributes.chapter.as_deref());
+            let key = if number.is_unnumbered() {
+                (volume, number, Some(chapter.id))
+            } else {
+                (volume, number, None)
+            };
+            match picked.get(&key) {
+                Some(current) if self.rank(current) <= self.rank(&chapter) => {}
+                _ => {
+                    picked.insert(key, chapter);
+                }
+            }
+        }
+        picked.into_values().collect()
+    }
+
+    /// The sort key of the chapter, lower is better, or `None` if the chapter is excluded.
+    fn rank(&self, chapter: &ChapterObject) -> Option<Rank> {
+        let attributes = &chapter.attributes;
+        let language = if self.languages.is_empty() {
+            0
+        } else {
+            self.languages
+                .iter()
+                .position(|language| *language == attributes.translated_language)?
+        };
+        let groups: Vec<Uuid> = chapter
+            .find_relationships(RelationshipType::ScanlationGroup)
+            .into_iter()
+            .map(|rel| rel.id)
+            .collect();
+        if groups.iter().any(|id| self.excluded_groups.contains(id)) {
+            return None;
+        }
+        let group = groups
+            .iter()
+            .filter_map(|id| self.groups.iter().position(|group| group == id))
+            .min()
+            .unwrap_or(self.groups.len());
+        Some((
+            language,
+            group,
+            attributes.is_unavailable,
+            attributes.external_url.is_some(),
+            attributes.publish_at.is_none(),
+            attributes.publish_at,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_types::{Language, RelationshipType};
+    use uuid::Uuid;
+
+    use super::ChapterPreference;
+    use crate::v5::{ApiObject, ChapterAttributes, ChapterObject, Relationship};
+
+    fn chapter(id: u128, number: &str, language: Language, group: u128) -> ChapterObject {
+        let (volume, number) = number.split_once(':').unwrap_or(("none", number));
+        let attributes = ChapterAttributes {
+            volume: Some(volume.to_string()),
+            chapter: Some(number.to_string()),
+            translated_language: language,
+            ..Default::default()
+        };
+        let mut chapter =
+            ApiObject::new(Uuid::from_u128(id), RelationshipType::Chapter, attributes);
+        chapter.relationships = vec![Relationship {
+            id: Uuid::from_u128(group),
+            type_: RelationshipType::ScanlationGroup,
+            related: None,
+            attributes: None,
+        }];
+        chapter
+    }
+
+    fn preference() -> ChapterPreference {
+        ChapterPreference::new()
+            .language(Language::French)
+            .language(Language::English)
+            .group(Uuid::from_u128(20))
+            .exclude_group(Uuid::from_u128(30))
+    }
+
+    fn chapters() -> Vec<ChapterObject> {
+        vec![
+            chapter(1, "2", Language::English, 10),
+            chapter(2, "2", Language::English, 20),
+            chapter(3, "1", Language::English, 20),
+            chapter(4, "1", Language::French, 10),
+            chapter(5, "3", Language::English, 30),
+            chapter(6, "4", Language::Japanese, 10),
+            chapter(7, "none", Language::English, 10),
+            chapter(8, "none", Language::English, 10),
+            chapter(9, "2:1", Language::English, 10),
+            chapter(10, "1:1", Language::English, 10),
+        ]
+    }
+
+    fn picked_ids(preference: ChapterPreference) -> Vec<Uuid> {
+        preference
+            .canonical(chapters())
+            .into_iter()
+            .map(|chapter| chapter.id)
+            .collect()
+    }
+
+    #[test]
+    fn one_chapter_is_picked_per_number() {
+        assert_eq!(
+            picked_ids(preference()),
+            [4, 2, 7, 8].map(Uuid::from_u128),
+            "the french chapter 1 whatever its volume, the preferred group for the chapter 2 \
+            and the unnumbered chapters"
+        );
+    }
+
+    #[test]
+    fn one_chapter_is_picked_per_volume_and_number() {
+        assert_eq!(
+            picked_ids(preference().by_volume()),
+            [10, 9, 4, 2, 7, 8].map(Uuid::from_u128),
+            "the chapters 1 of the volumes 1 and 2, then without volume the french chapter 1, \
+            the preferred group for the chapter 2 and the unnumbered chapters"
+        );
+    }
+}
@@ -1,7 +1,8 @@
 #[cfg(feature = "serialize")]
 use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use mangadex_api_types::ResultType;
+use mangadex_api_types::{ChapterNumber, ResultType, VolumeNumber};
 #[cfg(feature = "serialize")]
 use serde::Serialize;
 
@@ -121,3 +122,166 @@ pub struct ChapterAggregate {
     #[serde(default)]
     pub is_unavailable: bool,
 }
+
+impl MangaAggregate {
+    /// The chapters in reading order.
+    ///
+    /// The volumes and the chapters are sorted by their [`ChapterNumber`],
+    /// so the chapters without volume come last.
+    pub fn chapters(&self) -> Vec<&ChapterAggregate> {
+        let mut volumes: Vec<&VolumeAggregate> = self.volumes.iter().collect();
+        volumes.sort_by_cached_key(|volume| volume.number());
+        volumes
+            .into_iter()
+            .flat_map(|volume| {
+                let mut chapters: Vec<&ChapterAggregate> = volume.chapters.iter().collect();
+                chapters.sort_by_cached_key(|chapter| chapter.number());
+                chapters
+            })
+            .collect()
+    }
+
+    /// The whole chapter numbers missing between the first and the last numbered chapter.
+    ///
+    /// The result is meaningless for the manga restarting the chapter numbers on each volume.
+    pub fn gaps(&self) -> Vec<u32> {
+        let numbers: BTreeSet<u32> = self
+            .volumes
+            .iter()
+            .flat_map(|volume| &volume.chapters)
+            .filter_map(|chapter| chapter.number().whole())
+            .collect();
+        match (numbers.first(), numbers.last()) {
+            (Some(&first), Some(&last)) => (first..last)
+                .filter(|number| !numbers.contains(number))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The chapter numbers with more than one chapter, usually from several scanlation groups
+    /// or in several languages, with the ids of all these chapters.
+    ///
+    /// The unnumbered chapters are not duplicates of each other.
+    pub fn duplicates(&self) -> Vec<(ChapterNumber, Vec<Uuid>)> {
+        let mut chapters: BTreeMap<ChapterNumber, Vec<Uuid>> = BTreeMap::new();
+        for chapter in self.volumes.iter().flat_map(|volume| &volume.chapters) {
+            let number = chapter.number();
+            if number.is_unnumbered() {
+                continue;
+            }
+            let ids = chapters.entry(number).or_default();
+            for id in chapter.ids() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        chapters
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .collect()
+    }
+}
+
+impl VolumeAggregate {
+    /// The parsed volume number.
+    pub fn number(&self) -> VolumeNumber {
+        self.volume.as_str().into()
+    }
+}
+
+impl ChapterAggregate {
+    /// The parsed chapter number.
+    pub fn number(&self) -> ChapterNumber {
+        self.chapter.as_str().into()
+    }
+
+    /// The id of the chapter followed by the ids of the [`others`](Self::others) chapters.
+    pub fn ids(&self) -> impl Iterator<Item = Uuid> + '_ {
+        std::iter::once(self.id).chain(self.others.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::MangaAggregate;
+
+    #[test]
+    fn chapters_gaps_and_duplicates() {
+        let aggregate: MangaAggregate = serde_json::from_value(serde_json::json!({
+            "result": "ok",
+            "volumes": {
+                "none": {
+                    "volume": "none",
+                    "count": 1,
+                    "chapters": {
+                        "10.5": {
+                            "chapter": "10.5",
+                            "id": Uuid::from_u128(6),
+                            "others": [],
+                            "count": 1
+                        }
+                    }
+                },
+                "2": {
+                    "volume": "2",
+                    "count": 1,
+                    "chapters": {
+                        "Extra": {
+                            "chapter": "Extra",
+                            "id": Uuid::from_u128(5),
+                            "others": [],
+                            "count": 1
+                        },
+                        "5": {
+                            "chapter": "5",
+                            "id": Uuid::from_u128(4),
+                            "others": [],
+                            "count": 1
+                        }
+                    }
+                },
+                "1": {
+                    "volume": "1",
+                    "count": 3,
+                    "chapters": {
+                        "1.25": {
+                            "chapter": "1.25",
+                            "id": Uuid::from_u128(3),
+                            "others": [],
+                            "count": 1
+                        },
+                        "1.5": {
+                            "chapter": "1.5",
+                            "id": Uuid::from_u128(2),
+                            "others": [],
+                            "count": 1
+                        },
+                        "1": {
+                            "chapter": "1",
+                            "id": Uuid::from_u128(1),
+                            "others": [Uuid::from_u128(11)],
+                            "count": 2
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let chapters: Vec<&str> = aggregate
+            .chapters()
+            .into_iter()
+            .map(|chapter| chapter.chapter.as_str())
+            .collect();
+        assert_eq!(chapters, ["1", "1.25", "1.5", "5", "Extra", "10.5"]);
+        assert_eq!(aggregate.gaps(), [2, 3, 4, 6, 7, 8, 9]);
+        let duplicates = aggregate.duplicates();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].0.to_string(), "1");
+        assert_eq!(duplicates[0].1, [Uuid::from_u128(1), Uuid::from_u128(11)]);
+    }
+}
@@ -39,3 +39,66 @@ pub struct ClientInfo {
     pub client_id: String,
     pub client_secret: String,
 }
+
+/// The OpenID Connect user info of the session.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub struct UserInfo {
+    /// The user ID.
+    pub sub: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// The claims of an access token.
+///
+/// The token signature is not verified when they are decoded.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub struct AccessTokenClaims {
+    /// The user ID.
+    pub sub: String,
+    /// Expiration time, in seconds since the Unix epoch.
+    pub exp: i64,
+    /// Issue time, in seconds since the Unix epoch.
+    pub iat: Option<i64>,
+    /// The issuer, the MangaDex realm URL.
+    pub iss: Option<String>,
+    /// The client the token was issued to.
+    pub azp: Option<String>,
+    /// The Keycloak session ID.
+    pub sid: Option<String>,
+    pub scope: Option<String>,
+    pub preferred_username: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub realm_access: RealmAccess,
+}
+
+impl AccessTokenClaims {
+    /// Check if the token has `role`, in its `roles` or realm roles.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles
+            .iter()
+            .chain(&self.realm_access.roles)
+            .any(|other| other == role)
+    }
+}
+
+/// The Keycloak realm roles of an access token.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub struct RealmAccess {
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
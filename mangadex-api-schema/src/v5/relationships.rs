@@ -0,0 +1,162 @@
+//! Typed accessors for the relationships expanded with the `includes[]` parameter.
+//!
+//! <https://api.mangadex.org/docs/01-concepts/reference-expansion/>
+//!
+//! The accessors only return the expanded relationships,
+//! use [`ApiObject::find_relationships`] to get their ids.
+
+use mangadex_api_types::RelationshipType;
+
+use crate::v5::{
+    ApiObject, ApiObjectNoRelationships, AuthorAttributes, ChapterAttributes, CoverAttributes,
+    CustomListAttributes, MangaAttributes, ScanlationGroupAttributes, UserAttributes,
+};
+
+impl ApiObject<MangaAttributes> {
+    /// The expanded authors of the manga, with `includes[]=author`.
+    pub fn authors(&self) -> Vec<ApiObjectNoRelationships<AuthorAttributes>> {
+        self.expanded_relationships(RelationshipType::Author)
+    }
+
+    /// The expanded artists of the manga, with `includes[]=artist`.
+    pub fn artists(&self) -> Vec<ApiObjectNoRelationships<AuthorAttributes>> {
+        self.expanded_relationships(RelationshipType::Artist)
+    }
+
+    /// The expanded primary cover art of the manga, with `includes[]=cover_art`.
+    pub fn cover_art(&self) -> Option<ApiObjectNoRelationships<CoverAttributes>> {
+        self.expanded_relationships(RelationshipType::CoverArt)
+            .into_iter()
+            .next()
+    }
+
+    /// The expanded user who created the manga, with `includes[]=creator`.
+    pub fn creator(&self) -> Option<ApiObjectNoRelationships<UserAttributes>> {
+        self.expanded_relationships(RelationshipType::Creator)
+            .into_iter()
+            .next()
+    }
+}
+
+impl ApiObject<ChapterAttributes> {
+    /// The expanded manga of the chapter, with `includes[]=manga`.
+    pub fn manga(&self) -> Option<ApiObjectNoRelationships<MangaAttributes>> {
+        self.expanded_relationships(RelationshipType::Manga)
+            .into_iter()
+            .next()
+    }
+
+    /// The expanded groups of the chapter, with `includes[]=scanlation_group`.
+    pub fn scanlation_groups(&self) -> Vec<ApiObjectNoRelationships<ScanlationGroupAttributes>> {
+        self.expanded_relationships(RelationshipType::ScanlationGroup)
+    }
+
+    /// The expanded uploader of the chapter, with `includes[]=user`.
+    pub fn uploader(&self) -> Option<ApiObjectNoRelationships<UserAttributes>> {
+        self.expanded_relationships(RelationshipType::User)
+            .into_iter()
+            .next()
+    }
+}
+
+impl ApiObject<CoverAttributes> {
+    /// The expanded manga of the cover art, with `includes[]=manga`.
+    pub fn manga(&self) -> Option<ApiObjectNoRelationships<MangaAttributes>> {
+        self.expanded_relationships(RelationshipType::Manga)
+            .into_iter()
+            .next()
+    }
+
+    /// The expanded uploader of the cover art, with `includes[]=user`.
+    pub fn uploader(&self) -> Option<ApiObjectNoRelationships<UserAttributes>> {
+        self.expanded_relationships(RelationshipType::User)
+            .into_iter()
+            .next()
+    }
+}
+
+impl ApiObject<AuthorAttributes> {
+    /// The expanded manga of the author, with `includes[]=manga`.
+    pub fn works(&self) -> Vec<ApiObjectNoRelationships<MangaAttributes>> {
+        self.expanded_relationships(RelationshipType::Manga)
+    }
+}
+
+impl ApiObject<ScanlationGroupAttributes> {
+    /// The expanded leader of the group, with `includes[]=leader`.
+    pub fn leader(&self) -> Option<ApiObjectNoRelationships<UserAttributes>> {
+        self.expanded_relationships(RelationshipType::Leader)
+            .into_iter()
+            .next()
+    }
+
+    /// The expanded members of the group, with `includes[]=member`.
+    pub fn members(&self) -> Vec<ApiObjectNoRelationships<UserAttributes>> {
+        self.expanded_relationships(RelationshipType::Member)
+    }
+}
+
+impl ApiObject<CustomListAttributes> {
+    /// The expanded owner of the custom list, with `includes[]=user`.
+    pub fn owner(&self) -> Option<ApiObjectNoRelationships<UserAttributes>> {
+        self.expanded_relationships(RelationshipType::User)
+            .into_iter()
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_types::RelationshipType;
+    use uuid::Uuid;
+
+    use crate::v5::{
+        ApiObject, ChapterAttributes, MangaAttributes, RelatedAttributes, Relationship,
+    };
+
+    fn relationship(
+        id: u128,
+        type_: RelationshipType,
+        attributes: Option<RelatedAttributes>,
+    ) -> Relationship {
+        Relationship {
+            id: Uuid::from_u128(id),
+            type_,
+            related: None,
+            attributes,
+        }
+    }
+
+    #[test]
+    fn only_expanded_relationships_are_returned() {
+        let manga = relationship(
+            1,
+            RelationshipType::Manga,
+            Some(RelatedAttributes::Manga(MangaAttributes::default())),
+        );
+        let mut chapter = ApiObject::new(
+            Uuid::from_u128(0),
+            RelationshipType::Chapter,
+            ChapterAttributes::default(),
+        );
+        chapter.relationships = vec![
+            manga.clone(),
+            relationship(2, RelationshipType::ScanlationGroup, None),
+            relationship(3, RelationshipType::User, None),
+        ];
+
+        assert_eq!(chapter.manga().map(|manga| manga.id), Some(manga.id));
+        assert_eq!(
+            chapter.manga().map(|manga| manga.type_),
+            Some(RelationshipType::Manga)
+        );
+        assert!(chapter.scanlation_groups().is_empty());
+        assert!(chapter.uploader().is_none());
+        assert_eq!(
+            chapter
+                .find_relationships(RelationshipType::ScanlationGroup)
+                .len(),
+            1
+        );
+    }
+}
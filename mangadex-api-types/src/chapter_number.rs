@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A parsed chapter or volume number, like `10`, `10.5`, `10a`, `Extra` or `none`.
+///
+/// The numbers are totally ordered:
+///
+/// 1. the numbered chapters, by their decimal value, their sub-parts then their suffix:
+///    `1` < `1a` < `1.05` < `1.5` < `1.5.1` < `2`,
+/// 2. the special chapters, by their name: `Extra` < `Oneshot`,
+/// 3. the unnumbered chapters (`none` or empty).
+///
+/// Two numbers with the same value are equal even if they are written differently,
+/// like `01` and `1`, but [`Display`] keeps the original text.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ChapterNumber {
+    /// A chapter starting with a number.
+    Numbered {
+        /// The whole part of the number: `10` for `10.25`.
+        whole: u32,
+        /// The decimal digits of the number without their trailing zeros: `"25"` for `10.25`.
+        fraction: String,
+        /// The dot separated parts after the decimal ones: `[1]` for `10.5.1`.
+        sub_parts: Vec<u32>,
+        /// The text after the number, like the `a` of `10a`.
+        suffix: Option<String>,
+        /// The number as written by the uploader.
+        text: String,
+    },
+    /// A chapter with a name instead of a number.
+    Special(String),
+    /// A chapter without number.
+    Unnumbered,
+}
+
+/// A parsed volume number, ordered like the chapter numbers.
+pub type VolumeNumber = ChapterNumber;
+
+impl ChapterNumber {
+    pub fn parse(number: &str) -> Self {
+        let number = number.trim();
+        if number.is_empty() || number.eq_ignore_ascii_case("none") {
+            return Self::Unnumbered;
+        }
+        let end = number
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(number.len());
+        let (prefix, suffix) = number.split_at(end);
+        let mut parts = prefix.split('.');
+        let whole = parts.next().and_then(|whole| whole.parse().ok());
+        let fraction = parts.next().unwrap_or_default().trim_end_matches('0');
+        let sub_parts: Option<Vec<u32>> = parts
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse().ok())
+            .collect();
+        match (whole, sub_parts) {
+            (Some(whole), Some(sub_parts)) => {
+                let suffix = suffix.trim();
+                Self::Numbered {
+                    whole,
+                    fraction: fraction.to_string(),
+                    sub_parts,
+                    suffix: (!suffix.is_empty()).then(|| suffix.to_string()),
+                    text: number.to_string(),
+                }
+            }
+            _ => Self::Special(number.to_string()),
+        }
+    }
+
+    /// The whole number of a numbered chapter: `10` for `10.5`.
+    pub fn whole(&self) -> Option<u32> {
+        match self {
+            Self::Numbered { whole, .. } => Some(*whole),
+            _ => None,
+        }
+    }
+
+    pub fn is_numbered(&self) -> bool {
+        matches!(self, Self::Numbered { .. })
+    }
+
+    pub fn is_unnumbered(&self) -> bool {
+        matches!(self, Self::Unnumbered)
+    }
+
+    /// The value compared by the ordering, without the original text.
+    fn key(&self) -> (u8, u32, &str, &[u32], Option<&str>) {
+        match self {
+            Self::Numbered {
+                whole,
+                fraction,
+                sub_parts,
+                suffix,
+                ..
+            } => (0, *whole, fraction, sub_parts, suffix.as_deref()),
+            Self::Special(name) => (1, 0, name, &[], None),
+            Self::Unnumbered => (2, 0, "", &[], None),
+        }
+    }
+}
+
+impl PartialEq for ChapterNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ChapterNumber {}
+
+impl PartialOrd for ChapterNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ChapterNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for ChapterNumber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl From<&str> for ChapterNumber {
+    fn from(number: &str) -> Self {
+        Self::parse(number)
+    }
+}
+
+impl From<Option<&str>> for ChapterNumber {
+    fn from(number: Option<&str>) -> Self {
+        number.map_or(Self::Unnumbered, Self::parse)
+    }
+}
+
+impl FromStr for ChapterNumber {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(s))
+    }
+}
+
+impl Display for ChapterNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Numbered { text, .. } | Self::Special(text) => f.write_str(text),
+            Self::Unnumbered => f.write_str("none"),
+        }
+    }
+}
+
+impl Serialize for ChapterNumber {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Unnumbered => serializer.serialize_none(),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChapterNumber {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let number: Option<String> = Deserialize::deserialize(deserializer)?;
+        Ok(number.as_deref().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChapterNumber;
+
+    #[test]
+    fn chapter_numbers_are_totally_ordered() {
+        let mut numbers: Vec<ChapterNumber> = [
+            "none", "Extra", "10", "2", "10.75", "1a", "10.5", "", "10.25", "Oneshot", "0", "1",
+            "1.5.1",
+        ]
+        .into_iter()
+        .map(ChapterNumber::parse)
+        .collect();
+        numbers.sort();
+        let numbers: Vec<String> = numbers.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            numbers,
+            [
+                "0", "1", "1a", "1.5.1", "2", "10", "10.25", "10.5", "10.75", "Extra", "Oneshot",
+                "none", "none"
+            ]
+        );
+    }
+
+    #[test]
+    fn chapter_numbers_are_parsed() {
+        assert_eq!(ChapterNumber::parse("10.5b").whole(), Some(10));
+        assert!(ChapterNumber::parse("1.05") < ChapterNumber::parse("1.5"));
+        assert_eq!(ChapterNumber::parse("1.50"), ChapterNumber::parse("1.5"));
+        assert_eq!(ChapterNumber::parse("01"), ChapterNumber::parse("1"));
+        assert_eq!(ChapterNumber::parse("01").to_string(), "01");
+        assert_eq!(
+            ChapterNumber::parse(".5"),
+            ChapterNumber::Special(".5".to_string())
+        );
+        assert_eq!(
+            serde_json::from_str::<ChapterNumber>("null").unwrap(),
+            ChapterNumber::Unnumbered
+        );
+    }
+}
@@ -3,6 +3,7 @@
 
 pub mod api_client_profile;
 pub mod api_client_state;
+pub mod chapter_number;
 pub mod error;
 pub mod forum_thread;
 pub mod include_empty_pages;
@@ -32,6 +33,7 @@ pub mod username;
 
 pub use api_client_profile::ApiClientProfile;
 pub use api_client_state::ApiClientState;
+pub use chapter_number::{ChapterNumber, VolumeNumber};
 pub use forum_thread::ForumThreadType;
 pub use include_empty_pages::IncludeFuturePages;
 pub use include_external_url::IncludeExternalUrl;
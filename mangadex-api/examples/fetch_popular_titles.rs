@@ -44,9 +44,7 @@ async fn main() -> anyhow::Result<()> {
     let not_found = String::from("Not found");
 
     // Just a simple index :3
-    let mut index = 1;
-
-    for manga in res.data {
+    for (index, manga) in (1..).zip(res.data) {
         // Find the English title
         let title = manga
             .attributes
@@ -98,7 +96,6 @@ async fn main() -> anyhow::Result<()> {
 
         println!("\tLink: {title_link}");
         println!();
-        index += 1;
     }
 
     println!("Done :3");
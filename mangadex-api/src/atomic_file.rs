@@ -0,0 +1,95 @@
+//! Replace files atomically.
+//!
+//! The content is written to a temporary file next to the destination, flushed to the disk,
+//! then renamed over the destination, so a crash never leaves a truncated file behind.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The temporary file written before being renamed to `path`: `path` with a `.tmp` suffix.
+pub(crate) fn temporary_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.to_path_buf().into_os_string();
+    tmp_path.push(".tmp");
+    PathBuf::from(tmp_path)
+}
+
+/// Replace the file at `path` with `content`.
+pub(crate) fn write(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    write_with(path, content, false)
+}
+
+/// Replace the file at `path` with `content`, readable only by its owner on Unix.
+pub(crate) fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    write_with(path, content, true)
+}
+
+fn write_with(path: &Path, content: &[u8], private: bool) -> std::io::Result<()> {
+    let tmp_path = temporary_path(path);
+    let result = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)
+        .and_then(|mut file| {
+            if private {
+                restrict_to_owner(&file)?;
+            }
+            file.write_all(content)?;
+            Ok(file)
+        });
+    match result {
+        Ok(file) => persist(file, &tmp_path, path),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Make `file` readable only by its owner, even if it existed before being opened.
+#[cfg(unix)]
+fn restrict_to_owner(file: &File) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_to_owner(_: &File) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Flush `file`, written at `tmp_path`, then move it to `path`.
+///
+/// The temporary file is removed if it cannot be moved.
+pub(crate) fn persist(file: File, tmp_path: &Path, path: &Path) -> std::io::Result<()> {
+    let result = file
+        .sync_all()
+        .and_then(|_| std::fs::rename(tmp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(tmp_path);
+    }
+    result
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::{temporary_path, write_private};
+
+    #[test]
+    fn private_files_ignore_the_permissions_of_a_stale_temporary_file() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::new_v4()));
+        let tmp_path = temporary_path(&path);
+        std::fs::write(&tmp_path, "stale")?;
+        std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o644))?;
+
+        write_private(&path, b"secret")?;
+        let mode = std::fs::metadata(&path)?.permissions().mode();
+        std::fs::remove_file(&path)?;
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!tmp_path.exists());
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use std::fmt::Display;
 
 use derive_builder::UninitializedFieldError;
-use mangadex_api_schema::error::RelationshipConversionError;
+use mangadex_api_schema::error::{ApiErrorKind, MangaDexError, RelationshipConversionError};
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -15,6 +15,10 @@ pub enum Error {
     #[error("there was an error from the MangaDex servers (HTTP {0}): {1}")]
     ServerError(u16, String),
 
+    /// The request was rejected by the servers (HTTP 4xx).
+    #[error("the request was rejected by the MangaDex servers (HTTP {0}): {1}")]
+    ClientError(u16, String),
+
     #[error("failed to send a request to MangaDex: {0:?}")]
     RequestError(#[from] reqwest::Error),
 
@@ -34,6 +38,12 @@ pub enum Error {
     #[error("missing captcha; please insert it or solve a captcha")]
     MissingCaptcha,
 
+    /// A captcha must be solved with this site key.
+    ///
+    /// Returned by the [`CaptchaSolver`](crate::http_client::captcha::CaptchaSolver)s giving up.
+    #[error("a captcha must be solved with the site key {0}")]
+    CaptchaRequired(String),
+
     #[error("an error occurred while pinging the MangaDex server")]
     PingError,
 
@@ -67,6 +77,16 @@ pub enum Error {
     #[error(transparent)]
     Types(#[from] mangadex_api_types::error::Error),
 
+    /// No interaction of the VCR cassette matches the request.
+    #[error("no recorded interaction matches {0}")]
+    UnmatchedInteraction(String),
+
+    #[error("the image {0} is invalid: {1}")]
+    InvalidImage(String, String),
+
+    #[error("an upload session {0} already exists")]
+    UploadSessionAlreadyExists(uuid::Uuid),
+
     #[error("This file {0} was skipped")]
     SkippedDownload(String),
 
@@ -81,6 +101,23 @@ pub enum Error {
     #[error("Got Oauth Error response (status: {}, reason: {})", .code, reason.as_ref().map_or("...", |v| v))]
     OauthError { code: u16, reason: Option<String> },
 
+    /// The authorization redirect reports an error or doesn't match the authorization request.
+    #[cfg(feature = "oauth")]
+    #[error("the authorization failed: {0}")]
+    AuthorizationError(String),
+
+    /// The account was not added to the [`ClientPool`](crate::pool::ClientPool).
+    #[error("the account {0} is not in the pool")]
+    UnknownAccount(String),
+
+    /// The [`TokenStore`](crate::token_store::TokenStore) failed to load or save the tokens.
+    #[error("the token store failed: {0}")]
+    TokenStore(#[source] std::sync::Arc<Error>),
+
+    /// The chapter number is not in the aggregate of the manga.
+    #[error("the chapter {0} was not found in the manga")]
+    UnknownChapter(String),
+
     #[error("{0}")]
     UnknowSource(String),
 }
@@ -92,6 +129,98 @@ impl Error {
     {
         Self::UnknowSource(source.into())
     }
+
+    /// The errors returned by the MangaDex API, empty for the other errors.
+    pub fn api_errors(&self) -> &[MangaDexError] {
+        match self {
+            Self::Api(error) => &error.errors,
+            _ => &[],
+        }
+    }
+
+    /// The kinds of the errors returned by the MangaDex API.
+    pub fn api_error_kinds(&self) -> impl Iterator<Item = ApiErrorKind> + '_ {
+        self.api_errors().iter().map(MangaDexError::kind)
+    }
+
+    fn has_api_error(&self, predicate: impl Fn(&ApiErrorKind) -> bool) -> bool {
+        self.api_error_kinds().any(|kind| predicate(&kind))
+    }
+
+    /// The HTTP status of the [`ServerError`](Self::ServerError)s
+    /// and the [`ClientError`](Self::ClientError)s.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::ServerError(status, _) | Self::ClientError(status, _) => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// The requested resource doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404) || self.has_api_error(|kind| *kind == ApiErrorKind::NotFound)
+    }
+
+    /// The user isn't allowed to do this.
+    pub fn is_forbidden(&self) -> bool {
+        self.status() == Some(403) || self.has_api_error(|kind| *kind == ApiErrorKind::Forbidden)
+    }
+
+    /// The session is missing or expired.
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, Self::MissingTokens)
+            || self.status() == Some(401)
+            || self.has_api_error(|kind| *kind == ApiErrorKind::Unauthorized)
+    }
+
+    /// A field of the request was rejected.
+    pub fn is_validation_error(&self) -> bool {
+        self.has_api_error(|kind| matches!(kind, ApiErrorKind::Validation { .. }))
+    }
+
+    /// The request conflicts with an existing resource.
+    pub fn is_conflict(&self) -> bool {
+        self.status() == Some(409) || self.has_api_error(|kind| *kind == ApiErrorKind::Conflict)
+    }
+
+    /// A captcha must be solved before retrying the request.
+    ///
+    /// See [`Error::captcha_site_key`].
+    pub fn is_captcha_required(&self) -> bool {
+        matches!(self, Self::MissingCaptcha | Self::CaptchaRequired(_))
+            || self.has_api_error(|kind| matches!(kind, ApiErrorKind::CaptchaRequired { .. }))
+    }
+
+    /// The site key of the captcha to solve, if one is required.
+    pub fn captcha_site_key(&self) -> Option<String> {
+        if let Self::CaptchaRequired(site_key) = self {
+            return Some(site_key.clone());
+        }
+        self.api_error_kinds().find_map(|kind| match kind {
+            ApiErrorKind::CaptchaRequired { site_key } => site_key,
+            _ => None,
+        })
+    }
+
+    /// The user already has an upload session.
+    pub fn is_upload_session_exists(&self) -> bool {
+        matches!(self, Self::UploadSessionAlreadyExists(_))
+            || self.has_api_error(|kind| *kind == ApiErrorKind::UploadSessionExists)
+    }
+
+    /// The rate limit was exceeded.
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, Self::RateLimitExcedeed)
+            || self.status() == Some(429)
+            || self.has_api_error(|kind| *kind == ApiErrorKind::RateLimited)
+    }
+
+    /// The MangaDex servers failed or are unavailable.
+    pub fn is_server_error(&self) -> bool {
+        matches!(self, Self::ServerError(status, _) if (500..600).contains(status))
+            || matches!(self, Self::ServiceUnavailable(_))
+            || self.has_api_error(|kind| *kind == ApiErrorKind::Server)
+    }
 }
 
 impl serde::Serialize for Error {
@@ -134,3 +263,50 @@ impl Display for BuilderError {
     }
 }
 impl std::error::Error for BuilderError {}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_schema::error::MangaDexErrorResponse_;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn api_errors_are_classified() -> anyhow::Result<()> {
+        let error = Error::Api(serde_json::from_value::<MangaDexErrorResponse_>(json!({
+            "result": "error",
+            "errors": [{
+                "id": "9e2f6f3c-4a76-4c4e-9d5b-2e8b8a4f7a10",
+                "status": 403,
+                "title": "captcha_required_exception",
+                "detail": "Captcha required",
+                "context": {"siteKey": "someSiteKey"}
+            }]
+        }))?);
+        assert!(error.is_captcha_required());
+        assert_eq!(error.captcha_site_key().as_deref(), Some("someSiteKey"));
+        assert!(!error.is_not_found());
+        assert!(!error.is_forbidden());
+
+        assert!(Error::RateLimitExcedeed.is_rate_limited());
+        assert!(Error::ServerError(502, String::new()).is_server_error());
+        let not_found = Error::ServerError(404, String::new());
+        assert!(!not_found.is_server_error());
+        assert!(not_found.is_not_found());
+        assert!(Error::ClientError(403, String::new()).is_forbidden());
+        assert!(Error::MissingTokens.api_errors().is_empty());
+
+        let validation = Error::Api(serde_json::from_value::<MangaDexErrorResponse_>(json!({
+            "result": "error",
+            "errors": [{
+                "id": "0b6c8d2e-5f1a-4b7e-8c3d-9a4e1f2b3c4d",
+                "status": 400,
+                "title": "validation_exception",
+                "detail": "Error validating /title: String value found, but an object is required"
+            }]
+        }))?);
+        assert!(validation.is_validation_error());
+        assert!(!Error::ClientError(400, String::new()).is_validation_error());
+        Ok(())
+    }
+}
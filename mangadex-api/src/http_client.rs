@@ -1,21 +1,41 @@
+pub mod cache;
+pub mod captcha;
+pub mod middleware;
+pub mod retry;
+pub(crate) mod route;
+pub mod vcr;
+
 use std::sync::Arc;
 
 use derive_builder::Builder;
 use mangadex_api_schema::v5::oauth::ClientInfo;
+#[cfg(feature = "oauth")]
+use mangadex_api_schema::v5::oauth::OAuthTokenResponse;
 use mangadex_api_schema::ApiResult;
 use reqwest::{Client, Response, StatusCode};
 use serde::de::DeserializeOwned;
+#[cfg(feature = "oauth")]
+use time::OffsetDateTime;
 use tokio::sync::RwLock;
 use url::Url;
 
 use crate::error::Error;
-use crate::rate_limit::Limited;
+use crate::http_client::cache::{CachedResponse, ResponseCache};
+use crate::http_client::captcha::CaptchaSolver;
+use crate::http_client::middleware::Middleware;
+use crate::http_client::retry::RetryPolicy;
+use crate::http_client::vcr::Vcr;
+use crate::observe::RequestObserver;
+use crate::rate_limit::{Limited, RateLimit, RateLimiter};
+use crate::token_store::{self, StoredTokens, TokenStore};
 use crate::v5::AuthTokens;
 use crate::{
     traits::{Endpoint, FromResponse, UrlSerdeQS},
     Result,
 };
 use crate::{API_DEV_URL, API_URL};
+#[cfg(feature = "oauth")]
+use crate::{AUTH_DEV_URL, AUTH_URL};
 
 pub type HttpClientRef = Arc<RwLock<HttpClient>>;
 
@@ -32,6 +52,52 @@ pub struct HttpClient {
     captcha: Option<String>,
     #[cfg(feature = "oauth")]
     client_info: Option<ClientInfo>,
+    /// Base URL of the MangaDex OAuth server.
+    #[cfg(feature = "oauth")]
+    pub auth_url: Url,
+    /// Refresh the session automatically when it expires.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "oauth")]
+    auto_refresh: bool,
+    #[cfg(feature = "oauth")]
+    #[builder(setter(skip))]
+    session_expiration: Option<OffsetDateTime>,
+    /// Client-side rate limiter applied before each request.
+    ///
+    /// Disabled by default.
+    /// Use [`RateLimiter::default`] for the limits documented by MangaDex.
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Retry policy applied by the `send()` function of the endpoints.
+    ///
+    /// Disabled by default.
+    retry_policy: Option<RetryPolicy>,
+    /// Middlewares called around each request.
+    ///
+    /// Use [`HttpClientBuilder::middleware`] or [`HttpClient::add_middleware`] to stack them.
+    #[builder(setter(custom))]
+    middlewares: Vec<Arc<dyn Middleware>>,
+    /// Where the tokens are persisted.
+    ///
+    /// Use [`HttpClientBuilder::token_store`] or [`HttpClient::set_token_store`] to set it.
+    #[builder(setter(custom))]
+    token_store: Option<Arc<dyn TokenStore>>,
+    /// The error of the last load or save of the token store.
+    #[builder(setter(skip))]
+    token_store_error: Option<Arc<Error>>,
+    /// Cache for the read-only endpoints.
+    ///
+    /// Disabled by default.
+    cache: Option<Arc<ResponseCache>>,
+    /// Records the interactions to a cassette, or replays them.
+    ///
+    /// Disabled by default.
+    vcr: Option<Arc<Vcr>>,
+    /// Solves the captcha challenges before sending the request again.
+    ///
+    /// Use [`HttpClientBuilder::captcha_solver`] or [`HttpClient::set_captcha_solver`] to set it.
+    #[builder(setter(custom))]
+    captcha_solver: Option<Arc<dyn CaptchaSolver>>,
 }
 
 impl Default for HttpClient {
@@ -43,6 +109,20 @@ impl Default for HttpClient {
             captcha: None,
             #[cfg(feature = "oauth")]
             client_info: None,
+            #[cfg(feature = "oauth")]
+            auth_url: Url::parse(AUTH_URL).expect("error parsing the auth url"),
+            #[cfg(feature = "oauth")]
+            auto_refresh: false,
+            #[cfg(feature = "oauth")]
+            session_expiration: None,
+            rate_limiter: None,
+            retry_policy: None,
+            middlewares: Vec::new(),
+            token_store: None,
+            token_store_error: None,
+            cache: None,
+            vcr: None,
+            captcha_solver: None,
         }
     }
 }
@@ -91,6 +171,20 @@ impl HttpClient {
         endpoint: &E,
         base_url: &url::Url,
     ) -> Result<reqwest::Response>
+    where
+        E: Endpoint,
+    {
+        self.send_request_with_captcha(endpoint, base_url, self.get_captcha().map(String::as_str))
+            .await
+    }
+
+    /// Send the request to the endpoint with `captcha` instead of the captcha stored in the client.
+    pub(crate) async fn send_request_with_captcha<E>(
+        &self,
+        endpoint: &E,
+        base_url: &url::Url,
+        captcha: Option<&str>,
+    ) -> Result<reqwest::Response>
     where
         E: Endpoint,
     {
@@ -112,11 +206,87 @@ impl HttpClient {
             let tokens = self.get_tokens().ok_or(Error::MissingTokens)?;
             req = req.bearer_auth(&tokens.session);
         }
-        if let Some(captcha) = self.get_captcha() {
+        if let Some(captcha) = captcha {
             req = req.header("X-Captcha-Result", captcha);
         }
 
-        Ok(req.send().await?)
+        let method = endpoint.method();
+        let path = endpoint.path();
+        let request = req.build()?;
+        let url = request.url().clone();
+
+        let vcr = self.get_vcr();
+        if let Some(vcr) = vcr
+            && vcr.is_replaying()
+        {
+            return vcr.play(&request);
+        }
+        let recorded_request = vcr.and_then(|vcr| vcr.recorded_request(&request));
+
+        // Only the public endpoints are cached since the others depend on the user.
+        let cache = self
+            .get_cache()
+            .filter(|_| !endpoint.require_auth())
+            .and_then(|cache| Some((cache, cache.ttl(&method, &path)?)));
+        let cache_key = ResponseCache::key(&method, &url);
+        if let Some((cache, _)) = cache
+            && !cache::is_bypassed()
+            && let Some(cached) = cache.backend().get(&cache_key)
+        {
+            return Ok(cached.into());
+        }
+
+        let res = self.execute(request, &method, &path).await;
+        if let Err(e) = &res {
+            for middleware in self.middlewares.iter().rev() {
+                middleware.on_error(&method, &url, e);
+            }
+        }
+        let res = match (res, vcr.zip(recorded_request)) {
+            (Ok(res), Some((vcr, recorded_request))) => {
+                vcr.record_response(recorded_request, res).await
+            }
+            (res, _) => res,
+        };
+        match (res, cache) {
+            (Ok(res), Some((cache, ttl))) if res.status().is_success() => {
+                let cached = CachedResponse::read(res).await?;
+                cache.backend().insert(cache_key, cached.clone(), ttl);
+                Ok(cached.into())
+            }
+            (res, _) => res,
+        }
+    }
+
+    /// Run the middlewares and the rate limiter around the request.
+    async fn execute(
+        &self,
+        mut request: reqwest::Request,
+        method: &reqwest::Method,
+        path: &str,
+    ) -> Result<Response> {
+        if let Some(rate_limiter) = self.get_rate_limiter() {
+            rate_limiter.acquire(method, path).await;
+        }
+        for middleware in &self.middlewares {
+            middleware.before_request(&mut request)?;
+        }
+
+        let mut res = self.client.execute(request).await?;
+
+        if let Some(rate_limiter) = self.get_rate_limiter()
+            && let Ok(rate_limit) = <RateLimit as TryFrom<&Response>>::try_from(&res)
+        {
+            if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                rate_limiter.block(method, path, &rate_limit);
+            } else {
+                rate_limiter.update(method, path, &rate_limit);
+            }
+        }
+        for middleware in self.middlewares.iter().rev() {
+            middleware.after_response(&mut res)?;
+        }
+        Ok(res)
     }
 
     /// Send the request to the endpoint but don't deserialize the response.
@@ -134,15 +304,7 @@ impl HttpClient {
             .await
     }
 
-    pub(crate) async fn send_request_with_checks<E>(
-        &self,
-        endpoint: &E,
-    ) -> Result<reqwest::Response>
-    where
-        E: Endpoint,
-    {
-        let res = self.send_request_without_deserializing(endpoint).await?;
-
+    pub(crate) async fn check_response(res: Response) -> Result<Response> {
         let status_code = res.status();
 
         if status_code.as_u16() == 429 {
@@ -157,67 +319,17 @@ impl HttpClient {
         Ok(res)
     }
 
-    pub(crate) async fn handle_result<T>(&self, res: Response) -> Result<T>
-    where
-        T: DeserializeOwned,
-    {
-        /*let res_text = res.text().await?;
-        eprintln!("{}", res_text);
-        Ok(serde_json::from_str::<ApiResult<T>>(&res_text)
-        .map_err(|e| Error::UnexpectedError(anyhow::Error::msg(e.to_string())))?
-        .into_result()?)
-        */
-        Ok(res.json::<ApiResult<T>>().await?.into_result()?)
-    }
-
-    /// Send the request to the endpoint and deserialize the response body.
-    pub(crate) async fn send_request<E>(&self, endpoint: &E) -> Result<E::Response>
-    where
-        E: Endpoint,
-        <<E as Endpoint>::Response as FromResponse>::Response: DeserializeOwned,
-    {
-        let res = self.send_request_with_checks(endpoint).await?;
-
-        let res = res
-            .json::<<E::Response as FromResponse>::Response>()
-            .await?;
-
-        Ok(FromResponse::from_response(res))
-    }
-
-    /// Send the request to the endpoint and deserialize the response body.
-    pub(crate) async fn send_request_with_rate_limit<E>(
-        &self,
-        endpoint: &E,
-    ) -> Result<Limited<E::Response>>
-    where
-        E: Endpoint,
-        <<E as Endpoint>::Response as FromResponse>::Response: DeserializeOwned,
-    {
-        use crate::rate_limit::RateLimit;
-
-        let resp = self.send_request_with_checks(endpoint).await?;
-
-        let some_rate_limit = <RateLimit as TryFrom<&Response>>::try_from(&resp);
-
-        let res = self
-            .handle_result::<<E::Response as FromResponse>::Response>(resp)
-            .await?;
-
-        Ok(Limited {
-            rate_limit: some_rate_limit?,
-            body: FromResponse::from_response(res),
-        })
-    }
-
     /// Get the authentication tokens stored in the client.
     pub fn get_tokens(&self) -> Option<&AuthTokens> {
         self.auth_tokens.as_ref()
     }
 
     /// Set new authentication tokens into the client.
+    ///
+    /// This also forgets the expiration of the previous session.
     pub fn set_auth_tokens(&mut self, auth_tokens: &AuthTokens) {
-        self.auth_tokens = Some(auth_tokens.clone());
+        self.replace_auth_tokens(Some(auth_tokens.clone()));
+        self.persist_tokens();
     }
 
     /// Remove all authentication tokens from the client.
@@ -225,7 +337,17 @@ impl HttpClient {
     /// This is effectively the same as logging out, though will not remove the active session from
     /// the MangaDex server. Be sure to call the logout endpoint to ensure your session is removed.
     pub fn clear_auth_tokens(&mut self) {
-        self.auth_tokens = None;
+        self.replace_auth_tokens(None);
+        self.persist_tokens();
+    }
+
+    /// Replace the authentication tokens without saving them in the token store.
+    pub(crate) fn replace_auth_tokens(&mut self, auth_tokens: Option<AuthTokens>) {
+        self.auth_tokens = auth_tokens;
+        #[cfg(feature = "oauth")]
+        {
+            self.session_expiration = None;
+        }
     }
 
     /// Get the captcha solution stored in the client.
@@ -250,7 +372,8 @@ impl HttpClient {
     #[cfg(feature = "oauth")]
     #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
     pub fn set_client_info(&mut self, client_info: &ClientInfo) {
-        self.client_info = Some(client_info.clone());
+        self.replace_client_info(Some(client_info.clone()));
+        self.persist_tokens();
     }
 
     #[cfg(feature = "oauth")]
@@ -262,7 +385,244 @@ impl HttpClient {
     #[cfg(feature = "oauth")]
     #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
     pub fn clear_client_info(&mut self) {
-        self.client_info = None;
+        self.replace_client_info(None);
+        self.persist_tokens();
+    }
+
+    /// Replace the client info without saving it in the token store.
+    #[cfg(feature = "oauth")]
+    pub(crate) fn replace_client_info(&mut self, client_info: Option<ClientInfo>) {
+        self.client_info = client_info;
+    }
+
+    /// Get the date when the current session (access token) expires, if known.
+    #[cfg(feature = "oauth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
+    pub fn get_session_expiration(&self) -> Option<OffsetDateTime> {
+        self.session_expiration
+    }
+
+    /// Set the date when the current session (access token) expires.
+    ///
+    /// This is done by the login and refresh endpoints,
+    /// but it can be useful when restoring tokens saved somewhere else.
+    #[cfg(feature = "oauth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
+    pub fn set_session_expiration(&mut self, expiration: OffsetDateTime) {
+        self.session_expiration = Some(expiration);
+        self.persist_tokens();
+    }
+
+    /// Store the tokens returned by the OAuth token endpoint with their expiration.
+    ///
+    /// The tokens are not persisted, save the [`HttpClient::token_store_snapshot`]
+    /// once the client lock is released.
+    #[cfg(feature = "oauth")]
+    pub(crate) fn set_oauth_tokens(&mut self, res: &OAuthTokenResponse) {
+        self.auth_tokens = Some(From::from(res.clone()));
+        self.session_expiration =
+            Some(OffsetDateTime::now_utc() + time::Duration::seconds(res.expires_in.into()));
+    }
+
+    #[cfg(feature = "oauth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
+    pub fn is_auto_refresh_enabled(&self) -> bool {
+        self.auto_refresh
+    }
+
+    /// Enable or disable the automatic refresh of the session.
+    ///
+    /// When enabled, the session is refreshed shortly before its expiration
+    /// and when an authenticated request gets a `401 Unauthorized`.
+    /// The request is then sent again once.
+    #[cfg(feature = "oauth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
+    pub fn set_auto_refresh(&mut self, auto_refresh: bool) {
+        self.auto_refresh = auto_refresh;
+    }
+
+    /// Get the rate limiter used by the client.
+    pub fn get_rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Set a new rate limiter into the client.
+    ///
+    /// The same [`RateLimiter`] can be shared between several clients with an [`Arc`].
+    pub fn set_rate_limiter<T: Into<Arc<RateLimiter>>>(&mut self, rate_limiter: T) {
+        self.rate_limiter = Some(rate_limiter.into());
+    }
+
+    /// Remove the rate limiter from the client.
+    pub fn clear_rate_limiter(&mut self) {
+        self.rate_limiter = None;
+    }
+
+    /// Get the retry policy used by the client.
+    pub fn get_retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    /// Set a new retry policy into the client.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = Some(retry_policy);
+    }
+
+    /// Remove the retry policy from the client.
+    pub fn clear_retry_policy(&mut self) {
+        self.retry_policy = None;
+    }
+
+    /// Add a middleware on top of the existing ones.
+    pub fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middlewares.push(Arc::new(middleware));
+    }
+
+    /// Remove all the middlewares from the client.
+    pub fn clear_middlewares(&mut self) {
+        self.middlewares.clear();
+    }
+
+    pub fn get_token_store(&self) -> Option<&Arc<dyn TokenStore>> {
+        self.token_store.as_ref()
+    }
+
+    /// Set the store where the tokens are persisted.
+    ///
+    /// This doesn't load the stored tokens, use [`HttpClient::load_from_token_store`] for that.
+    pub fn set_token_store<S: TokenStore + 'static>(&mut self, token_store: S) {
+        self.token_store = Some(Arc::new(token_store));
+    }
+
+    pub fn clear_token_store(&mut self) {
+        self.token_store = None;
+    }
+
+    /// Load the tokens and the client info from the token store.
+    ///
+    /// Only the values that are not already set in the client are loaded.
+    pub fn load_from_token_store(&mut self) -> Result<()> {
+        let Some(stored) = self
+            .token_store
+            .as_ref()
+            .map(|store| store.load())
+            .transpose()?
+            .flatten()
+        else {
+            return Ok(());
+        };
+        if self.auth_tokens.is_none() {
+            self.auth_tokens = stored.auth_tokens;
+            #[cfg(feature = "oauth")]
+            {
+                self.session_expiration = stored.session_expiration;
+            }
+        }
+        #[cfg(feature = "oauth")]
+        if self.client_info.is_none() {
+            self.client_info = stored.client_info;
+        }
+        Ok(())
+    }
+
+    /// Save the tokens and the client info into the token store.
+    pub fn save_to_token_store(&self) -> Result<()> {
+        match self.token_store_snapshot() {
+            Some((store, stored)) => token_store::write(store.as_ref(), &stored),
+            None => Ok(()),
+        }
+    }
+
+    /// The token store and the tokens to save into it, if there is a token store.
+    pub(crate) fn token_store_snapshot(&self) -> Option<(Arc<dyn TokenStore>, StoredTokens)> {
+        let store = self.token_store.clone()?;
+        #[cfg(feature = "oauth")]
+        let stored = StoredTokens::new(
+            self.auth_tokens.clone(),
+            self.session_expiration,
+            self.client_info.clone(),
+        );
+        #[cfg(not(feature = "oauth"))]
+        let stored = StoredTokens::new(self.auth_tokens.clone(), None, None);
+        Some((store, stored))
+    }
+
+    /// Save the tokens in the token store.
+    ///
+    /// The errors are logged and kept, see [`HttpClient::get_token_store_error`].
+    fn persist_tokens(&mut self) {
+        let result = self.save_to_token_store();
+        self.record_token_store_result(result);
+    }
+
+    /// Keep the error of a load or a save of the token store, or forget the previous one.
+    pub(crate) fn record_token_store_result(&mut self, result: Result<()>) {
+        self.token_store_error = result.err().map(|error| {
+            crate::observe::token_store_failed(&error);
+            Arc::new(error)
+        });
+    }
+
+    /// The error of the last load or save of the token store, if it failed.
+    ///
+    /// The setters of the tokens and the client info save them without returning the errors.
+    pub fn get_token_store_error(&self) -> Option<&Error> {
+        self.token_store_error.as_deref()
+    }
+
+    /// Return the error of the last load or save of the token store, if it failed.
+    pub(crate) fn check_token_store(&self) -> Result<()> {
+        match &self.token_store_error {
+            Some(error) => Err(Error::TokenStore(error.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the response cache used by the client.
+    pub fn get_cache(&self) -> Option<&Arc<ResponseCache>> {
+        self.cache.as_ref()
+    }
+
+    /// Set a new response cache into the client.
+    ///
+    /// The same [`ResponseCache`] can be shared between several clients with an [`Arc`].
+    pub fn set_cache<T: Into<Arc<ResponseCache>>>(&mut self, cache: T) {
+        self.cache = Some(cache.into());
+    }
+
+    /// Remove the response cache from the client.
+    pub fn clear_cache(&mut self) {
+        self.cache = None;
+    }
+
+    /// Get the VCR used by the client.
+    pub fn get_vcr(&self) -> Option<&Arc<Vcr>> {
+        self.vcr.as_ref()
+    }
+
+    /// Set a new VCR into the client.
+    pub fn set_vcr<T: Into<Arc<Vcr>>>(&mut self, vcr: T) {
+        self.vcr = Some(vcr.into());
+    }
+
+    /// Remove the VCR from the client.
+    pub fn clear_vcr(&mut self) {
+        self.vcr = None;
+    }
+
+    /// Get the captcha solver used by the client.
+    pub fn get_captcha_solver(&self) -> Option<&Arc<dyn CaptchaSolver>> {
+        self.captcha_solver.as_ref()
+    }
+
+    /// Set a new captcha solver into the client.
+    pub fn set_captcha_solver<S: CaptchaSolver + 'static>(&mut self, captcha_solver: S) {
+        self.captcha_solver = Some(Arc::new(captcha_solver));
+    }
+
+    /// Remove the captcha solver from the client.
+    pub fn clear_captcha_solver(&mut self) {
+        self.captcha_solver = None;
     }
 
     pub fn api_dev_client() -> Self {
@@ -273,10 +633,217 @@ impl HttpClient {
             captcha: None,
             #[cfg(feature = "oauth")]
             client_info: None,
+            #[cfg(feature = "oauth")]
+            auth_url: Url::parse(AUTH_DEV_URL).expect("error parsing the auth url"),
+            #[cfg(feature = "oauth")]
+            auto_refresh: false,
+            #[cfg(feature = "oauth")]
+            session_expiration: None,
+            rate_limiter: None,
+            retry_policy: None,
+            middlewares: Vec::new(),
+            token_store: None,
+            token_store_error: None,
+            cache: None,
+            vcr: None,
+            captcha_solver: None,
+        }
+    }
+}
+
+impl HttpClientBuilder {
+    /// Set the store where the tokens are persisted.
+    pub fn token_store<S: TokenStore + 'static>(&mut self, token_store: S) -> &mut Self {
+        self.token_store = Some(Some(Arc::new(token_store)));
+        self
+    }
+
+    /// Add a middleware on top of the previous ones.
+    pub fn middleware<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middlewares
+            .get_or_insert_with(Vec::new)
+            .push(Arc::new(middleware));
+        self
+    }
+
+    /// Set the solver of the captcha challenges.
+    pub fn captcha_solver<S: CaptchaSolver + 'static>(&mut self, captcha_solver: S) -> &mut Self {
+        self.captcha_solver = Some(Some(Arc::new(captcha_solver)));
+        self
+    }
+}
+
+/// Save a [`HttpClient::token_store_snapshot`] on the blocking thread pool
+/// and keep the outcome in the client.
+pub(crate) async fn persist_token_snapshot(
+    http_client: &HttpClientRef,
+    snapshot: Option<(Arc<dyn TokenStore>, StoredTokens)>,
+) -> Result<()> {
+    let Some((store, tokens)) = snapshot else {
+        return Ok(());
+    };
+    let result = token_store::write_blocking(store, tokens).await;
+    let mut client = http_client.write().await;
+    client.record_token_store_result(result);
+    client.check_token_store()
+}
+
+/// Send the request with the checks and the retry policy of the client.
+///
+/// The lock is released between the attempts,
+/// so the client can still be updated while waiting for the next one.
+pub(crate) async fn send_with_checks<E>(
+    http_client: &HttpClientRef,
+    endpoint: &E,
+) -> Result<Response>
+where
+    E: Endpoint,
+{
+    let observer = RequestObserver::new(&endpoint.method(), &endpoint.path());
+    let mut attempts = 1;
+    let res = observer
+        .instrument(send_with_retries(
+            http_client,
+            endpoint,
+            &observer,
+            &mut attempts,
+        ))
+        .await;
+    observer.finish(attempts, &res);
+    res
+}
+
+async fn send_with_retries<E>(
+    http_client: &HttpClientRef,
+    endpoint: &E,
+    observer: &RequestObserver,
+    attempt: &mut u32,
+) -> Result<Response>
+where
+    E: Endpoint,
+{
+    let method = endpoint.method();
+    #[cfg(feature = "oauth")]
+    let mut refreshed = false;
+    let mut captcha_solved = false;
+    // A captcha solution is valid once, so it is only sent with the request following the solve.
+    let mut solved_captcha: Option<String> = None;
+    loop {
+        #[cfg(feature = "oauth")]
+        if endpoint.require_auth() {
+            // If this fails, the request is sent with the current session anyway.
+            let _ = crate::v5::oauth::refresh_token::refresh_if_expiring(http_client).await;
+        }
+        let (res, retry_policy) = {
+            let client = http_client.read().await;
+            #[cfg(feature = "oauth")]
+            let session = client
+                .get_tokens()
+                .filter(|_| client.is_auto_refresh_enabled() && endpoint.require_auth())
+                .map(|tokens| tokens.session.clone());
+            let res = match solved_captcha.take() {
+                Some(captcha) => {
+                    client
+                        .send_request_with_captcha(endpoint, &client.base_url, Some(&captcha))
+                        .await
+                }
+                None => client.send_request_without_deserializing(endpoint).await,
+            };
+            #[cfg(feature = "oauth")]
+            let res = match (res, session) {
+                (Ok(res), Some(session))
+                    if !refreshed && res.status() == StatusCode::UNAUTHORIZED =>
+                {
+                    drop(client);
+                    refreshed = true;
+                    crate::v5::oauth::refresh_token::refresh_session(http_client, &session).await?;
+                    continue;
+                }
+                (res, _) => res,
+            };
+            let solver = client
+                .get_captcha_solver()
+                .filter(|_| !captcha_solved)
+                .cloned();
+            drop(client);
+            let res = match (res, solver) {
+                (Ok(res), Some(solver)) if res.status() == StatusCode::FORBIDDEN => {
+                    let res = CachedResponse::read(res).await?;
+                    match captcha::site_key(res.status, &res.headers, &res.body) {
+                        Some(site_key) => {
+                            solved_captcha = Some(solver.solve(&site_key).await?);
+                            captcha_solved = true;
+                            continue;
+                        }
+                        None => Ok(res.into()),
+                    }
+                }
+                (res, _) => res,
+            };
+            (res, http_client.read().await.get_retry_policy().cloned())
+        };
+        let mut retry_after = None;
+        let res = match res {
+            Ok(res) => {
+                observer.response(&res);
+                if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                    retry_after = <RateLimit as TryFrom<&Response>>::try_from(&res)
+                        .ok()
+                        .map(|rate_limit| rate_limit.retry_after);
+                }
+                HttpClient::check_response(res).await
+            }
+            Err(e) => Err(e),
+        };
+        match (res, retry_policy) {
+            (Err(e), Some(retry_policy)) if retry_policy.should_retry(&method, &e, *attempt) => {
+                observer.retry(*attempt, &e);
+                tokio::time::sleep(retry_policy.delay(*attempt, retry_after.as_ref())).await;
+                *attempt += 1;
+            }
+            (res, _) => return res,
         }
     }
 }
 
+/// Send the request with [`send_with_checks`] and deserialize the response body.
+pub(crate) async fn send<E>(http_client: &HttpClientRef, endpoint: &E) -> Result<E::Response>
+where
+    E: Endpoint,
+    <<E as Endpoint>::Response as FromResponse>::Response: DeserializeOwned,
+{
+    let res = send_with_checks(http_client, endpoint)
+        .await?
+        .json::<<E::Response as FromResponse>::Response>()
+        .await?;
+
+    Ok(FromResponse::from_response(res))
+}
+
+/// Send the request with [`send_with_checks`] and deserialize the response body with its rate limit.
+pub(crate) async fn send_with_rate_limit<E>(
+    http_client: &HttpClientRef,
+    endpoint: &E,
+) -> Result<Limited<E::Response>>
+where
+    E: Endpoint,
+    <<E as Endpoint>::Response as FromResponse>::Response: DeserializeOwned,
+{
+    let resp = send_with_checks(http_client, endpoint).await?;
+
+    let some_rate_limit = <RateLimit as TryFrom<&Response>>::try_from(&resp);
+
+    let res = resp
+        .json::<ApiResult<<E::Response as FromResponse>::Response>>()
+        .await?
+        .into_result()?;
+
+    Ok(Limited {
+        rate_limit: some_rate_limit?,
+        body: FromResponse::from_response(res),
+    })
+}
+
 /// Helper macros for implementing the send function on the builder
 ///
 /// Introduced in v3.0.0-alpha.1
@@ -473,18 +1040,7 @@ macro_rules! endpoint {
         impl $typ {
             /// Send the request.
             pub async fn send(&self) -> crate::Result<$out> {
-                #[cfg(all(not(feature = "multi-thread"), not(feature = "tokio-multi-thread"), not(feature = "rw-multi-thread")))]
-                {
-                    self.http_client.try_borrow()?.send_request(self).await
-                }
-                #[cfg(any(feature = "multi-thread", feature = "tokio-multi-thread"))]
-                {
-                    self.http_client.lock().await.send_request(self).await
-                }
-                #[cfg(feature = "rw-multi-thread")]
-                {
-                    self.http_client.read().await.send_request(self).await
-                }
+                crate::http_client::send(&self.http_client, self).await
             }
         }
 
@@ -500,9 +1056,7 @@ macro_rules! endpoint {
         impl $typ {
             /// Send the request.
             pub async fn send(&self) -> crate::Result<crate::rate_limit::Limited<$out>> {
-
-                    self.http_client.read().await.send_request_with_rate_limit(self).await
-
+                crate::http_client::send_with_rate_limit(&self.http_client, self).await
             }
         }
 
@@ -519,7 +1073,7 @@ macro_rules! endpoint {
             /// Send the request.
             #[allow(dead_code)]
             pub async fn send(&self) -> $out {
-                self.http_client.read().await.send_request(self).await?
+                crate::http_client::send(&self.http_client, self).await?
             }
         }
 
@@ -536,7 +1090,7 @@ macro_rules! endpoint {
             /// Send the request.
             #[allow(dead_code)]
             pub async fn send(&self) -> crate::Result<()> {
-                self.http_client.read().await.send_request(self).await??;
+                crate::http_client::send(&self.http_client, self).await??;
                 Ok(())
             }
         }
@@ -0,0 +1,475 @@
+//! Response cache for the read-only endpoints.
+//!
+//! A [`ResponseCache`] set on the [`HttpClient`](crate::HttpClient) serves the `GET` requests
+//! that don't require authentication from a [`CacheBackend`] while they are fresh.
+//! Entries are keyed on the method and the full URL with its query string, so a cache shared
+//! by clients of different servers (e.g. [`HttpClient::api_dev_client`](crate::HttpClient::api_dev_client)) keeps their responses apart.
+//!
+//! Only the routes with a TTL are cached.
+//! [`ResponseCache::new`] comes with TTLs for the tags, manga, aggregate, statistics and cover endpoints.
+//!
+//! A single request can skip the cache with [`bypass`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use mangadex_api::http_client::cache::{LruCache, ResponseCache};
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let http_client = HttpClient::builder()
+//!     .cache(
+//!         ResponseCache::new(LruCache::new(512))
+//!             .with_ttl("/author/{}", Duration::from_secs(60 * 10)),
+//!     )
+//!     .build()?;
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//!
+//! // Served from the cache the second time.
+//! let tags = client.manga().tag().get().send().await?;
+//! let tags = client.manga().tag().get().send().await?;
+//!
+//! // Always sent to MangaDex.
+//! let tags = mangadex_api::http_client::cache::bypass(client.manga().tag().get().send()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Response, StatusCode};
+
+use crate::Result;
+use crate::http_client::route::RoutePattern;
+
+/// A response stored in the cache.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl CachedResponse {
+    pub fn new(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    /// Read a response to put it in the cache.
+    pub(crate) async fn read(res: Response) -> Result<Self> {
+        Ok(Self {
+            status: res.status(),
+            headers: res.headers().clone(),
+            body: res.bytes().await?.to_vec(),
+        })
+    }
+}
+
+impl From<CachedResponse> for Response {
+    fn from(value: CachedResponse) -> Self {
+        let mut res = http::Response::new(value.body);
+        *res.status_mut() = value.status;
+        *res.headers_mut() = value.headers;
+        Response::from(res)
+    }
+}
+
+/// A storage for the cached responses.
+///
+/// The backend is responsible for the expiration of its entries.
+pub trait CacheBackend: Send + Sync {
+    /// Get a fresh entry.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Insert an entry that expires after `ttl`.
+    fn insert(&self, key: String, response: CachedResponse, ttl: Duration);
+
+    fn remove(&self, key: &str);
+
+    fn clear(&self);
+}
+
+impl Debug for dyn CacheBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CacheBackend")
+    }
+}
+
+#[derive(Debug)]
+struct LruEntry {
+    response: CachedResponse,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    entries: HashMap<String, LruEntry>,
+    tick: u64,
+}
+
+/// An in-memory cache evicting the least recently used entry when full.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct LruCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+impl LruCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for LruCache {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl CacheBackend for LruCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+        match state.entries.get_mut(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                entry.last_used = tick;
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                state.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: String, response: CachedResponse, ttl: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.capacity {
+            let now = Instant::now();
+            state.entries.retain(|_, entry| entry.expires_at > now);
+            if state.entries.len() >= self.capacity
+                && let Some(oldest) = state
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone())
+            {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(
+            key,
+            LruEntry {
+                response,
+                expires_at: Instant::now() + ttl,
+                last_used: tick,
+            },
+        );
+    }
+
+    fn remove(&self, key: &str) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .remove(key);
+    }
+
+    fn clear(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .clear();
+    }
+}
+
+/// The cache configuration: a backend and the TTL of each cached route.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ResponseCache {
+    backend: Box<dyn CacheBackend>,
+    routes: Vec<(RoutePattern, Duration)>,
+}
+
+impl ResponseCache {
+    /// Create a cache with the default TTLs.
+    pub fn new<B: CacheBackend + 'static>(backend: B) -> Self {
+        Self::without_routes(backend)
+            .with_ttl("/manga/tag", Duration::from_secs(60 * 60))
+            .with_ttl("/manga/{}", Duration::from_secs(60 * 5))
+            .with_ttl("/manga/{}/aggregate", Duration::from_secs(60 * 5))
+            .with_ttl("/statistics/manga", Duration::from_secs(60 * 5))
+            .with_ttl("/statistics/manga/{}", Duration::from_secs(60 * 5))
+            .with_ttl("/cover", Duration::from_secs(60 * 5))
+            .with_ttl("/cover/{}", Duration::from_secs(60 * 5))
+    }
+
+    /// Create a cache where no route is cached.
+    pub fn without_routes<B: CacheBackend + 'static>(backend: B) -> Self {
+        Self {
+            backend: Box::new(backend),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Cache the `GET` requests to `path` for `ttl`.
+    ///
+    /// `path` is the endpoint path where `{}` matches an id (e.g. `/manga/{}`).
+    pub fn with_ttl(mut self, path: &str, ttl: Duration) -> Self {
+        let pattern = RoutePattern::ids(Method::GET, path);
+        self.routes.retain(|(route, _)| *route != pattern);
+        self.routes.push((pattern, ttl));
+        self
+    }
+
+    /// Stop caching the `GET` requests to `path`.
+    pub fn without_ttl(mut self, path: &str) -> Self {
+        let pattern = RoutePattern::ids(Method::GET, path);
+        self.routes.retain(|(route, _)| *route != pattern);
+        self
+    }
+
+    pub fn backend(&self) -> &dyn CacheBackend {
+        self.backend.as_ref()
+    }
+
+    /// Remove all the cached responses.
+    pub fn clear(&self) {
+        self.backend.clear();
+    }
+
+    /// Get the TTL of the route.
+    pub fn ttl(&self, method: &Method, path: &str) -> Option<Duration> {
+        self.routes
+            .iter()
+            .find(|(route, _)| route.matches(method, path))
+            .map(|(_, ttl)| *ttl)
+    }
+
+    pub(crate) fn key(method: &Method, url: &url::Url) -> String {
+        format!("{method} {}", &url[..url::Position::AfterQuery])
+    }
+}
+
+thread_local! {
+    static BYPASS: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn is_bypassed() -> bool {
+    BYPASS.with(Cell::get)
+}
+
+/// A future skipping the cache lookup while it runs.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Bypass<F> {
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Bypass<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let previous = BYPASS.with(|bypass| bypass.replace(true));
+        let res = self.inner.as_mut().poll(cx);
+        BYPASS.with(|bypass| bypass.set(previous));
+        res
+    }
+}
+
+/// Run the requests in `future` without looking up the cache.
+///
+/// The fresh responses still replace the cached ones.
+pub fn bypass<F: Future>(future: F) -> Bypass<F> {
+    Bypass {
+        inner: Box::pin(future),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::header::HeaderMap;
+    use reqwest::{Method, StatusCode};
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{CacheBackend, CachedResponse, LruCache, ResponseCache, bypass};
+    use crate::{HttpClient, MangaDexClient};
+
+    fn tags_response() -> serde_json::Value {
+        json!({
+            "result": "ok",
+            "response": "collection",
+            "data": [
+                {
+                    "id": "0234a31e-a729-4e28-9d6a-3f87c4966b9e",
+                    "type": "tag",
+                    "attributes": {
+                        "name": {
+                            "en": "Oneshot"
+                        },
+                        "description": {
+                            "en": "One-off works"
+                        },
+                        "group": "format",
+                        "version": 1
+                    },
+                    "relationships": []
+                }
+            ],
+            "limit": 10,
+            "offset": 0,
+            "total": 1
+        })
+    }
+
+    #[test]
+    fn lru_cache_evicts_the_least_recently_used_entry() {
+        let cache = LruCache::new(2);
+        let response = CachedResponse::new(StatusCode::OK, HeaderMap::new(), Vec::new());
+        let ttl = Duration::from_secs(60);
+        cache.insert("a".to_string(), response.clone(), ttl);
+        cache.insert("b".to_string(), response.clone(), ttl);
+        assert!(cache.get("a").is_some());
+
+        cache.insert("c".to_string(), response.clone(), ttl);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+
+        cache.insert("d".to_string(), response, Duration::ZERO);
+        assert!(cache.get("d").is_none());
+    }
+
+    #[test]
+    fn route_ids_only_match_uuids() {
+        let cache = ResponseCache::new(LruCache::default());
+        assert!(
+            cache
+                .ttl(&Method::GET, "/manga/f9c33607-9180-4ba6-b85c-e4b5faee7192")
+                .is_some()
+        );
+        assert!(cache.ttl(&Method::GET, "/manga/random").is_none());
+    }
+
+    #[tokio::test]
+    async fn cache_serves_repeated_requests() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .cache(ResponseCache::new(LruCache::default()))
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .and(path(r"/manga/tag"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(tags_response()))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let first = mangadex_client.manga().tag().get().send().await?;
+        let second = mangadex_client.manga().tag().get().send().await?;
+        assert_eq!(first.data.len(), 1);
+        assert_eq!(second.data[0].id, first.data[0].id);
+
+        // Skips the lookup but refreshes the entry.
+        bypass(mangadex_client.manga().tag().get().send()).await?;
+        mangadex_client.manga().tag().get().send().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shared_cache_keeps_the_servers_apart() -> anyhow::Result<()> {
+        let cache = std::sync::Arc::new(ResponseCache::new(LruCache::default()));
+        let mut clients = Vec::new();
+        let mut mock_servers = Vec::new();
+        for _ in 0..2 {
+            let mock_server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path(r"/manga/tag"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(tags_response()))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+            let mut http_client = HttpClient::builder()
+                .base_url(Url::parse(&mock_server.uri())?)
+                .build()?;
+            http_client.set_cache(cache.clone());
+            clients.push(MangaDexClient::new_with_http_client(http_client));
+            mock_servers.push(mock_server);
+        }
+
+        for client in &clients {
+            client.manga().tag().get().send().await?;
+            client.manga().tag().get().send().await?;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cache_ignores_routes_without_ttl() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .cache(ResponseCache::new(LruCache::default()).without_ttl("/manga/tag"))
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .and(path(r"/manga/tag"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(tags_response()))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        mangadex_client.manga().tag().get().send().await?;
+        mangadex_client.manga().tag().get().send().await?;
+
+        Ok(())
+    }
+}
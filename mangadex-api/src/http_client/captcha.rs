@@ -0,0 +1,237 @@
+//! Captcha challenges raised by the MangaDex API.
+//!
+//! Some endpoints answer `403 captcha_required_exception` to slow down automated traffic.
+//! When a [`CaptchaSolver`] is set with
+//! [`HttpClientBuilder::captcha_solver`](crate::http_client::HttpClientBuilder::captcha_solver),
+//! it gets the site key of the challenge and the request is sent again once
+//! with its solution in the `X-Captcha-Result` header.
+//! The solutions are valid once, so they are not kept in the client
+//! unlike the captcha set with [`HttpClient::set_captcha`](crate::HttpClient::set_captcha).
+//!
+//! Without a solver, the request fails with an error for which
+//! [`Error::is_captcha_required`] is `true`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//!
+//! async fn show_captcha(site_key: String) -> mangadex_api::Result<String> {
+//!     // Render the reCAPTCHA widget with `site_key` and return its response.
+//!     # Ok(site_key)
+//! }
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let http_client = HttpClient::builder().captcha_solver(show_captcha).build()?;
+//!
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+
+use mangadex_api_schema::error::{ApiErrorKind, MangaDexErrorResponse_};
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+
+use crate::Result;
+use crate::error::Error;
+
+/// The future returned by [`CaptchaSolver::solve`].
+pub type CaptchaFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Solves the captcha challenges of the MangaDex API.
+///
+/// Any `Fn(String) -> impl Future<Output = Result<String>>` is a solver.
+pub trait CaptchaSolver: Send + Sync {
+    /// Solve the challenge of `site_key` and return the captcha result.
+    ///
+    /// Return [`Error::CaptchaRequired`] to give up.
+    fn solve<'a>(&'a self, site_key: &'a str) -> CaptchaFuture<'a>;
+}
+
+impl Debug for dyn CaptchaSolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CaptchaSolver")
+    }
+}
+
+impl<F, Fut> CaptchaSolver for F
+where
+    F: Fn(String) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String>> + Send + 'static,
+{
+    fn solve<'a>(&'a self, site_key: &'a str) -> CaptchaFuture<'a> {
+        Box::pin(self(site_key.to_string()))
+    }
+}
+
+/// A solver that never solves the challenges, for the non-interactive applications.
+///
+/// The requests fail with [`Error::CaptchaRequired`], which holds the site key.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct RejectCaptcha;
+
+impl CaptchaSolver for RejectCaptcha {
+    fn solve<'a>(&'a self, site_key: &'a str) -> CaptchaFuture<'a> {
+        Box::pin(async move { Err(Error::CaptchaRequired(site_key.to_string())) })
+    }
+}
+
+/// Get the site key of a captcha challenge from the `X-Captcha-Sitekey` header
+/// or the error context of the response.
+pub(crate) fn site_key(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Option<String> {
+    if status != StatusCode::FORBIDDEN {
+        return None;
+    }
+    let from_body = || {
+        serde_json::from_slice::<MangaDexErrorResponse_>(body)
+            .ok()?
+            .kinds()
+            .find_map(|kind| match kind {
+                ApiErrorKind::CaptchaRequired { site_key } => Some(site_key),
+                _ => None,
+            })
+    };
+    let from_header = headers
+        .get("x-captcha-sitekey")
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    match from_body() {
+        Some(site_key) => site_key.or(from_header),
+        None => from_header,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{header, header_exists, method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::{HttpClient, MangaDexClient};
+
+    fn captcha_required() -> ResponseTemplate {
+        ResponseTemplate::new(403)
+            .insert_header("x-captcha-sitekey", "someSiteKey")
+            .set_body_json(json!({
+                "result": "error",
+                "errors": [{
+                    "id": Uuid::new_v4(),
+                    "status": 403,
+                    "title": "captcha_required_exception",
+                    "detail": "Captcha required",
+                    "context": {"siteKey": "someSiteKey"}
+                }]
+            }))
+    }
+
+    async fn mount(mock_server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path_regex(r"/report"))
+            .and(header("x-captcha-result", "solved-someSiteKey"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-retry-after", "1698723860")
+                    .insert_header("x-ratelimit-limit", "10")
+                    .insert_header("x-ratelimit-remaining", "9")
+                    .set_body_json(json!({"result": "ok"})),
+            )
+            .expect(2)
+            .mount(mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path_regex(r"/report"))
+            .respond_with(captcha_required())
+            .mount(mock_server)
+            .await;
+    }
+
+    async fn report(client: &MangaDexClient) -> Result<()> {
+        client
+            .report()
+            .post()
+            .category(mangadex_api_types::ReportCategory::Manga)
+            .reason(Uuid::new_v4())
+            .object_id(Uuid::new_v4())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn captcha_is_solved_and_request_retried() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        mount(&mock_server).await;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let solver_calls = calls.clone();
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_tokens(non_exhaustive::non_exhaustive!(crate::v5::AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .captcha_solver(move |site_key: String| {
+                solver_calls.fetch_add(1, Ordering::SeqCst);
+                async move { Ok(format!("solved-{site_key}")) }
+            })
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+
+        report(&client).await?;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(
+            client
+                .get_http_client()
+                .read()
+                .await
+                .get_captcha()
+                .is_none()
+        );
+
+        // The solution isn't reused, a new captcha is solved for the next request.
+        report(&client).await?;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn captcha_required_error_without_solution() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path_regex(r"/report"))
+            .and(header_exists("authorization"))
+            .respond_with(captcha_required())
+            .mount(&mock_server)
+            .await;
+        let mut http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_tokens(non_exhaustive::non_exhaustive!(crate::v5::AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .build()?;
+
+        let client = MangaDexClient::new_with_http_client(http_client.clone());
+        let error = report(&client).await.unwrap_err();
+        assert!(matches!(error, Error::Api(_)));
+        assert!(error.is_captcha_required());
+
+        http_client.set_captcha_solver(RejectCaptcha);
+        let client = MangaDexClient::new_with_http_client(http_client);
+        let error = report(&client).await.unwrap_err();
+        assert!(matches!(&error, Error::CaptchaRequired(site_key) if site_key == "someSiteKey"));
+        assert_eq!(error.captcha_site_key().as_deref(), Some("someSiteKey"));
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! Middlewares called around every request sent by the [`HttpClient`](crate::HttpClient).
+//!
+//! A [`Middleware`] can inspect and modify the request before it is sent,
+//! inspect and modify the response, and get notified when an error occurs.
+//!
+//! Middlewares are stacked with [`HttpClientBuilder::middleware`](crate::http_client::HttpClientBuilder::middleware):
+//! [`Middleware::before_request`] is called in insertion order,
+//! [`Middleware::after_response`] and [`Middleware::on_error`] in reverse order.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::http_client::middleware::Middleware;
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//! use reqwest::header::HeaderValue;
+//!
+//! struct AddHeader;
+//!
+//! impl Middleware for AddHeader {
+//!     fn before_request(&self, request: &mut reqwest::Request) -> mangadex_api::Result<()> {
+//!         request
+//!             .headers_mut()
+//!             .insert("x-my-header", HeaderValue::from_static("value"));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let http_client = HttpClient::builder().middleware(AddHeader).build()?;
+//!
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+
+use reqwest::{Method, Request, Response};
+use url::Url;
+
+use crate::Result;
+use crate::error::Error;
+
+/// A hook around the requests sent by the [`HttpClient`](crate::HttpClient).
+///
+/// Every function has a default implementation that does nothing.
+pub trait Middleware: Send + Sync {
+    /// Called before sending the request.
+    ///
+    /// Returning an error aborts the request.
+    fn before_request(&self, request: &mut Request) -> Result<()> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called after receiving the response, before any status check.
+    ///
+    /// Returning an error discards the response.
+    fn after_response(&self, response: &mut Response) -> Result<()> {
+        let _ = response;
+        Ok(())
+    }
+
+    /// Called when the request failed, including errors returned by the other middlewares.
+    fn on_error(&self, method: &Method, url: &Url, error: &Error) {
+        let _ = (method, url, error);
+    }
+}
+
+impl<M: Middleware + ?Sized> Middleware for std::sync::Arc<M> {
+    fn before_request(&self, request: &mut Request) -> Result<()> {
+        (**self).before_request(request)
+    }
+
+    fn after_response(&self, response: &mut Response) -> Result<()> {
+        (**self).after_response(response)
+    }
+
+    fn on_error(&self, method: &Method, url: &Url, error: &Error) {
+        (**self).on_error(method, url, error)
+    }
+}
+
+impl Debug for dyn Middleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Middleware")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use reqwest::header::HeaderValue;
+    use reqwest::{Method, Request, Response};
+    use serde_json::json;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{header, method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::Middleware;
+    use crate::error::Error;
+    use crate::{HttpClient, MangaDexClient, Result};
+
+    #[derive(Default)]
+    struct Recorder {
+        requests: AtomicU32,
+        responses: AtomicU32,
+        errors: AtomicU32,
+        fail: bool,
+    }
+
+    impl Middleware for Recorder {
+        fn before_request(&self, request: &mut Request) -> Result<()> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                return Err(Error::unknow("injected fault"));
+            }
+            request
+                .headers_mut()
+                .insert("x-test-header", HeaderValue::from_static("middleware"));
+            Ok(())
+        }
+
+        fn after_response(&self, _response: &mut Response) -> Result<()> {
+            self.responses.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn on_error(&self, _method: &Method, _url: &Url, _error: &Error) {
+            self.errors.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn middlewares_are_called_around_the_request() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let recorder = Arc::new(Recorder::default());
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .middleware(recorder.clone())
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"/manga/[0-9a-fA-F-]+/aggregate"))
+            .and(header("x-test-header", "middleware"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "volumes": {}
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        mangadex_client
+            .manga()
+            .id(Uuid::new_v4())
+            .aggregate()
+            .get()
+            .send()
+            .await?;
+
+        assert_eq!(recorder.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(recorder.responses.load(Ordering::SeqCst), 1);
+        assert_eq!(recorder.errors.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn middleware_errors_abort_the_request() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let recorder = Arc::new(Recorder::default());
+        let fault = Arc::new(Recorder {
+            fail: true,
+            ..Default::default()
+        });
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .middleware(recorder.clone())
+            .middleware(fault.clone())
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let res = mangadex_client
+            .manga()
+            .id(Uuid::new_v4())
+            .aggregate()
+            .get()
+            .send()
+            .await;
+
+        assert!(matches!(res, Err(Error::UnknowSource(_))));
+        assert_eq!(recorder.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(recorder.responses.load(Ordering::SeqCst), 0);
+        assert_eq!(recorder.errors.load(Ordering::SeqCst), 1);
+        assert_eq!(fault.errors.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+//! Retry policy for transient failures.
+//!
+//! A [`RetryPolicy`] can be set with [`HttpClientBuilder::retry_policy`](crate::http_client::HttpClientBuilder::retry_policy).
+//! It is applied by the `send()` function of every endpoint.
+//!
+//! By default, it retries:
+//!
+//! - [`Error::ServiceUnavailable`] and [`Error::ServerError`] (HTTP 5xx),
+//! - connection errors and timeouts from [`reqwest`],
+//! - [`Error::RateLimitExcedeed`] (HTTP 429) by sleeping until the `x-ratelimit-retry-after` date,
+//!   at most [`RetryPolicy::max_retry_after`],
+//!
+//! but only for idempotent methods (`GET`, `HEAD`, `PUT`, `DELETE` and `OPTIONS`).
+//! A `429` is retried whatever the method since the request was not processed by the server.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//! use mangadex_api::http_client::retry::{Backoff, Jitter, RetryPolicy};
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let http_client = HttpClient::builder()
+//!     .retry_policy(
+//!         RetryPolicy::default()
+//!             .with_max_attempts(5)
+//!             .with_backoff(Backoff::exponential(Duration::from_millis(500), Duration::from_secs(30)))
+//!             .with_jitter(Jitter::Full),
+//!     )
+//!     .build()?;
+//!
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mangadex_api_types::MangaDexDateTime;
+use reqwest::Method;
+use time::OffsetDateTime;
+
+use crate::error::Error;
+
+/// The delay curve between two attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backoff {
+    /// Always wait the same duration.
+    Constant(Duration),
+    /// Wait `initial * factor^(attempt - 1)`, capped at `max`.
+    Exponential {
+        initial: Duration,
+        max: Duration,
+        factor: u32,
+    },
+}
+
+impl Backoff {
+    /// An exponential backoff doubling the delay after each attempt.
+    pub const fn exponential(initial: Duration, max: Duration) -> Self {
+        Self::Exponential {
+            initial,
+            max,
+            factor: 2,
+        }
+    }
+
+    /// Get the delay after the `attempt`-th failed attempt (starting at 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Self::Constant(delay) => delay,
+            Self::Exponential {
+                initial,
+                max,
+                factor,
+            } => factor
+                .checked_pow(attempt.saturating_sub(1))
+                .and_then(|multiplier| initial.checked_mul(multiplier))
+                .map_or(max, |delay| delay.min(max)),
+        }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::exponential(Duration::from_millis(500), Duration::from_secs(30))
+    }
+}
+
+/// Randomization applied to the backoff delay.
+///
+/// This avoids having concurrent tasks retrying at the same time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Jitter {
+    /// Use the backoff delay as is.
+    None,
+    /// Pick a random delay between zero and the backoff delay.
+    Full,
+    /// Pick a random delay between the half of the backoff delay and the backoff delay.
+    #[default]
+    Equal,
+}
+
+impl Jitter {
+    fn apply(&self, delay: Duration) -> Duration {
+        match self {
+            Self::None => delay,
+            Self::Full => delay.mul_f64(random_fraction()),
+            Self::Equal => delay / 2 + (delay / 2).mul_f64(random_fraction()),
+        }
+    }
+}
+
+/// A random number between `0.0` and `1.0`.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish() as f64 / u64::MAX as f64
+}
+
+type RetryableFn = Arc<dyn Fn(&Error) -> bool + Send + Sync>;
+
+/// Configuration of the retries applied when sending a request.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    pub jitter: Jitter,
+    /// The methods that are safe to send more than once.
+    pub idempotent_methods: Vec<Method>,
+    /// The longest wait for a `x-ratelimit-retry-after` date.
+    pub max_retry_after: Duration,
+    retryable: RetryableFn,
+}
+
+impl Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("backoff", &self.backoff)
+            .field("jitter", &self.jitter)
+            .field("idempotent_methods", &self.idempotent_methods)
+            .field("max_retry_after", &self.max_retry_after)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Backoff::default(),
+            jitter: Jitter::default(),
+            idempotent_methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::PUT,
+                Method::DELETE,
+                Method::OPTIONS,
+            ],
+            max_retry_after: Duration::from_secs(60),
+            retryable: Arc::new(Self::is_transient),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self::default().with_max_attempts(1)
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_idempotent_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.idempotent_methods = methods.into_iter().collect();
+        self
+    }
+
+    pub fn with_max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+
+    /// Replace the predicate deciding which errors can be retried.
+    ///
+    /// The default is [`RetryPolicy::is_transient`].
+    pub fn with_retryable<F>(mut self, retryable: F) -> Self
+    where
+        F: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Arc::new(retryable);
+        self
+    }
+
+    /// Check if the error is a transient failure.
+    pub fn is_transient(error: &Error) -> bool {
+        match error {
+            Error::RateLimitExcedeed | Error::ServiceUnavailable(_) => true,
+            Error::ServerError(status, _) => (500..600).contains(status),
+            Error::RequestError(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            _ => false,
+        }
+    }
+
+    /// Check if the request can be sent again after the `attempt`-th failed attempt.
+    pub fn should_retry(&self, method: &Method, error: &Error, attempt: u32) -> bool {
+        attempt < self.max_attempts
+            && (self.retryable)(error)
+            && (matches!(error, Error::RateLimitExcedeed)
+                || self.idempotent_methods.contains(method))
+    }
+
+    /// Get the delay before the next attempt.
+    ///
+    /// If the server returned a `x-ratelimit-retry-after` date, wait until that date instead,
+    /// but no longer than [`max_retry_after`](Self::max_retry_after).
+    pub fn delay(&self, attempt: u32, retry_after: Option<&MangaDexDateTime>) -> Duration {
+        match retry_after {
+            Some(retry_after) => {
+                Duration::try_from(*retry_after.as_ref() - OffsetDateTime::now_utc())
+                    .unwrap_or_default()
+                    .min(self.max_retry_after)
+            }
+            None => self.jitter.apply(self.backoff.delay(attempt)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Method;
+    use serde_json::json;
+    use time::OffsetDateTime;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{Backoff, Jitter, RetryPolicy};
+    use crate::error::Error;
+    use crate::{HttpClient, MangaDexClient};
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let backoff = Backoff::exponential(Duration::from_millis(100), Duration::from_secs(1));
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(3), Duration::from_millis(400));
+        assert_eq!(backoff.delay(5), Duration::from_secs(1));
+        assert_eq!(backoff.delay(100), Duration::from_secs(1));
+
+        let delay = Jitter::Equal.apply(Duration::from_secs(1));
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn retry_after_is_capped() {
+        let policy = RetryPolicy::default().with_max_retry_after(Duration::from_secs(5));
+        let retry_after = (OffsetDateTime::now_utc() + time::Duration::hours(1)).into();
+        assert_eq!(policy.delay(1, Some(&retry_after)), Duration::from_secs(5));
+
+        let retry_after = (OffsetDateTime::now_utc() - time::Duration::hours(1)).into();
+        assert_eq!(policy.delay(1, Some(&retry_after)), Duration::ZERO);
+    }
+
+    #[test]
+    fn only_idempotent_methods_are_retried() {
+        let policy = RetryPolicy::default();
+        let error = Error::ServerError(502, String::new());
+        assert!(policy.should_retry(&Method::GET, &error, 1));
+        assert!(!policy.should_retry(&Method::GET, &error, 3));
+        assert!(!policy.should_retry(&Method::POST, &error, 1));
+        assert!(policy.should_retry(&Method::POST, &Error::RateLimitExcedeed, 1));
+        assert!(!policy.should_retry(&Method::GET, &Error::MissingTokens, 1));
+    }
+
+    #[tokio::test]
+    async fn send_retries_transient_failures() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .retry_policy(
+                RetryPolicy::default()
+                    .with_backoff(Backoff::Constant(Duration::from_millis(10)))
+                    .with_jitter(Jitter::None),
+            )
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"/manga/[0-9a-fA-F-]+/aggregate"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/manga/[0-9a-fA-F-]+/aggregate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "volumes": {}
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let res = mangadex_client
+            .manga()
+            .id(Uuid::new_v4())
+            .aggregate()
+            .get()
+            .send()
+            .await?;
+        assert!(res.volumes.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_gives_up_after_max_attempts() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .retry_policy(
+                RetryPolicy::default()
+                    .with_max_attempts(2)
+                    .with_backoff(Backoff::Constant(Duration::from_millis(10))),
+            )
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"/manga/[0-9a-fA-F-]+/aggregate"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("oops"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let res = mangadex_client
+            .manga()
+            .id(Uuid::new_v4())
+            .aggregate()
+            .get()
+            .send()
+            .await;
+        assert!(matches!(res, Err(Error::ServerError(500, _))));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use reqwest::Method;
+use uuid::Uuid;
+
+/// A method and an endpoint path where `{}` matches a path segment (e.g. `/at-home/server/{}`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RoutePattern {
+    method: Method,
+    segments: Vec<String>,
+    /// Whether `{}` only matches a UUID, so `/manga/{}` does not match `/manga/random`.
+    ids_only: bool,
+}
+
+impl RoutePattern {
+    /// A pattern where `{}` matches any single segment.
+    pub(crate) fn new(method: Method, path: &str) -> Self {
+        Self {
+            method,
+            segments: path
+                .trim_matches('/')
+                .split('/')
+                .map(String::from)
+                .collect(),
+            ids_only: false,
+        }
+    }
+
+    /// A pattern where `{}` only matches a UUID.
+    pub(crate) fn ids(method: Method, path: &str) -> Self {
+        Self {
+            ids_only: true,
+            ..Self::new(method, path)
+        }
+    }
+
+    pub(crate) fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method != *method {
+            return false;
+        }
+        let mut path_segments = path.trim_matches('/').split('/');
+        let mut pattern = self.segments.iter();
+        loop {
+            match (pattern.next(), path_segments.next()) {
+                (None, None) => return true,
+                (Some(p), Some(s)) if p == s || (p == "{}" && self.matches_id(s)) => continue,
+                _ => return false,
+            }
+        }
+    }
+
+    fn matches_id(&self, segment: &str) -> bool {
+        !self.ids_only || Uuid::parse_str(segment).is_ok()
+    }
+}
@@ -0,0 +1,497 @@
+//! Record and replay the API responses.
+//!
+//! A [`Vcr`] set on the [`HttpClient`](crate::HttpClient) either writes every request
+//! and its response to a [`Cassette`] file, or serves the responses from a cassette
+//! recorded earlier without sending anything to MangaDex.
+//! The `Authorization`, `Cookie`, `Set-Cookie` and `X-Captcha-Result` headers are redacted
+//! from the cassette.
+//!
+//! The recorded interactions are kept in memory and written with [`Vcr::save`], or when the
+//! VCR is dropped.
+//!
+//! The OAuth requests (login, token refresh, ...) are sent with the inner
+//! [`reqwest::Client`] of the [`HttpClient`](crate::HttpClient) to the authentication server,
+//! so they are neither recorded nor replayed: a replayed client needs its tokens set directly.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::http_client::vcr::{Matching, Vcr};
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! // Capture the real API once...
+//! let http_client = HttpClient::builder()
+//!     .vcr(Vcr::record("tests/cassettes/tags.json"))
+//!     .build()?;
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! let tags = client.manga().tag().get().send().await?;
+//! if let Some(vcr) = client.get_http_client().read().await.get_vcr() {
+//!     vcr.save()?;
+//! }
+//!
+//! // ...and replay it offline.
+//! let http_client = HttpClient::builder()
+//!     .vcr(Vcr::replay("tests/cassettes/tags.json", Matching::Strict)?)
+//!     .build()?;
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! let tags = client.manga().tag().get().send().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+use crate::atomic_file;
+use crate::error::Error;
+
+/// The headers never written to a cassette.
+const REDACTED_HEADERS: [&str; 4] = ["authorization", "cookie", "set-cookie", "x-captcha-result"];
+const REDACTED: &str = "[REDACTED]";
+
+/// How a request is matched against the recorded ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Matching {
+    /// The method, the path and the query must match,
+    /// and each interaction is replayed only once.
+    #[default]
+    Strict,
+    /// The method and the path must match.
+    ///
+    /// The last matching interaction is replayed again once they were all used.
+    Lenient,
+}
+
+/// A body, stored as text when possible.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum Body {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Body {
+    fn new(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::Text(text),
+            Err(e) => Self::Bytes(e.into_bytes()),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Text(text) => text.into_bytes(),
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+/// A recorded request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// The query pairs, in the order they were sent.
+    #[serde(default)]
+    pub query: Vec<(String, String)>,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Body>,
+}
+
+impl RecordedRequest {
+    fn new(request: &Request) -> Self {
+        Self {
+            method: request.method().to_string(),
+            path: request.url().path().to_string(),
+            query: request.url().query_pairs().into_owned().collect(),
+            headers: redact(request.headers()),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(|bytes| Body::new(bytes.to_vec())),
+        }
+    }
+
+    fn matches(&self, other: &Self, matching: Matching) -> bool {
+        if self.method != other.method || self.path != other.path {
+            return false;
+        }
+        match matching {
+            Matching::Strict => {
+                let mut query = self.query.clone();
+                let mut other_query = other.query.clone();
+                query.sort();
+                other_query.sort();
+                query == other_query
+            }
+            Matching::Lenient => true,
+        }
+    }
+}
+
+/// A recorded response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl RecordedResponse {
+    fn into_response(self) -> Result<Response> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| Error::ParseError(e.to_string()))?,
+                HeaderValue::from_str(&value).map_err(|e| Error::ParseError(e.to_string()))?,
+            );
+        }
+        let mut res = http::Response::new(self.body.into_bytes());
+        *res.status_mut() =
+            StatusCode::from_u16(self.status).map_err(|e| Error::ParseError(e.to_string()))?;
+        *res.headers_mut() = headers;
+        Ok(Response::from(res))
+    }
+}
+
+/// A request and the response it got.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// The interactions recorded in a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read(path)?;
+        serde_json::from_slice(&content).map_err(|e| Error::ParseError(e.to_string()))
+    }
+
+    /// Write the cassette to a temporary file, then move it to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content =
+            serde_json::to_vec_pretty(self).map_err(|e| Error::ParseError(e.to_string()))?;
+        atomic_file::write(path, &content)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record {
+        path: PathBuf,
+        /// The number of interactions already written to `path`.
+        saved: usize,
+    },
+    Replay {
+        matching: Matching,
+        used: Vec<bool>,
+    },
+}
+
+#[derive(Debug)]
+struct State {
+    mode: Mode,
+    cassette: Cassette,
+}
+
+/// Records the interactions to a cassette or replays them.
+#[derive(Debug)]
+pub struct Vcr {
+    state: Mutex<State>,
+}
+
+impl Vcr {
+    /// Record the interactions to `path`.
+    ///
+    /// The cassette is written by [`save`](Self::save), or when the VCR is dropped,
+    /// replacing the previous file.
+    pub fn record<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            state: Mutex::new(State {
+                mode: Mode::Record {
+                    path: path.into(),
+                    saved: 0,
+                },
+                cassette: Cassette::default(),
+            }),
+        }
+    }
+
+    /// Replay the interactions recorded in `path`.
+    pub fn replay<P: AsRef<Path>>(path: P, matching: Matching) -> Result<Self> {
+        Ok(Self::from_cassette(Cassette::load(path)?, matching))
+    }
+
+    /// Replay the interactions of `cassette`.
+    pub fn from_cassette(cassette: Cassette, matching: Matching) -> Self {
+        Self {
+            state: Mutex::new(State {
+                mode: Mode::Replay {
+                    matching,
+                    used: vec![false; cassette.interactions.len()],
+                },
+                cassette,
+            }),
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.lock().mode, Mode::Replay { .. })
+    }
+
+    /// Get the interactions recorded or replayed so far.
+    pub fn cassette(&self) -> Cassette {
+        self.lock().cassette.clone()
+    }
+
+    /// Write the interactions recorded so far to the cassette file.
+    ///
+    /// Does nothing when replaying, or when nothing was recorded since the last save.
+    pub fn save(&self) -> Result<()> {
+        let (path, content, len) = {
+            let state = self.lock();
+            let Mode::Record { path, saved } = &state.mode else {
+                return Ok(());
+            };
+            let len = state.cassette.interactions.len();
+            if *saved == len {
+                return Ok(());
+            }
+            let content = serde_json::to_vec_pretty(&state.cassette)
+                .map_err(|e| Error::ParseError(e.to_string()))?;
+            (path.clone(), content, len)
+        };
+        atomic_file::write(&path, &content)?;
+        if let Mode::Record { saved, .. } = &mut self.lock().mode {
+            *saved = (*saved).max(len);
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the recorded response to `request`.
+    pub(crate) fn play(&self, request: &Request) -> Result<Response> {
+        let recorded = RecordedRequest::new(request);
+        let mut state = self.lock();
+        let State { mode, cassette } = &mut *state;
+        let Mode::Replay { matching, used } = mode else {
+            return Err(Error::unknow("the VCR is not in replay mode"));
+        };
+        let candidates: Vec<usize> = cassette
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| interaction.request.matches(&recorded, *matching))
+            .map(|(index, _)| index)
+            .collect();
+        let index = match (candidates.iter().find(|index| !used[**index]), matching) {
+            (Some(index), _) => *index,
+            (None, Matching::Lenient) if !candidates.is_empty() => candidates[candidates.len() - 1],
+            (None, _) => {
+                return Err(Error::UnmatchedInteraction(format!(
+                    "{} {}",
+                    request.method(),
+                    request.url()
+                )));
+            }
+        };
+        used[index] = true;
+        cassette.interactions[index]
+            .response
+            .clone()
+            .into_response()
+    }
+
+    /// Add the request and its response to the cassette.
+    pub(crate) async fn record_response(
+        &self,
+        request: RecordedRequest,
+        res: Response,
+    ) -> Result<Response> {
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes().await?.to_vec();
+        self.lock().cassette.interactions.push(Interaction {
+            request,
+            response: RecordedResponse {
+                status: status.as_u16(),
+                headers: redact(&headers),
+                body: Body::new(body.clone()),
+            },
+        });
+        let mut res = http::Response::new(body);
+        *res.status_mut() = status;
+        *res.headers_mut() = headers;
+        Ok(Response::from(res))
+    }
+
+    /// Get what is recorded from `request`, if the VCR is recording.
+    pub(crate) fn recorded_request(&self, request: &Request) -> Option<RecordedRequest> {
+        matches!(self.lock().mode, Mode::Record { .. }).then(|| RecordedRequest::new(request))
+    }
+}
+
+impl Drop for Vcr {
+    fn drop(&mut self) {
+        // Errors cannot be reported here, call `save` to handle them.
+        let _ = self.save();
+    }
+}
+
+fn redact(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{Cassette, Matching, Vcr};
+    use crate::error::Error;
+    use crate::v5::AuthTokens;
+    use crate::{HttpClient, MangaDexClient};
+
+    fn read_markers_response() -> serde_json::Value {
+        json!({
+            "result": "ok",
+            "data": ["00000000-0000-0000-0000-000000000001"]
+        })
+    }
+
+    fn client<V: Into<Arc<Vcr>>>(base_url: &str, vcr: V) -> anyhow::Result<MangaDexClient> {
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(base_url)?)
+            .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .vcr(vcr.into())
+            .build()?;
+        Ok(MangaDexClient::new_with_http_client(http_client))
+    }
+
+    #[tokio::test]
+    async fn vcr_replays_the_recorded_interactions() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let manga_id = uuid::Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path(format!("/manga/{manga_id}/read")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(read_markers_response()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let cassette_path =
+            std::env::temp_dir().join(format!("mangadex-api-vcr-{}.json", uuid::Uuid::new_v4()));
+        let vcr = Arc::new(Vcr::record(&cassette_path));
+        let recording = client(&mock_server.uri(), vcr.clone())?;
+        let recorded = recording.manga().id(manga_id).read().get().send().await?;
+        assert!(!cassette_path.exists());
+
+        vcr.save()?;
+        let content = std::fs::read_to_string(&cassette_path)?;
+        assert!(!content.contains("sessiontoken"));
+        assert!(content.contains("[REDACTED]"));
+
+        // The server is not called again.
+        let replaying = client(
+            "http://127.0.0.1:1",
+            Vcr::replay(&cassette_path, Matching::Strict)?,
+        )?;
+        let replayed = replaying.manga().id(manga_id).read().get().send().await?;
+        assert_eq!(replayed.data, recorded.data);
+
+        match replaying.manga().id(manga_id).read().get().send().await {
+            Err(Error::UnmatchedInteraction(_)) => {}
+            other => panic!("unexpected result: {other:?}"),
+        }
+        std::fs::remove_file(cassette_path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lenient_matching_ignores_the_query() -> anyhow::Result<()> {
+        let manga_id = uuid::Uuid::new_v4();
+        let cassette: Cassette = serde_json::from_value(json!({
+            "interactions": [{
+                "request": {
+                    "method": "GET",
+                    "path": format!("/manga/{manga_id}/read"),
+                    "query": [["grouped", "true"]],
+                },
+                "response": {
+                    "status": 200,
+                    "headers": [["content-type", "application/json"]],
+                    "body": read_markers_response().to_string(),
+                }
+            }]
+        }))?;
+
+        let strict = client(
+            "http://127.0.0.1:1",
+            Vcr::from_cassette(cassette.clone(), Matching::Strict),
+        )?;
+        assert!(
+            strict
+                .manga()
+                .id(manga_id)
+                .read()
+                .get()
+                .send()
+                .await
+                .is_err()
+        );
+
+        let lenient = client(
+            "http://127.0.0.1:1",
+            Vcr::from_cassette(cassette, Matching::Lenient),
+        )?;
+        for _ in 0..2 {
+            let res = lenient.manga().id(manga_id).read().get().send().await?;
+            assert_eq!(res.data.len(), 1);
+        }
+        Ok(())
+    }
+}
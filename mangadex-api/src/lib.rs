@@ -7,11 +7,15 @@
 #[macro_use]
 pub(crate) mod macros;
 
+pub(crate) mod atomic_file;
 pub mod constants;
 #[macro_use]
 pub mod http_client;
 pub mod error;
+pub(crate) mod observe;
+pub mod pool;
 pub mod rate_limit;
+pub mod token_store;
 pub mod traits;
 pub mod v5;
 
@@ -19,6 +23,10 @@ cfg_utils! {
     pub mod utils;
 }
 
+cfg_mock_server! {
+    pub mod mock_server;
+}
+
 pub use constants::*;
 pub use http_client::{HttpClient, HttpClientRef};
 use reqwest::{
@@ -8,6 +8,16 @@ macro_rules! cfg_utils{
     }
 }
 
+macro_rules! cfg_mock_server{
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "mock-server")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "mock-server")))]
+            $item
+        )*
+    }
+}
+
 macro_rules! cfg_oauth{
     ($($item:item)*) => {
         $(
@@ -0,0 +1,291 @@
+//! An in-process fake MangaDex API for the integration tests.
+//!
+//! [`MockMangaDex`] serves the main v5 routes (manga, chapter, cover, at-home, OAuth token,
+//! upload sessions, follows and read markers) from an in-memory [`MockState`],
+//! with the `x-ratelimit-*` headers and the pagination of the real API.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::mock_server::{MockManga, MockMangaDex};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let server = MockMangaDex::start().await;
+//! let manga_id = server.add_manga(MockManga::new("Test Manga"));
+//!
+//! let client = server.client();
+//! let manga = client.manga().id(manga_id).get().send().await?;
+//! assert_eq!(manga.data.id, manga_id);
+//! # Ok(())
+//! # }
+//! ```
+
+mod data;
+mod router;
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use url::Url;
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, MockServer};
+
+use crate::v5::AuthTokens;
+use crate::{HttpClient, MangaDexClient};
+use router::Router;
+
+pub use data::{MockChapter, MockCover, MockManga, MockState, MockUploadFile, MockUploadSession};
+
+/// A fake MangaDex API listening on a random local port.
+///
+/// The server is stopped when this is dropped.
+#[derive(Debug)]
+pub struct MockMangaDex {
+    server: MockServer,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockMangaDex {
+    /// Start a server without any data.
+    pub async fn start() -> Self {
+        Self::start_with_state(MockState::default()).await
+    }
+
+    /// Start a server serving `state`.
+    pub async fn start_with_state(state: MockState) -> Self {
+        let server = MockServer::start().await;
+        let state = Arc::new(Mutex::new(state));
+        Mock::given(any())
+            .respond_with(Router {
+                state: state.clone(),
+                base_url: server.uri(),
+            })
+            .mount(&server)
+            .await;
+        Self { server, state }
+    }
+
+    /// The URL of the server, to use as the API, OAuth and MangaDex@Home base URL.
+    pub fn base_url(&self) -> Url {
+        Url::parse(&self.server.uri()).expect("error parsing the mock server url")
+    }
+
+    /// Get an [`HttpClient`] sending its requests to this server.
+    pub fn http_client(&self) -> HttpClient {
+        let mut builder = HttpClient::builder();
+        builder.base_url(self.base_url());
+        #[cfg(feature = "oauth")]
+        builder.auth_url(self.base_url());
+        builder
+            .build()
+            .expect("error building the mock http client")
+    }
+
+    /// Get a [`MangaDexClient`] sending its requests to this server.
+    pub fn client(&self) -> MangaDexClient {
+        MangaDexClient::new_with_http_client(self.http_client())
+    }
+
+    /// Get a [`MangaDexClient`] logged in on this server.
+    pub async fn logged_in_client(&self) -> MangaDexClient {
+        let client = self.client();
+        client
+            .set_auth_tokens(&self.issue_tokens())
+            .await
+            .expect("error setting the mock auth tokens");
+        client
+    }
+
+    /// Create a session accepted by the authenticated routes.
+    pub fn issue_tokens(&self) -> AuthTokens {
+        self.state().issue_tokens()
+    }
+
+    /// Lock the data served by the server.
+    ///
+    /// Don't hold the guard while sending requests to the server.
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn add_manga(&self, manga: MockManga) -> Uuid {
+        let id = manga.id;
+        self.state().mangas.push(manga);
+        id
+    }
+
+    pub fn add_chapter(&self, chapter: MockChapter) -> Uuid {
+        let id = chapter.id;
+        self.state().chapters.push(chapter);
+        id
+    }
+
+    pub fn add_cover(&self, cover: MockCover) -> Uuid {
+        let id = cover.id;
+        self.state().covers.push(cover);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_types::Language;
+
+    use super::*;
+    use crate::error::Error;
+
+    #[tokio::test]
+    async fn mock_server_paginates_the_collections() -> anyhow::Result<()> {
+        let server = MockMangaDex::start().await;
+        let manga_id = server.add_manga(MockManga::new("Test Manga"));
+        for number in 1..=15 {
+            server.add_chapter(
+                MockChapter::new(manga_id, Language::English).chapter(number.to_string()),
+            );
+        }
+        let client = server.client();
+
+        let feed = client
+            .manga()
+            .id(manga_id)
+            .feed()
+            .get()
+            .limit(10u32)
+            .offset(10u32)
+            .send()
+            .await?;
+        assert_eq!(feed.total, 15);
+        assert_eq!(feed.data.len(), 5);
+        assert_eq!(feed.data[0].attributes.chapter.as_deref(), Some("11"));
+
+        let manga = client.manga().id(manga_id).get().send().await?;
+        assert_eq!(
+            manga.data.attributes.available_translated_languages,
+            vec![Language::English]
+        );
+
+        match client.manga().id(Uuid::new_v4()).get().send().await {
+            Err(Error::Api(error)) => assert_eq!(error.errors[0].status, 404),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_server_tracks_the_user_data() -> anyhow::Result<()> {
+        let server = MockMangaDex::start().await;
+        let manga_id = server.add_manga(MockManga::new("Test Manga"));
+        let chapter_id =
+            server.add_chapter(MockChapter::new(manga_id, Language::English).chapter("1"));
+
+        let anonymous = server.client();
+        assert!(
+            anonymous
+                .manga()
+                .id(manga_id)
+                .follow()
+                .post()
+                .send()
+                .await
+                .is_err()
+        );
+
+        let client = server.logged_in_client().await;
+        client.manga().id(manga_id).follow().post().send().await?;
+        client
+            .manga()
+            .id(manga_id)
+            .read()
+            .post()
+            .mark_chapter_read(chapter_id)
+            .send()
+            .await?;
+
+        let follows = client.user().follows().manga().get().send().await?;
+        assert_eq!(follows.data[0].id, manga_id);
+        let read = client.manga().id(manga_id).read().get().send().await?;
+        assert_eq!(read.data, vec![chapter_id]);
+        assert!(server.state().followed_mangas.contains(&manga_id));
+        Ok(())
+    }
+
+    #[cfg(feature = "oauth")]
+    #[tokio::test]
+    async fn mock_server_issues_oauth_tokens() -> anyhow::Result<()> {
+        use mangadex_api_schema::v5::oauth::ClientInfo;
+        use mangadex_api_types::{Password, Username};
+
+        let server = MockMangaDex::start().await;
+        let manga_id = server.add_manga(MockManga::new("Test Manga"));
+        let client = server.client();
+        client
+            .set_client_info(&non_exhaustive::non_exhaustive!(ClientInfo {
+                client_id: "someClientId".to_string(),
+                client_secret: "someClientSecret".to_string(),
+            }))
+            .await?;
+
+        client
+            .oauth()
+            .login()
+            .username(Username::parse("myusername")?)
+            .password(Password::parse("mypassword")?)
+            .send()
+            .await?;
+        let first = client.get_auth_tokens().await?;
+        client.oauth().refresh().send().await?;
+        let second = client.get_auth_tokens().await?;
+        assert_ne!(first.session, second.session);
+        assert!(!server.state().refresh_tokens.contains(&first.refresh));
+
+        client.manga().id(manga_id).follow().post().send().await?;
+
+        let user_info = client.oauth().userinfo().send().await?;
+        assert_eq!(user_info.preferred_username.as_deref(), Some("mock-user"));
+        client.logout().await?;
+        assert!(!server.state().refresh_tokens.contains(&second.refresh));
+        Ok(())
+    }
+
+    #[cfg(feature = "utils")]
+    #[tokio::test]
+    async fn mock_server_serves_the_uploaded_chapters() -> anyhow::Result<()> {
+        use crate::utils::download::chapter::DownloadMode;
+        use crate::v5::upload::upload_session_id::commit::post::ChapterDraft;
+        use crate::v5::upload::upload_session_id::post::UploadImage;
+
+        let server = MockMangaDex::start().await;
+        let manga_id = server.add_manga(MockManga::new("Test Manga"));
+        let client = server.logged_in_client().await;
+
+        let mut builder = client
+            .upload_chapter(manga_id)
+            .chapter(ChapterDraft::new(Language::English))
+            .terms_accepted(true);
+        for page in 0..12_u32 {
+            let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+            data.extend(800_u32.to_be_bytes());
+            data.extend((1000 + page).to_be_bytes());
+            builder = builder.add_page(UploadImage {
+                filename: format!("{page}.png"),
+                data,
+            });
+        }
+        let chapter = builder.build()?.send().await?;
+        assert!(server.state().upload_session.is_none());
+
+        let pages = client
+            .download()
+            .chapter(chapter.data.id)
+            .mode(DownloadMode::Normal)
+            .build()?
+            .download_element_vec()
+            .await?;
+        assert_eq!(pages.len(), 12);
+        for (index, (filename, data)) in pages.into_iter().enumerate() {
+            assert_eq!(filename, format!("{}.png", index + 1));
+            assert_eq!(data?[20..24], (1000 + index as u32).to_be_bytes());
+        }
+        Ok(())
+    }
+}
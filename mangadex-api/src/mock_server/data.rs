@@ -0,0 +1,314 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use mangadex_api_types::{Language, MangaDexDateTime};
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::v5::AuthTokens;
+use crate::v5::upload::upload_session_id::post::UploadImage;
+
+/// A manga stored in the [`MockMangaDex`](super::MockMangaDex).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MockManga {
+    pub id: Uuid,
+    pub title: String,
+    pub original_language: Language,
+    pub created_at: MangaDexDateTime,
+}
+
+impl MockManga {
+    pub fn new<T: Into<String>>(title: T) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            title: title.into(),
+            original_language: Language::Japanese,
+            created_at: MangaDexDateTime::new(&OffsetDateTime::now_utc()),
+        }
+    }
+}
+
+/// A chapter stored in the [`MockMangaDex`](super::MockMangaDex).
+///
+/// The pages are served by the fake MangaDex@Home server.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MockChapter {
+    pub id: Uuid,
+    pub manga_id: Uuid,
+    pub groups: Vec<Uuid>,
+    pub volume: Option<String>,
+    pub chapter: Option<String>,
+    pub title: Option<String>,
+    pub translated_language: Language,
+    pub hash: String,
+    pub pages: Vec<UploadImage>,
+    pub publish_at: MangaDexDateTime,
+}
+
+impl MockChapter {
+    pub fn new(manga_id: Uuid, translated_language: Language) -> Self {
+        let id = Uuid::new_v4();
+        Self {
+            id,
+            manga_id,
+            groups: Vec::new(),
+            volume: None,
+            chapter: None,
+            title: None,
+            translated_language,
+            hash: id.simple().to_string(),
+            pages: Vec::new(),
+            publish_at: MangaDexDateTime::new(&OffsetDateTime::now_utc()),
+        }
+    }
+
+    pub fn chapter<T: Into<String>>(mut self, chapter: T) -> Self {
+        self.chapter = Some(chapter.into());
+        self
+    }
+
+    pub fn volume<T: Into<String>>(mut self, volume: T) -> Self {
+        self.volume = Some(volume.into());
+        self
+    }
+
+    pub fn add_page<T: Into<String>>(mut self, filename: T, data: Vec<u8>) -> Self {
+        self.pages.push(UploadImage {
+            filename: filename.into(),
+            data,
+        });
+        self
+    }
+}
+
+/// A cover stored in the [`MockMangaDex`](super::MockMangaDex).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MockCover {
+    pub id: Uuid,
+    pub manga_id: Uuid,
+    pub volume: Option<String>,
+    pub file_name: String,
+    pub locale: Option<Language>,
+}
+
+impl MockCover {
+    pub fn new<T: Into<String>>(manga_id: Uuid, file_name: T) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            manga_id,
+            volume: None,
+            file_name: file_name.into(),
+            locale: None,
+        }
+    }
+}
+
+/// A file uploaded in a [`MockUploadSession`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MockUploadFile {
+    pub id: Uuid,
+    pub image: UploadImage,
+}
+
+/// The upload session of the [`MockMangaDex`](super::MockMangaDex) user.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MockUploadSession {
+    pub id: Uuid,
+    pub manga_id: Uuid,
+    pub groups: Vec<Uuid>,
+    pub files: Vec<MockUploadFile>,
+}
+
+/// The data served by the [`MockMangaDex`](super::MockMangaDex).
+///
+/// It can be read and modified with [`MockMangaDex::state`](super::MockMangaDex::state).
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct MockState {
+    pub mangas: Vec<MockManga>,
+    pub chapters: Vec<MockChapter>,
+    pub covers: Vec<MockCover>,
+    /// The manga followed by the user.
+    pub followed_mangas: BTreeSet<Uuid>,
+    /// The chapters read by the user, by manga.
+    pub read_markers: HashMap<Uuid, BTreeSet<Uuid>>,
+    pub upload_session: Option<MockUploadSession>,
+    /// The session tokens accepted by the authenticated routes.
+    pub session_tokens: HashSet<String>,
+    /// The refresh tokens accepted by the OAuth token route.
+    pub refresh_tokens: HashSet<String>,
+}
+
+impl MockState {
+    pub fn manga(&self, id: Uuid) -> Option<&MockManga> {
+        self.mangas.iter().find(|manga| manga.id == id)
+    }
+
+    pub fn chapter(&self, id: Uuid) -> Option<&MockChapter> {
+        self.chapters.iter().find(|chapter| chapter.id == id)
+    }
+
+    /// Create a session accepted by the authenticated routes.
+    pub fn issue_tokens(&mut self) -> AuthTokens {
+        let tokens = non_exhaustive::non_exhaustive!(AuthTokens {
+            session: Uuid::new_v4().simple().to_string(),
+            refresh: Uuid::new_v4().simple().to_string(),
+        });
+        self.session_tokens.insert(tokens.session.clone());
+        self.refresh_tokens.insert(tokens.refresh.clone());
+        tokens
+    }
+
+    pub(crate) fn manga_json(&self, manga: &MockManga) -> Value {
+        let languages: BTreeSet<&str> = self
+            .chapters
+            .iter()
+            .filter(|chapter| chapter.manga_id == manga.id)
+            .map(|chapter| chapter.translated_language.code2())
+            .collect();
+        let relationships: Vec<Value> = self
+            .covers
+            .iter()
+            .filter(|cover| cover.manga_id == manga.id)
+            .take(1)
+            .map(|cover| json!({"id": cover.id, "type": "cover_art"}))
+            .collect();
+        json!({
+            "id": manga.id,
+            "type": "manga",
+            "attributes": {
+                "title": {"en": manga.title},
+                "altTitles": [],
+                "description": {},
+                "isLocked": false,
+                "links": {},
+                "originalLanguage": manga.original_language.code2(),
+                "lastVolume": null,
+                "lastChapter": null,
+                "publicationDemographic": null,
+                "status": "ongoing",
+                "year": null,
+                "contentRating": "safe",
+                "chapterNumbersResetOnNewVolume": false,
+                "availableTranslatedLanguages": languages,
+                "tags": [],
+                "state": "published",
+                "version": 1,
+                "createdAt": manga.created_at.to_string(),
+                "updatedAt": manga.created_at.to_string(),
+            },
+            "relationships": relationships
+        })
+    }
+
+    pub(crate) fn chapter_json(chapter: &MockChapter) -> Value {
+        let mut relationships = vec![json!({"id": chapter.manga_id, "type": "manga"})];
+        relationships.extend(
+            chapter
+                .groups
+                .iter()
+                .map(|group| json!({"id": group, "type": "scanlation_group"})),
+        );
+        json!({
+            "id": chapter.id,
+            "type": "chapter",
+            "attributes": {
+                "title": chapter.title,
+                "volume": chapter.volume,
+                "chapter": chapter.chapter,
+                "pages": chapter.pages.len(),
+                "translatedLanguage": chapter.translated_language.code2(),
+                "externalUrl": null,
+                "version": 1,
+                "createdAt": chapter.publish_at.to_string(),
+                "updatedAt": chapter.publish_at.to_string(),
+                "publishAt": chapter.publish_at.to_string(),
+                "readableAt": chapter.publish_at.to_string(),
+            },
+            "relationships": relationships
+        })
+    }
+
+    pub(crate) fn cover_json(cover: &MockCover) -> Value {
+        let now = MangaDexDateTime::new(&OffsetDateTime::now_utc());
+        json!({
+            "id": cover.id,
+            "type": "cover_art",
+            "attributes": {
+                "volume": cover.volume,
+                "fileName": cover.file_name,
+                "description": "",
+                "locale": cover.locale.map(|locale| locale.code2().to_string()),
+                "version": 1,
+                "createdAt": now.to_string(),
+                "updatedAt": now.to_string(),
+            },
+            "relationships": [{"id": cover.manga_id, "type": "manga"}]
+        })
+    }
+
+    pub(crate) fn upload_session_json(session: &MockUploadSession) -> Value {
+        let now = MangaDexDateTime::new(&OffsetDateTime::now_utc());
+        let mut relationships = vec![json!({"id": session.manga_id, "type": "manga"})];
+        relationships.extend(
+            session
+                .groups
+                .iter()
+                .map(|group| json!({"id": group, "type": "scanlation_group"})),
+        );
+        relationships.extend(
+            session
+                .files
+                .iter()
+                .map(|file| json!({"id": file.id, "type": "upload_session_file"})),
+        );
+        json!({
+            "id": session.id,
+            "type": "upload_session",
+            "attributes": {
+                "isCommitted": false,
+                "isProcessed": false,
+                "isDeleted": false,
+                "version": 1,
+                "createdAt": now.to_string(),
+                "updatedAt": now.to_string(),
+            },
+            "relationships": relationships
+        })
+    }
+
+    pub(crate) fn upload_file_json(file: &MockUploadFile) -> Value {
+        json!({
+            "id": file.id,
+            "type": "upload_session_file",
+            "attributes": {
+                "originalFileName": file.image.filename,
+                "fileHash": Uuid::new_v4().simple().to_string(),
+                "fileSize": file.image.data.len(),
+                "mimeType": mime_type(&file.image.filename),
+                "source": "local",
+                "version": 1,
+            },
+            "relationships": []
+        })
+    }
+}
+
+/// Get the MIME type of an image from its extension.
+pub(crate) fn mime_type(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
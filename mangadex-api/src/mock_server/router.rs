@@ -0,0 +1,608 @@
+// The error responses are returned early with `?`, their size doesn't matter here.
+#![allow(clippy::result_large_err)]
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use mangadex_api_types::Language;
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use url::Url;
+use uuid::Uuid;
+use wiremock::{Request, Respond, ResponseTemplate};
+
+use super::data::{MockChapter, MockState, MockUploadFile, MockUploadSession, mime_type};
+use crate::v5::upload::upload_session_id::post::UploadImage;
+
+/// The maximum number of files uploaded in one request.
+const MAX_FILES_PER_REQUEST: usize = 10;
+/// The maximum number of files in an upload session.
+const MAX_FILES_PER_SESSION: usize = 500;
+/// The maximum `limit` of a collection.
+const MAX_LIMIT: usize = 100;
+/// The maximum `offset + limit` of a collection.
+const MAX_RESULTS: usize = 10_000;
+
+type Reply = Result<ResponseTemplate, ResponseTemplate>;
+
+/// Dispatch the requests to the routes of the fake MangaDex API.
+#[derive(Debug, Clone)]
+pub(crate) struct Router {
+    pub(crate) state: Arc<Mutex<MockState>>,
+    /// The URL of the server, sent as the MangaDex@Home base URL.
+    pub(crate) base_url: String,
+}
+
+impl Respond for Router {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let segments: Vec<&str> = request
+            .url
+            .path_segments()
+            .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+            .unwrap_or_default();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let reply = route(&mut state, request, &segments, &self.base_url);
+        let reset = OffsetDateTime::now_utc().unix_timestamp() + 60;
+        reply
+            .unwrap_or_else(|error| error)
+            .insert_header("x-ratelimit-retry-after", reset.to_string().as_str())
+            .insert_header("x-ratelimit-limit", "40")
+            .insert_header("x-ratelimit-remaining", "39")
+    }
+}
+
+fn route(state: &mut MockState, request: &Request, segments: &[&str], base_url: &str) -> Reply {
+    let query = Query::new(&request.url);
+    match (request.method.as_str(), segments) {
+        ("GET", ["ping"]) => Ok(ResponseTemplate::new(200).set_body_string("pong")),
+        (method, ["realms", "mangadex", "protocol", "openid-connect", endpoint]) => {
+            openid_connect(state, request, method, endpoint)
+        }
+        ("GET", ["manga"]) => {
+            let ids = query.ids("ids");
+            let title = query.get("title").map(str::to_lowercase);
+            let items = state
+                .mangas
+                .iter()
+                .filter(|manga| ids.is_empty() || ids.contains(&manga.id))
+                .filter(|manga| {
+                    title
+                        .as_ref()
+                        .is_none_or(|title| manga.title.to_lowercase().contains(title))
+                })
+                .map(|manga| state.manga_json(manga))
+                .collect();
+            collection(items, &query)
+        }
+        ("GET", ["manga", "read"]) => {
+            authenticate(state, request)?;
+            let ids = query.ids("ids");
+            let markers = ids
+                .iter()
+                .map(|id| (*id, state.read_markers.get(id).cloned().unwrap_or_default()));
+            if query.get("grouped") == Some("true") {
+                let data: serde_json::Map<String, Value> = markers
+                    .map(|(id, chapters)| (id.to_string(), json!(chapters)))
+                    .collect();
+                Ok(ok(json!({"result": "ok", "data": data})))
+            } else {
+                let data: BTreeSet<Uuid> = markers.flat_map(|(_, chapters)| chapters).collect();
+                Ok(ok(json!({"result": "ok", "data": data})))
+            }
+        }
+        ("GET", ["manga", id]) => {
+            let id = parse_id(id)?;
+            let manga = state.manga(id).ok_or_else(|| not_found("Manga"))?;
+            Ok(entity(state.manga_json(manga)))
+        }
+        ("GET", ["manga", id, "feed"]) => {
+            let id = parse_id(id)?;
+            state.manga(id).ok_or_else(|| not_found("Manga"))?;
+            let languages = query.all("translatedLanguage");
+            let items = state
+                .chapters
+                .iter()
+                .filter(|chapter| chapter.manga_id == id)
+                .filter(|chapter| {
+                    languages.is_empty() || languages.contains(&chapter.translated_language.code2())
+                })
+                .map(MockState::chapter_json)
+                .collect();
+            collection(items, &query)
+        }
+        ("GET", ["manga", id, "read"]) => {
+            authenticate(state, request)?;
+            let id = parse_id(id)?;
+            let data = state.read_markers.get(&id).cloned().unwrap_or_default();
+            Ok(ok(json!({"result": "ok", "data": data})))
+        }
+        ("POST", ["manga", id, "read"]) => {
+            authenticate(state, request)?;
+            let id = parse_id(id)?;
+            let body = json_body(request)?;
+            let read = uuids(&body["chapterIdsRead"]);
+            let unread = uuids(&body["chapterIdsUnread"]);
+            let markers = state.read_markers.entry(id).or_default();
+            markers.extend(read);
+            markers.retain(|chapter| !unread.contains(chapter));
+            Ok(ok(json!({"result": "ok"})))
+        }
+        ("POST", ["manga", id, "follow"]) => {
+            authenticate(state, request)?;
+            let id = parse_id(id)?;
+            state.manga(id).ok_or_else(|| not_found("Manga"))?;
+            state.followed_mangas.insert(id);
+            Ok(ok(json!({"result": "ok"})))
+        }
+        ("DELETE", ["manga", id, "follow"]) => {
+            authenticate(state, request)?;
+            let id = parse_id(id)?;
+            state.manga(id).ok_or_else(|| not_found("Manga"))?;
+            state.followed_mangas.remove(&id);
+            Ok(ok(json!({"result": "ok"})))
+        }
+        ("GET", ["user", "follows", "manga"]) => {
+            authenticate(state, request)?;
+            let items = state
+                .mangas
+                .iter()
+                .filter(|manga| state.followed_mangas.contains(&manga.id))
+                .map(|manga| state.manga_json(manga))
+                .collect();
+            collection(items, &query)
+        }
+        ("GET", ["user", "follows", "manga", id]) => {
+            authenticate(state, request)?;
+            let id = parse_id(id)?;
+            if state.followed_mangas.contains(&id) {
+                Ok(ok(json!({"result": "ok"})))
+            } else {
+                Err(not_found("Follow"))
+            }
+        }
+        ("GET", ["chapter"]) => {
+            let ids = query.ids("ids");
+            let mangas = query.ids("manga");
+            let languages = query.all("translatedLanguage");
+            let items = state
+                .chapters
+                .iter()
+                .filter(|chapter| ids.is_empty() || ids.contains(&chapter.id))
+                .filter(|chapter| mangas.is_empty() || mangas.contains(&chapter.manga_id))
+                .filter(|chapter| {
+                    languages.is_empty() || languages.contains(&chapter.translated_language.code2())
+                })
+                .map(MockState::chapter_json)
+                .collect();
+            collection(items, &query)
+        }
+        ("GET", ["chapter", id]) => {
+            let id = parse_id(id)?;
+            let chapter = state.chapter(id).ok_or_else(|| not_found("Chapter"))?;
+            Ok(entity(MockState::chapter_json(chapter)))
+        }
+        ("GET", ["cover"]) => {
+            let ids = query.ids("ids");
+            let mangas = query.ids("manga");
+            let items = state
+                .covers
+                .iter()
+                .filter(|cover| ids.is_empty() || ids.contains(&cover.id))
+                .filter(|cover| mangas.is_empty() || mangas.contains(&cover.manga_id))
+                .map(MockState::cover_json)
+                .collect();
+            collection(items, &query)
+        }
+        ("GET", ["cover", id]) => {
+            let id = parse_id(id)?;
+            let cover = state
+                .covers
+                .iter()
+                .find(|cover| cover.id == id)
+                .ok_or_else(|| not_found("Cover"))?;
+            Ok(entity(MockState::cover_json(cover)))
+        }
+        ("GET", ["at-home", "server", id]) => {
+            let id = parse_id(id)?;
+            let chapter = state.chapter(id).ok_or_else(|| not_found("Chapter"))?;
+            let filenames: Vec<&str> = chapter
+                .pages
+                .iter()
+                .map(|page| page.filename.as_str())
+                .collect();
+            Ok(ok(json!({
+                "result": "ok",
+                "baseUrl": base_url,
+                "chapter": {
+                    "hash": chapter.hash,
+                    "data": filenames,
+                    "dataSaver": filenames,
+                }
+            })))
+        }
+        ("GET", ["data" | "data-saver", hash, filename]) => state
+            .chapters
+            .iter()
+            .filter(|chapter| chapter.hash == *hash)
+            .flat_map(|chapter| chapter.pages.iter())
+            .find(|page| page.filename == *filename)
+            .map(|page| {
+                ResponseTemplate::new(200)
+                    .set_body_raw(page.data.clone(), mime_type(&page.filename))
+            })
+            .ok_or_else(|| ResponseTemplate::new(404)),
+        ("GET", ["upload"]) => {
+            authenticate(state, request)?;
+            let session = state
+                .upload_session
+                .as_ref()
+                .ok_or_else(|| not_found("Upload session"))?;
+            Ok(entity(MockState::upload_session_json(session)))
+        }
+        ("POST", ["upload", "begin"]) => {
+            authenticate(state, request)?;
+            if state.upload_session.is_some() {
+                return Err(error(
+                    400,
+                    "Bad Request",
+                    "An upload session already exists",
+                ));
+            }
+            let body = json_body(request)?;
+            let manga_id = body["manga"]
+                .as_str()
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .ok_or_else(|| bad_request("The manga id is missing"))?;
+            state.manga(manga_id).ok_or_else(|| not_found("Manga"))?;
+            let session = MockUploadSession {
+                id: Uuid::new_v4(),
+                manga_id,
+                groups: uuids(&body["groups"]),
+                files: Vec::new(),
+            };
+            let reply = entity(MockState::upload_session_json(&session));
+            state.upload_session = Some(session);
+            Ok(reply)
+        }
+        ("POST", ["upload", id]) => {
+            authenticate(state, request)?;
+            let session = upload_session(state, id)?;
+            let images = multipart_files(request)
+                .ok_or_else(|| bad_request("The body must be a multipart form"))?;
+            if images.len() > MAX_FILES_PER_REQUEST {
+                return Err(bad_request("Too many files in the request"));
+            }
+            let mut data = Vec::new();
+            let mut errors = Vec::new();
+            for image in images {
+                if session.files.len() >= MAX_FILES_PER_SESSION {
+                    errors.push(error_json(400, "Bad Request", "The upload session is full"));
+                } else if mime_type(&image.filename) == "application/octet-stream" {
+                    errors.push(error_json(
+                        400,
+                        "Bad Request",
+                        &format!("{} is not an accepted image", image.filename),
+                    ));
+                } else {
+                    let file = MockUploadFile {
+                        id: Uuid::new_v4(),
+                        image,
+                    };
+                    data.push(MockState::upload_file_json(&file));
+                    session.files.push(file);
+                }
+            }
+            Ok(ok(json!({"result": "ok", "errors": errors, "data": data})))
+        }
+        ("DELETE", ["upload", id]) => {
+            authenticate(state, request)?;
+            upload_session(state, id)?;
+            state.upload_session = None;
+            Ok(ok(json!({"result": "ok"})))
+        }
+        ("DELETE", ["upload", id, "batch"]) => {
+            authenticate(state, request)?;
+            let files = uuids(&json_body(request)?);
+            let session = upload_session(state, id)?;
+            session.files.retain(|file| !files.contains(&file.id));
+            Ok(ok(json!({"result": "ok"})))
+        }
+        ("POST", ["upload", id, "commit"]) => {
+            authenticate(state, request)?;
+            upload_session(state, id)?;
+            let body = json_body(request)?;
+            let draft = &body["chapterDraft"];
+            let page_order = uuids(&body["pageOrder"]);
+            let Some(session) = state.upload_session.take() else {
+                return Err(not_found("Upload session"));
+            };
+            let mut chapter = MockChapter::new(
+                session.manga_id,
+                Language::from(draft["translatedLanguage"].as_str().unwrap_or_default()),
+            );
+            chapter.groups = session.groups;
+            chapter.volume = draft["volume"].as_str().map(String::from);
+            chapter.chapter = draft["chapter"].as_str().map(String::from);
+            chapter.title = draft["title"].as_str().map(String::from);
+            for (index, id) in page_order.iter().enumerate() {
+                let Some(file) = session.files.iter().find(|file| file.id == *id) else {
+                    return Err(bad_request("The page order has an unknown file"));
+                };
+                let extension = file.image.filename.rsplit_once('.').map_or("", |(_, e)| e);
+                chapter.pages.push(UploadImage {
+                    filename: format!("{}.{extension}", index + 1),
+                    data: file.image.data.clone(),
+                });
+            }
+            let reply = entity(MockState::chapter_json(&chapter));
+            state.chapters.push(chapter);
+            Ok(reply)
+        }
+        ("DELETE", ["upload", id, file_id]) => {
+            authenticate(state, request)?;
+            let file_id = parse_id(file_id)?;
+            let session = upload_session(state, id)?;
+            session.files.retain(|file| file.id != file_id);
+            Ok(ok(json!({"result": "ok"})))
+        }
+        _ => Err(error(404, "Not Found", "No route matches the request")),
+    }
+}
+
+/// The OpenID Connect routes of the auth server.
+fn openid_connect(state: &mut MockState, request: &Request, method: &str, endpoint: &str) -> Reply {
+    match (method, endpoint) {
+        ("POST", "token") => token(state, request),
+        ("POST", "logout" | "revoke") => {
+            for (key, token) in form_params(request) {
+                if key == "refresh_token" || key == "token" {
+                    state.refresh_tokens.remove(&token);
+                    state.session_tokens.remove(&token);
+                }
+            }
+            Ok(ResponseTemplate::new(204))
+        }
+        ("GET", "userinfo") => {
+            authenticate(state, request)?;
+            Ok(ok(json!({
+                "sub": Uuid::nil(),
+                "email_verified": true,
+                "preferred_username": "mock-user",
+                "roles": ["ROLE_USER"],
+            })))
+        }
+        _ => Err(error(404, "Not Found", "No route matches the request")),
+    }
+}
+
+/// The OAuth token route, for the `password`, `authorization_code` and `refresh_token` grants.
+fn token(state: &mut MockState, request: &Request) -> Reply {
+    let params = form_params(request);
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let oauth_error = |error: &str| {
+        ResponseTemplate::new(400).set_body_json(json!({
+            "error": error,
+            "error_description": error,
+        }))
+    };
+    match param("grant_type") {
+        Some("password") => {}
+        Some("authorization_code") if param("code").is_some() => {}
+        Some("refresh_token") => {
+            let refresh = param("refresh_token").unwrap_or_default();
+            if !state.refresh_tokens.remove(refresh) {
+                return Err(oauth_error("invalid_grant"));
+            }
+        }
+        _ => return Err(oauth_error("unsupported_grant_type")),
+    }
+    let tokens = state.issue_tokens();
+    Ok(ok(json!({
+        "access_token": tokens.session,
+        "expires_in": 900,
+        "refresh_expires_in": 7776000,
+        "refresh_token": tokens.refresh,
+        "token_type": "Bearer",
+        "not-before-policy": 0,
+        "session_state": Uuid::new_v4(),
+        "scope": "groups email profile",
+        "client_type": "personal"
+    })))
+}
+
+/// The parameters of an `application/x-www-form-urlencoded` body.
+fn form_params(request: &Request) -> Vec<(String, String)> {
+    url::form_urlencoded::parse(&request.body)
+        .into_owned()
+        .collect()
+}
+
+fn authenticate(state: &MockState, request: &Request) -> Result<(), ResponseTemplate> {
+    let session = request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match session {
+        Some(session) if state.session_tokens.contains(session) => Ok(()),
+        _ => Err(error(
+            401,
+            "Unauthorized",
+            "The session is missing or expired",
+        )),
+    }
+}
+
+fn upload_session<'a>(
+    state: &'a mut MockState,
+    id: &str,
+) -> Result<&'a mut MockUploadSession, ResponseTemplate> {
+    let id = parse_id(id)?;
+    state
+        .upload_session
+        .as_mut()
+        .filter(|session| session.id == id)
+        .ok_or_else(|| not_found("Upload session"))
+}
+
+/// Read the files of a `multipart/form-data` body.
+fn multipart_files(request: &Request) -> Option<Vec<UploadImage>> {
+    let content_type = request.headers.get("content-type")?.to_str().ok()?;
+    let boundary = content_type.split_once("boundary=")?.1.trim_matches('"');
+    let delimiter = format!("\r\n--{boundary}");
+    // Prefix the body with a line break to find the first delimiter like the others.
+    let body = [b"\r\n".as_slice(), &request.body].concat();
+    let mut files = Vec::new();
+    let mut start = find(&body, delimiter.as_bytes(), 0)? + delimiter.len();
+    while let Some(end) = find(&body, delimiter.as_bytes(), start) {
+        let part = &body[start..end];
+        let headers_end = find(part, b"\r\n\r\n", 0)?;
+        let headers = String::from_utf8_lossy(&part[..headers_end]);
+        let filename = headers
+            .split_once("filename=\"")
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(filename, _)| filename.to_string())?;
+        files.push(UploadImage {
+            filename,
+            data: part[headers_end + 4..].to_vec(),
+        });
+        start = end + delimiter.len();
+    }
+    Some(files)
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+/// The query parameters of a request.
+struct Query(Vec<(String, String)>);
+
+impl Query {
+    fn new(url: &Url) -> Self {
+        Self(url.query_pairs().into_owned().collect())
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get the values of an array parameter, serialized as `key[]` or `key[0]`.
+    fn all(&self, key: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(name, _)| {
+                name == key
+                    || name
+                        .strip_prefix(key)
+                        .is_some_and(|index| index.starts_with('['))
+            })
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn ids(&self, key: &str) -> Vec<Uuid> {
+        self.all(key)
+            .into_iter()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect()
+    }
+
+    fn usize(&self, key: &str) -> Result<Option<usize>, ResponseTemplate> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| bad_request(&format!("{key} must be a positive integer")))
+            })
+            .transpose()
+    }
+}
+
+fn collection(items: Vec<Value>, query: &Query) -> Reply {
+    let limit = query.usize("limit")?.unwrap_or(10);
+    let offset = query.usize("offset")?.unwrap_or(0);
+    if limit > MAX_LIMIT {
+        return Err(bad_request(&format!(
+            "limit must be lower than {MAX_LIMIT}"
+        )));
+    }
+    if offset + limit > MAX_RESULTS {
+        return Err(bad_request(&format!(
+            "offset + limit must be lower than {MAX_RESULTS}"
+        )));
+    }
+    let total = items.len();
+    let data: Vec<Value> = items.into_iter().skip(offset).take(limit).collect();
+    Ok(ok(json!({
+        "result": "ok",
+        "response": "collection",
+        "data": data,
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+    })))
+}
+
+fn entity(data: Value) -> ResponseTemplate {
+    ok(json!({"result": "ok", "response": "entity", "data": data}))
+}
+
+fn ok(body: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(body)
+}
+
+fn error_json(status: u16, title: &str, detail: &str) -> Value {
+    json!({
+        "id": Uuid::new_v4(),
+        "status": status,
+        "title": title,
+        "detail": detail,
+    })
+}
+
+fn error(status: u16, title: &str, detail: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_json(json!({
+        "result": "error",
+        "errors": [error_json(status, title, detail)],
+    }))
+}
+
+fn bad_request(detail: &str) -> ResponseTemplate {
+    error(400, "Bad Request", detail)
+}
+
+fn not_found(resource: &str) -> ResponseTemplate {
+    error(404, "Not Found", &format!("{resource} could not be found"))
+}
+
+fn parse_id(id: &str) -> Result<Uuid, ResponseTemplate> {
+    Uuid::parse_str(id).map_err(|_| bad_request(&format!("{id} is not a valid id")))
+}
+
+fn json_body(request: &Request) -> Result<Value, ResponseTemplate> {
+    serde_json::from_slice(&request.body).map_err(|_| bad_request("The body must be JSON"))
+}
+
+fn uuids(value: &Value) -> Vec<Uuid> {
+    value
+        .as_array()
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str().and_then(|id| Uuid::parse_str(id).ok()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,352 @@
+//! `tracing` spans and metrics for the requests, enabled with the `tracing` and `metrics` features.
+//!
+//! Each endpoint call gets a `mangadex.request` span and each MangaDex@Home page a
+//! `mangadex.at_home.page` span.
+//! The metrics are:
+//!
+//! - `mangadex_requests_total` (`method`, `route`, `status`): the responses received.
+//! - `mangadex_request_retries_total` (`method`, `route`): the retried requests.
+//! - `mangadex_request_duration_seconds` (`method`, `route`): the duration of the endpoint calls,
+//!   retries included.
+//! - `mangadex_ratelimit_remaining` (`method`, `route`): the last `x-ratelimit-remaining` header.
+//! - `mangadex_at_home_pages_total` (`cache`, `success`): the downloaded pages.
+//! - `mangadex_at_home_bytes_total` (`cache`): the downloaded bytes.
+//! - `mangadex_at_home_page_duration_seconds`: the duration of the page downloads.
+
+use std::future::Future;
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use std::time::Instant;
+
+use reqwest::{Method, Response};
+
+use crate::Result;
+use crate::error::Error;
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use crate::rate_limit::RateLimit;
+
+/// Replace the ids of an endpoint path with `{}` to group the calls by route.
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+pub(crate) fn route_template(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if uuid::Uuid::parse_str(segment).is_ok() {
+                "{}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Observes an endpoint call, with its retries.
+pub(crate) struct RequestObserver {
+    #[cfg(feature = "metrics")]
+    method: Method,
+    #[cfg(feature = "metrics")]
+    route: String,
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl RequestObserver {
+    #[cfg_attr(
+        not(any(feature = "tracing", feature = "metrics")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn new(method: &Method, path: &str) -> Self {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let route = route_template(path);
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "mangadex.request",
+                method = %method,
+                route = %route,
+                status = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                ratelimit_remaining = tracing::field::Empty,
+                retries = tracing::field::Empty,
+            ),
+            #[cfg(feature = "metrics")]
+            method: method.clone(),
+            #[cfg(feature = "metrics")]
+            route,
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            start: Instant::now(),
+        }
+    }
+
+    /// Run `future` in the request span.
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        return tracing::Instrument::instrument(future, self.span.clone());
+        #[cfg(not(feature = "tracing"))]
+        future
+    }
+
+    #[cfg(feature = "metrics")]
+    fn labels(&self) -> [(&'static str, String); 2] {
+        [
+            ("method", self.method.to_string()),
+            ("route", self.route.clone()),
+        ]
+    }
+
+    /// Record a response, before its status is checked.
+    #[cfg_attr(
+        not(any(feature = "tracing", feature = "metrics")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn response(&self, res: &Response) {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let status = res.status().as_u16();
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let remaining = RateLimit::try_from(res).ok().map(|limit| limit.remaining);
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("status", status);
+            if let Some(remaining) = remaining {
+                self.span.record("ratelimit_remaining", remaining);
+            }
+        }
+        #[cfg(feature = "metrics")]
+        {
+            let [method, route] = self.labels();
+            metrics::counter!(
+                "mangadex_requests_total",
+                &[
+                    method.clone(),
+                    route.clone(),
+                    ("status", status.to_string())
+                ]
+            )
+            .increment(1);
+            if let Some(remaining) = remaining {
+                metrics::gauge!("mangadex_ratelimit_remaining", &[method, route])
+                    .set(remaining as f64);
+            }
+        }
+    }
+
+    /// Record that the request is sent again after `error`.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn retry(&self, attempt: u32, error: &Error) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(attempt, %error, "retrying the request");
+        #[cfg(feature = "metrics")]
+        metrics::counter!("mangadex_request_retries_total", &self.labels()).increment(1);
+    }
+
+    /// Record the outcome of the call after `attempts` attempts.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn finish(&self, attempts: u32, res: &Result<Response>) {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let latency = self.start.elapsed();
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("latency_ms", latency.as_millis() as u64);
+            self.span.record("retries", attempts - 1);
+            if let Err(error) = res {
+                self.span
+                    .in_scope(|| tracing::error!(%error, "the request failed"));
+            }
+        }
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("mangadex_request_duration_seconds", &self.labels())
+            .record(latency.as_secs_f64());
+    }
+}
+
+/// Report a failed load or save of the token store.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn token_store_failed(error: &Error) {
+    #[cfg(feature = "tracing")]
+    tracing::error!(%error, "the token store failed");
+}
+
+/// Run the download of a MangaDex@Home page in its span.
+///
+/// The outcome is recorded with [`page_downloaded`].
+#[cfg(feature = "utils")]
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn instrument_page<F: Future>(
+    node: &str,
+    filename: &str,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    #[cfg(feature = "tracing")]
+    return tracing::Instrument::instrument(
+        future,
+        tracing::info_span!(
+            "mangadex.at_home.page",
+            node = %node,
+            filename = %filename,
+            bytes = tracing::field::Empty,
+            cached = tracing::field::Empty,
+            success = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+    );
+    #[cfg(not(feature = "tracing"))]
+    future
+}
+
+/// Record the outcome of a MangaDex@Home page download, in its span.
+#[cfg(feature = "utils")]
+#[cfg_attr(
+    not(any(feature = "tracing", feature = "metrics")),
+    allow(unused_variables)
+)]
+pub(crate) fn page_downloaded(
+    bytes: usize,
+    success: bool,
+    cached: bool,
+    duration: std::time::Duration,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("bytes", bytes);
+        span.record("cached", cached);
+        span.record("success", success);
+        span.record("latency_ms", duration.as_millis() as u64);
+    }
+    #[cfg(feature = "metrics")]
+    {
+        let cache = if cached { "hit" } else { "miss" };
+        metrics::counter!(
+            "mangadex_at_home_pages_total",
+            "cache" => cache,
+            "success" => success.to_string()
+        )
+        .increment(1);
+        metrics::counter!("mangadex_at_home_bytes_total", "cache" => cache).increment(bytes as u64);
+        metrics::histogram!("mangadex_at_home_page_duration_seconds")
+            .record(duration.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    #[test]
+    fn ids_are_replaced_in_the_route() {
+        use super::route_template;
+
+        assert_eq!(
+            route_template("/manga/a3219a4f-73c0-4213-8730-05985130539a/feed"),
+            "/manga/{}/feed"
+        );
+        assert_eq!(route_template("/manga/tag"), "/manga/tag");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn requests_are_traced() -> anyhow::Result<()> {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        use serde_json::json;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+        use url::Url;
+        use wiremock::matchers::{method, path_regex};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        use crate::{HttpClient, MangaDexClient};
+
+        type Fields = Arc<Mutex<Vec<(String, String)>>>;
+
+        struct Visitor(Fields);
+
+        impl Visit for Visitor {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((field.name().to_string(), format!("{value:?}")));
+            }
+        }
+
+        struct FieldRecorder {
+            fields: Fields,
+            next_id: AtomicU64,
+        }
+
+        impl tracing::Subscriber for FieldRecorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                span.record(&mut Visitor(self.fields.clone()));
+                Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+            }
+            fn record(&self, _: &Id, values: &Record<'_>) {
+                values.record(&mut Visitor(self.fields.clone()));
+            }
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let fields = Fields::default();
+        let _guard = tracing::subscriber::set_default(FieldRecorder {
+            fields: fields.clone(),
+            next_id: AtomicU64::new(0),
+        });
+
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-retry-after", "1698723860")
+                    .insert_header("x-ratelimit-limit", "40")
+                    .insert_header("x-ratelimit-remaining", "39")
+                    .set_body_json(json!({
+                        "result": "ok",
+                        "baseUrl": "https://example.org",
+                        "chapter": {
+                            "hash": "somehash",
+                            "data": ["1.jpg"],
+                            "dataSaver": ["1.jpg"],
+                        }
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        mangadex_client
+            .at_home()
+            .server()
+            .id(uuid::Uuid::new_v4())
+            .get()
+            .send()
+            .await?;
+
+        let fields = fields.lock().unwrap().clone();
+        for (name, value) in [
+            ("method", "GET"),
+            ("route", "/at-home/server/{}"),
+            ("status", "200"),
+            ("ratelimit_remaining", "39"),
+            ("retries", "0"),
+        ] {
+            assert!(
+                fields.contains(&(name.to_string(), value.to_string())),
+                "{name} = {value} is missing from {fields:?}"
+            );
+        }
+        Ok(())
+    }
+}
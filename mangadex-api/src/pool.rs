@@ -0,0 +1,198 @@
+//! Several MangaDex accounts sharing the same connections.
+//!
+//! A [`ClientPool`] holds one [`MangaDexClient`] per account, created from a template
+//! [`HttpClient`]. The accounts share the template [`reqwest::Client`] (and so its connection pool),
+//! its [`RateLimiter`](crate::rate_limit::RateLimiter), cache, middlewares and retry policy,
+//! but each one has its own tokens.
+//! The template client info is kept, so the accounts of the same personal client can share it.
+//! The template token store is not shared since it would mix the accounts tokens,
+//! set one per account with [`ClientPool::add_account_with`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::pool::ClientPool;
+//! use mangadex_api::rate_limit::RateLimiter;
+//! use mangadex_api::HttpClient;
+//! use uuid::Uuid;
+//!
+//! # async fn run(auth_tokens: mangadex_api_schema::v5::AuthTokens) -> anyhow::Result<()> {
+//! let pool = ClientPool::new(
+//!     HttpClient::builder()
+//!         .rate_limiter(RateLimiter::default())
+//!         .build()?,
+//! );
+//! pool.add_account_with("moderator", |http_client| http_client.set_auth_tokens(&auth_tokens));
+//!
+//! pool.as_account("moderator")?
+//!     .manga()
+//!     .id(Uuid::new_v4())
+//!     .follow()
+//!     .post()
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::error::Error;
+use crate::{HttpClient, MangaDexClient, Result};
+
+/// A pool of authenticated [`MangaDexClient`]s, by account name.
+///
+/// Cloning the pool is cheap, the clones share the accounts.
+#[derive(Debug, Clone)]
+pub struct ClientPool {
+    template: HttpClient,
+    accounts: Arc<RwLock<HashMap<String, MangaDexClient>>>,
+}
+
+impl ClientPool {
+    /// Create an empty pool whose accounts are based on `template`.
+    pub fn new(template: HttpClient) -> Self {
+        Self {
+            template,
+            accounts: Default::default(),
+        }
+    }
+
+    /// A client without account, sharing the connections of the pool.
+    pub fn anonymous(&self) -> MangaDexClient {
+        MangaDexClient::new_with_http_client(self.account_http_client())
+    }
+
+    /// Add an account without tokens, to log in with the returned client.
+    ///
+    /// An account with the same name is replaced.
+    pub fn add_account<K: Into<String>>(&self, account: K) -> MangaDexClient {
+        self.add_account_with(account, |_| ())
+    }
+
+    /// Add an account, configuring its [`HttpClient`] with `configure`.
+    ///
+    /// This is where the tokens, client info or token store of the account are set.
+    /// An account with the same name is replaced.
+    pub fn add_account_with<K, F>(&self, account: K, configure: F) -> MangaDexClient
+    where
+        K: Into<String>,
+        F: FnOnce(&mut HttpClient),
+    {
+        let mut http_client = self.account_http_client();
+        configure(&mut http_client);
+        // This loads the tokens of the token store set by `configure`.
+        let client = MangaDexClient::new_with_http_client(http_client);
+        self.accounts
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(account.into(), client.clone());
+        client
+    }
+
+    /// Get the client of `account`.
+    ///
+    /// Fails with [`Error::UnknownAccount`] if the account was not added.
+    pub fn as_account(&self, account: &str) -> Result<MangaDexClient> {
+        self.accounts
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(account)
+            .cloned()
+            .ok_or_else(|| Error::UnknownAccount(account.to_string()))
+    }
+
+    /// Remove `account` from the pool and return its client.
+    pub fn remove_account(&self, account: &str) -> Option<MangaDexClient> {
+        self.accounts
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(account)
+    }
+
+    /// The names of the accounts, in no particular order.
+    pub fn accounts(&self) -> Vec<String> {
+        self.accounts
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// A copy of the template without its tokens and token store.
+    fn account_http_client(&self) -> HttpClient {
+        let mut http_client = self.template.clone();
+        http_client.clear_token_store();
+        http_client.clear_auth_tokens();
+        http_client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{header, method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::ClientPool;
+    use crate::HttpClient;
+    use crate::error::Error;
+    use crate::rate_limit::RateLimiter;
+    use crate::v5::AuthTokens;
+
+    #[tokio::test]
+    async fn accounts_send_their_own_tokens() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let pool = ClientPool::new(
+            HttpClient::builder()
+                .base_url(Url::parse(&mock_server.uri())?)
+                .rate_limiter(RateLimiter::default())
+                .build()?,
+        );
+        for account in ["first", "second"] {
+            pool.add_account_with(account, |http_client| {
+                http_client.set_auth_tokens(&non_exhaustive::non_exhaustive!(AuthTokens {
+                    session: format!("{account}-session"),
+                    refresh: format!("{account}-refresh"),
+                }))
+            });
+            Mock::given(method("POST"))
+                .and(path_regex(r"/manga/[0-9a-fA-F-]+/follow"))
+                .and(header("authorization", format!("Bearer {account}-session")))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!({"result": "ok"})),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        for account in ["first", "second"] {
+            pool.as_account(account)?
+                .manga()
+                .id(Uuid::new_v4())
+                .follow()
+                .post()
+                .send()
+                .await?;
+        }
+
+        let first = pool.as_account("first")?.get_http_client();
+        let second = pool.as_account("second")?.get_http_client();
+        assert!(Arc::ptr_eq(
+            first.read().await.get_rate_limiter().unwrap(),
+            second.read().await.get_rate_limiter().unwrap()
+        ));
+        assert!(pool.anonymous().get_auth_tokens().await.is_err());
+        assert!(matches!(
+            pool.as_account("third"),
+            Err(Error::UnknownAccount(_))
+        ));
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+pub mod limiter;
+
 use std::{num::ParseIntError, ops::Deref};
 
 use reqwest::{
@@ -9,6 +11,8 @@ use time::OffsetDateTime;
 
 use mangadex_api_types::MangaDexDateTime;
 
+pub use limiter::{Quota, RateLimiter};
+
 pub const LIMIT: &str = "x-ratelimit-limit";
 
 pub const REMAINING: &str = "x-ratelimit-remaining";
@@ -0,0 +1,401 @@
+//! Client-side rate limiter.
+//!
+//! MangaDex enforces a global limit of 5 requests per second per IP
+//! and stricter limits on some routes.
+//! More details at <https://api.mangadex.org/docs/2-limitations/#endpoint-specific-rate-limits>
+//!
+//! The [`RateLimiter`] delays the requests before they are sent
+//! and synchronizes its buckets with the `x-ratelimit-*` headers returned by the server.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//! use mangadex_api::rate_limit::limiter::RateLimiter;
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let http_client = HttpClient::builder()
+//!     .rate_limiter(RateLimiter::default())
+//!     .build()?;
+//!
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::Method;
+use time::OffsetDateTime;
+use tokio::time::Instant;
+
+use super::RateLimit;
+use crate::http_client::route::RoutePattern;
+
+/// A number of requests allowed per period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Quota {
+    pub limit: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub const fn new(limit: u32, period: Duration) -> Self {
+        Self { limit, period }
+    }
+    pub const fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+    pub const fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+    pub const fn per_hour(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60 * 60))
+    }
+}
+
+#[derive(Debug)]
+struct BucketState {
+    remaining: u32,
+    reset_at: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    quota: Quota,
+    state: Mutex<BucketState>,
+}
+
+impl Bucket {
+    fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            state: Mutex::new(BucketState {
+                remaining: quota.limit,
+                reset_at: Instant::now(),
+            }),
+        }
+    }
+    /// Take a token or return the instant when the next token is available.
+    fn try_acquire(&self) -> Result<(), Instant> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if now >= state.reset_at {
+            state.remaining = self.quota.limit;
+            state.reset_at = now + self.quota.period;
+        }
+        if state.remaining > 0 {
+            state.remaining -= 1;
+            Ok(())
+        } else {
+            Err(state.reset_at)
+        }
+    }
+    fn update(&self, remaining: u32, reset_at: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.remaining = remaining;
+        state.reset_at = reset_at;
+    }
+    fn block_until(&self, reset_at: Instant) {
+        self.update(0, reset_at);
+    }
+}
+
+#[derive(Debug)]
+struct RouteBucket {
+    pattern: RoutePattern,
+    bucket: Bucket,
+}
+
+/// A rate limiter with an optional global bucket and per-route buckets.
+///
+/// [`RateLimiter::default`] contains the limits documented by MangaDex.
+/// Use [`RateLimiter::new`] to start from an empty limiter.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct RateLimiter {
+    global: Option<Bucket>,
+    routes: Vec<RouteBucket>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+            .with_global(Quota::per_second(5))
+            .with_route(Method::POST, "/account/create", Quota::per_hour(5))
+            .with_route(Method::POST, "/auth/login", Quota::per_hour(30))
+            .with_route(Method::POST, "/auth/refresh", Quota::per_hour(60))
+            .with_route(Method::GET, "/at-home/server/{}", Quota::per_minute(40))
+            .with_route(Method::POST, "/author", Quota::per_hour(10))
+            .with_route(
+                Method::POST,
+                "/captcha/solve",
+                Quota::new(10, Duration::from_secs(600)),
+            )
+            .with_route(Method::PUT, "/chapter/{}", Quota::per_minute(10))
+            .with_route(Method::DELETE, "/chapter/{}", Quota::per_minute(10))
+            .with_route(
+                Method::POST,
+                "/chapter/{}/read",
+                Quota::new(300, Duration::from_secs(600)),
+            )
+            .with_route(
+                Method::POST,
+                "/cover/{}",
+                Quota::new(100, Duration::from_secs(600)),
+            )
+            .with_route(
+                Method::PUT,
+                "/cover/{}",
+                Quota::new(100, Duration::from_secs(600)),
+            )
+            .with_route(
+                Method::DELETE,
+                "/cover/{}",
+                Quota::new(10, Duration::from_secs(600)),
+            )
+            .with_route(Method::POST, "/group", Quota::per_hour(10))
+            .with_route(Method::PUT, "/group/{}", Quota::per_minute(10))
+            .with_route(
+                Method::DELETE,
+                "/group/{}",
+                Quota::new(10, Duration::from_secs(600)),
+            )
+            .with_route(Method::POST, "/manga", Quota::per_hour(10))
+            .with_route(Method::PUT, "/manga/{}", Quota::per_minute(10))
+            .with_route(
+                Method::DELETE,
+                "/manga/{}",
+                Quota::new(10, Duration::from_secs(600)),
+            )
+            .with_route(Method::POST, "/manga/draft/{}/commit", Quota::per_hour(10))
+            .with_route(Method::POST, "/report", Quota::per_minute(10))
+            .with_route(Method::GET, "/report", Quota::per_minute(10))
+            .with_route(Method::POST, "/upload/begin", Quota::per_minute(10))
+            .with_route(Method::POST, "/upload/begin/{}", Quota::per_minute(10))
+            .with_route(Method::POST, "/upload/{}", Quota::per_minute(250))
+            .with_route(Method::DELETE, "/upload/{}/{}", Quota::per_minute(250))
+            .with_route(Method::DELETE, "/upload/{}/batch", Quota::per_minute(250))
+            .with_route(Method::POST, "/upload/{}/commit", Quota::per_minute(10))
+            .with_route(Method::POST, "/forums/thread", Quota::per_minute(10))
+    }
+}
+
+impl RateLimiter {
+    /// Create a limiter without any limit.
+    pub fn new() -> Self {
+        Self {
+            global: None,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Set the quota shared by every request.
+    pub fn with_global(mut self, quota: Quota) -> Self {
+        self.global = Some(Bucket::new(quota));
+        self
+    }
+
+    /// Add (or replace) a quota for a route.
+    ///
+    /// `path` is the endpoint path where `{}` matches any single segment (e.g. `/at-home/server/{}`).
+    pub fn with_route(mut self, method: Method, path: &str, quota: Quota) -> Self {
+        let pattern = RoutePattern::new(method, path);
+        self.routes.retain(|route| route.pattern != pattern);
+        self.routes.push(RouteBucket {
+            pattern,
+            bucket: Bucket::new(quota),
+        });
+        self
+    }
+
+    fn route(&self, method: &Method, path: &str) -> Option<&Bucket> {
+        self.routes
+            .iter()
+            .find(|route| route.pattern.matches(method, path))
+            .map(|route| &route.bucket)
+    }
+
+    /// Wait until the request can be sent without exceeding the global and route quotas.
+    pub async fn acquire(&self, method: &Method, path: &str) {
+        if let Some(route) = self.route(method, path) {
+            while let Err(reset_at) = route.try_acquire() {
+                tokio::time::sleep_until(reset_at).await;
+            }
+        }
+        if let Some(global) = &self.global {
+            while let Err(reset_at) = global.try_acquire() {
+                tokio::time::sleep_until(reset_at).await;
+            }
+        }
+    }
+
+    /// Synchronize the route bucket with the rate limit returned by the server.
+    pub fn update(&self, method: &Method, path: &str, rate_limit: &RateLimit) {
+        if let Some(route) = self.route(method, path) {
+            route.update(rate_limit.remaining, instant_of(rate_limit));
+        }
+    }
+
+    /// Block the route bucket (or the global one if the route is unknown)
+    /// until the `x-ratelimit-retry-after` date.
+    ///
+    /// This is called when the server responded with a `429 Too Many Requests`.
+    pub fn block(&self, method: &Method, path: &str, rate_limit: &RateLimit) {
+        let reset_at = instant_of(rate_limit);
+        if let Some(route) = self.route(method, path) {
+            route.block_until(reset_at);
+        } else if let Some(global) = &self.global {
+            global.block_until(reset_at);
+        }
+    }
+}
+
+/// Convert the `x-ratelimit-retry-after` date into an [`Instant`].
+fn instant_of(rate_limit: &RateLimit) -> Instant {
+    let wait = *rate_limit.retry_after.as_ref() - OffsetDateTime::now_utc();
+    Instant::now() + Duration::try_from(wait).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::Method;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use serde_json::json;
+    use time::OffsetDateTime;
+    use tokio::time::Instant;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{Quota, RateLimiter};
+    use crate::error::Error;
+    use crate::rate_limit::{LIMIT, REMAINING, RETRY_AFTER, RateLimit};
+    use crate::{HttpClient, MangaDexClient};
+
+    #[tokio::test]
+    async fn rate_limiter_delays_requests_over_the_quota() {
+        let limiter = RateLimiter::new().with_route(
+            Method::GET,
+            "/at-home/server/{}",
+            Quota::new(2, Duration::from_millis(300)),
+        );
+        let start = Instant::now();
+        for _ in 0..2 {
+            limiter
+                .acquire(&Method::GET, "/at-home/server/some-id")
+                .await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        limiter
+            .acquire(&Method::GET, "/at-home/server/some-id")
+            .await;
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        // Other routes are not limited
+        let start = Instant::now();
+        for _ in 0..10 {
+            limiter.acquire(&Method::GET, "/manga").await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_is_updated_from_the_response_headers() -> anyhow::Result<()> {
+        let limiter =
+            RateLimiter::new().with_route(Method::GET, "/at-home/server/{}", Quota::per_minute(40));
+        let mut headers = HeaderMap::new();
+        headers.append(LIMIT, HeaderValue::from_static("40"));
+        headers.append(REMAINING, HeaderValue::from_static("0"));
+        headers.append(
+            RETRY_AFTER,
+            HeaderValue::from(
+                (OffsetDateTime::now_utc() + time::Duration::seconds(2)).unix_timestamp(),
+            ),
+        );
+        let rate_limit = RateLimit::try_from(&headers)?;
+        limiter.update(&Method::GET, "/at-home/server/some-id", &rate_limit);
+
+        let start = Instant::now();
+        limiter
+            .acquire(&Method::GET, "/at-home/server/some-id")
+            .await;
+        assert!(start.elapsed() >= Duration::from_millis(500));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_waits_after_too_many_requests() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .rate_limiter(RateLimiter::default())
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let retry_after = (OffsetDateTime::now_utc() + time::Duration::seconds(2)).unix_timestamp();
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("x-ratelimit-retry-after", retry_after.to_string())
+                    .insert_header("x-ratelimit-limit", "40")
+                    .insert_header("x-ratelimit-remaining", "0"),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-retry-after", retry_after.to_string())
+                    .insert_header("x-ratelimit-limit", "40")
+                    .insert_header("x-ratelimit-remaining", "39")
+                    .set_body_json(json!({
+                        "result": "ok",
+                        "baseUrl": "https://example.org",
+                        "chapter": {
+                            "hash": "hash",
+                            "data": ["1.jpg"],
+                            "dataSaver": ["1.jpg"],
+                        }
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let chapter_id = Uuid::new_v4();
+        let res = mangadex_client
+            .at_home()
+            .server()
+            .id(chapter_id)
+            .get()
+            .send()
+            .await;
+        assert!(matches!(res, Err(Error::RateLimitExcedeed)));
+
+        let start = Instant::now();
+        let res = mangadex_client
+            .at_home()
+            .server()
+            .id(chapter_id)
+            .get()
+            .send()
+            .await?;
+        assert!(start.elapsed() >= Duration::from_millis(500));
+        assert_eq!(res.rate_limit.remaining, 39);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+//! Persistence of the authentication tokens.
+//!
+//! A [`TokenStore`] set on the [`HttpClient`](crate::HttpClient) is loaded by [`MangaDexClient`](crate::MangaDexClient) at startup
+//! and saved every time the tokens or the client info are set, cleared or refreshed.
+//!
+//! This crate provides:
+//!
+//! - [`FileTokenStore`]: a JSON file readable only by its owner,
+//! - [`MemoryTokenStore`]: an in-memory store, useful for tests.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::token_store::FileTokenStore;
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let http_client = HttpClient::builder()
+//!     .token_store(FileTokenStore::new("mangadex-tokens.json"))
+//!     .build()?;
+//!
+//! // The tokens saved by a previous run are loaded here.
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use mangadex_api_schema::v5::AuthTokens;
+use mangadex_api_schema::v5::oauth::ClientInfo;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::Result;
+use crate::atomic_file;
+use crate::error::Error;
+
+/// The data saved by a [`TokenStore`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[non_exhaustive]
+pub struct StoredTokens {
+    pub auth_tokens: Option<AuthTokens>,
+    /// The date when the session (access token) expires.
+    #[serde(default, with = "unix_timestamp")]
+    pub session_expiration: Option<OffsetDateTime>,
+    pub client_info: Option<ClientInfo>,
+}
+
+impl StoredTokens {
+    pub fn new(
+        auth_tokens: Option<AuthTokens>,
+        session_expiration: Option<OffsetDateTime>,
+        client_info: Option<ClientInfo>,
+    ) -> Self {
+        Self {
+            auth_tokens,
+            session_expiration,
+            client_info,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.auth_tokens.is_none() && self.client_info.is_none()
+    }
+}
+
+impl Serialize for StoredTokens {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        #[derive(Serialize)]
+        struct AuthTokensRef<'a> {
+            session: &'a str,
+            refresh: &'a str,
+        }
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ClientInfoRef<'a> {
+            client_id: &'a str,
+            client_secret: &'a str,
+        }
+        #[derive(Serialize)]
+        struct StoredTokensRef<'a> {
+            auth_tokens: Option<AuthTokensRef<'a>>,
+            #[serde(with = "unix_timestamp")]
+            session_expiration: &'a Option<OffsetDateTime>,
+            client_info: Option<ClientInfoRef<'a>>,
+        }
+        StoredTokensRef {
+            auth_tokens: self.auth_tokens.as_ref().map(|tokens| AuthTokensRef {
+                session: &tokens.session,
+                refresh: &tokens.refresh,
+            }),
+            session_expiration: &self.session_expiration,
+            client_info: self.client_info.as_ref().map(|info| ClientInfoRef {
+                client_id: &info.client_id,
+                client_secret: &info.client_secret,
+            }),
+        }
+        .serialize(serializer)
+    }
+}
+
+mod unix_timestamp {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use time::OffsetDateTime;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<OffsetDateTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(date) => serializer.serialize_some(&date.unix_timestamp()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<OffsetDateTime>, D::Error> {
+        Option::<i64>::deserialize(deserializer)?
+            .map(OffsetDateTime::from_unix_timestamp)
+            .transpose()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Save `tokens` into `store`, or clear it if there is nothing to save.
+pub(crate) fn write(store: &dyn TokenStore, tokens: &StoredTokens) -> Result<()> {
+    if tokens.is_empty() {
+        store.clear()
+    } else {
+        store.save(tokens)
+    }
+}
+
+/// [`write`] on the blocking thread pool, so the file stores don't stall the async runtime.
+pub(crate) async fn write_blocking(store: Arc<dyn TokenStore>, tokens: StoredTokens) -> Result<()> {
+    tokio::task::spawn_blocking(move || write(store.as_ref(), &tokens))
+        .await
+        .map_err(|e| Error::unknow(e.to_string()))?
+}
+
+/// A storage for the authentication tokens.
+pub trait TokenStore: Send + Sync {
+    /// Load the saved tokens, if any.
+    fn load(&self) -> Result<Option<StoredTokens>>;
+
+    /// Save the tokens, replacing the previous ones.
+    fn save(&self, tokens: &StoredTokens) -> Result<()>;
+
+    /// Remove the saved tokens.
+    fn clear(&self) -> Result<()>;
+}
+
+impl Debug for dyn TokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenStore")
+    }
+}
+
+impl<S: TokenStore + ?Sized> TokenStore for Arc<S> {
+    fn load(&self) -> Result<Option<StoredTokens>> {
+        (**self).load()
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<()> {
+        (**self).save(tokens)
+    }
+
+    fn clear(&self) -> Result<()> {
+        (**self).clear()
+    }
+}
+
+/// Store the tokens in a JSON file.
+///
+/// On Unix, the file is created with the `0600` permissions.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FileTokenStore {
+    pub path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>> {
+        match std::fs::read(&self.path) {
+            Ok(content) => Ok(Some(
+                serde_json::from_slice(&content).map_err(|e| Error::ParseError(e.to_string()))?,
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<()> {
+        let content =
+            serde_json::to_vec_pretty(tokens).map_err(|e| Error::ParseError(e.to_string()))?;
+        atomic_file::write_private(&self.path, &content)?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Store the tokens in memory.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct MemoryTokenStore {
+    tokens: Mutex<Option<StoredTokens>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>> {
+        Ok(self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<()> {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokens.clone());
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        *self.tokens.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "oauth")]
+    use std::sync::Arc;
+
+    use mangadex_api_schema::v5::AuthTokens;
+    use mangadex_api_schema::v5::oauth::ClientInfo;
+    #[cfg(feature = "oauth")]
+    use serde_json::json;
+    use time::OffsetDateTime;
+    #[cfg(feature = "oauth")]
+    use url::Url;
+    use uuid::Uuid;
+    #[cfg(feature = "oauth")]
+    use wiremock::matchers::{method, path};
+    #[cfg(feature = "oauth")]
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[cfg(feature = "oauth")]
+    use super::MemoryTokenStore;
+    use super::{FileTokenStore, StoredTokens, TokenStore};
+    #[cfg(feature = "oauth")]
+    use crate::{HttpClient, MangaDexClient};
+
+    fn auth_tokens(session: &str) -> AuthTokens {
+        non_exhaustive::non_exhaustive!(AuthTokens {
+            session: session.to_string(),
+            refresh: "refreshtoken".to_string(),
+        })
+    }
+
+    fn client_info() -> ClientInfo {
+        non_exhaustive::non_exhaustive!(ClientInfo {
+            client_id: "someClientId".to_string(),
+            client_secret: "someClientSecret".to_string(),
+        })
+    }
+
+    #[test]
+    fn file_token_store_saves_and_loads_tokens() -> anyhow::Result<()> {
+        let store =
+            FileTokenStore::new(std::env::temp_dir().join(format!("{}.json", Uuid::new_v4())));
+        assert!(store.load()?.is_none());
+
+        let expiration = OffsetDateTime::from_unix_timestamp(1698723860)?;
+        store.save(&StoredTokens::new(
+            Some(auth_tokens("sessiontoken")),
+            Some(expiration),
+            Some(client_info()),
+        ))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&store.path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = store.load()?.expect("the tokens should be saved");
+        assert_eq!(loaded.auth_tokens, Some(auth_tokens("sessiontoken")));
+        assert_eq!(loaded.session_expiration, Some(expiration));
+        assert_eq!(
+            loaded.client_info.map(|info| info.client_id),
+            Some("someClientId".to_string())
+        );
+
+        store.clear()?;
+        assert!(store.load()?.is_none());
+        Ok(())
+    }
+
+    struct FailingTokenStore;
+
+    impl TokenStore for FailingTokenStore {
+        fn load(&self) -> crate::Result<Option<StoredTokens>> {
+            Err(crate::error::Error::unknow("unreadable"))
+        }
+
+        fn save(&self, _: &StoredTokens) -> crate::Result<()> {
+            Err(crate::error::Error::unknow("read-only"))
+        }
+
+        fn clear(&self) -> crate::Result<()> {
+            Err(crate::error::Error::unknow("read-only"))
+        }
+    }
+
+    #[tokio::test]
+    async fn token_store_errors_are_reported() -> anyhow::Result<()> {
+        let http_client = crate::HttpClient::builder()
+            .token_store(FailingTokenStore)
+            .build()?;
+        let mangadex_client = crate::MangaDexClient::new_with_http_client(http_client);
+        assert!(
+            mangadex_client
+                .get_http_client()
+                .read()
+                .await
+                .get_token_store_error()
+                .is_some()
+        );
+
+        let res = mangadex_client
+            .set_auth_tokens(&auth_tokens("sessiontoken"))
+            .await;
+        assert!(matches!(res, Err(crate::error::Error::TokenStore(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "oauth")]
+    #[tokio::test]
+    async fn client_loads_and_persists_tokens() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let store = Arc::new(MemoryTokenStore::new());
+        store.save(&StoredTokens::new(
+            Some(auth_tokens("storedsession")),
+            None,
+            Some(client_info()),
+        ))?;
+
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
+            .token_store(store.clone())
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+        assert_eq!(
+            mangadex_client.get_auth_tokens().await?,
+            auth_tokens("storedsession")
+        );
+
+        Mock::given(method("POST"))
+            .and(path(r"/realms/mangadex/protocol/openid-connect/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "refreshedsession",
+                "expires_in": 900,
+                "refresh_expires_in": 2414162,
+                "refresh_token": "refreshtoken",
+                "token_type": "Bearer",
+                "not-before-policy": 0,
+                "session_state": "c176499d-6e8d-4ddf-ad59-6d922be66431",
+                "scope": "groups email profile",
+                "client_type": "personal"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mangadex_client.oauth().refresh().send().await?;
+
+        let stored = store.load()?.expect("the tokens should be saved");
+        assert_eq!(stored.auth_tokens, Some(auth_tokens("refreshedsession")));
+        assert!(stored.session_expiration.is_some());
+
+        mangadex_client.clear_auth_tokens().await?;
+        let stored = store
+            .load()?
+            .expect("the client info should still be saved");
+        assert!(stored.auth_tokens.is_none());
+        assert!(stored.client_info.is_some());
+
+        Ok(())
+    }
+}
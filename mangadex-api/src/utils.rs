@@ -1,2 +1,6 @@
 pub mod download;
+pub mod feed_sync;
+pub mod paginate;
+pub mod reading_progress;
+pub mod resolve;
 pub mod upload;
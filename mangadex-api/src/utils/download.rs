@@ -1,5 +1,6 @@
 pub mod chapter;
 pub mod cover;
+pub mod sink;
 
 use bytes::Bytes;
 use uuid::Uuid;
@@ -4,18 +4,21 @@ mod report;
 use std::sync::Arc;
 
 use crate::Result;
+use crate::error::Error;
 use async_stream::stream;
 use derive_builder::Builder;
+use futures::StreamExt;
 use mangadex_api_schema::v5::AtHomeServer;
 use reqwest::Response;
 use tokio::pin;
+use tokio::sync::Mutex;
 use tokio_stream::Stream;
-use tokio_stream::StreamExt;
 use uuid::Uuid;
 
 use crate::{HttpClientRef, MangaDexClient};
 
 use super::DownloadElement;
+use super::sink::ChapterSink;
 
 pub use mode::DownloadMode;
 pub use pre_download::AtHomePreDownloadImageData;
@@ -45,16 +48,35 @@ pub struct ChapterDownload {
     /// However, some misbehaving school/office network will at time block traffic to non-standard
     /// ports, and setting this flag to true will ensure selection of a server that uses these.
     force_port_443: bool,
+    /// Number of pages downloaded at the same time.
+    ///
+    /// Defaults to `1`, which downloads the pages one after another.
+    #[builder(default)]
+    concurrency: Option<usize>,
+    /// Number of times a failed page is downloaded again from a new MangaDex@Home server.
+    ///
+    /// Defaults to `2`.
+    #[builder(default)]
+    max_retries: Option<u32>,
+    /// Download the pages that still fail with the other quality.
+    #[builder(default)]
+    quality_fallback: bool,
+    #[builder(setter(skip))]
+    at_home: Arc<Mutex<Option<Arc<AtHomeServer>>>>,
     /// Chapter Id
     id: Uuid,
 }
 
 impl ChapterDownload {
-    pub async fn build_at_home_urls_as_stream(
-        &self,
-    ) -> Result<impl Stream<Item = AtHomePreDownloadImageData> + '_> {
+    fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(2)
+    }
+    fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(1).max(1)
+    }
+    async fn request_at_home(&self) -> Result<Arc<AtHomeServer>> {
         let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
-        let at_home: Arc<AtHomeServer> = Arc::new(
+        Ok(Arc::new(
             client
                 .at_home()
                 .server()
@@ -64,8 +86,30 @@ impl ChapterDownload {
                 .send()
                 .await?
                 .body,
-        );
-        let http_client = client.get_reqwest_client().await;
+        ))
+    }
+    /// Get a new MangaDex@Home server to replace `stale`.
+    ///
+    /// If another page already replaced it, its server is reused instead of sending a new request.
+    async fn refresh_at_home(&self, stale: &Arc<AtHomeServer>) -> Result<Arc<AtHomeServer>> {
+        let mut current = self.at_home.lock().await;
+        if let Some(at_home) = current.as_ref()
+            && !Arc::ptr_eq(at_home, stale)
+        {
+            return Ok(Arc::clone(at_home));
+        }
+        let at_home = self.request_at_home().await?;
+        *current = Some(Arc::clone(&at_home));
+        Ok(at_home)
+    }
+    pub async fn build_at_home_urls_as_stream(
+        &self,
+    ) -> Result<impl Stream<Item = AtHomePreDownloadImageData> + '_> {
+        let at_home = self.request_at_home().await?;
+        *self.at_home.lock().await = Some(Arc::clone(&at_home));
+        let http_client = MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+            .get_reqwest_client()
+            .await;
         let page_filenames = match self.mode.unwrap_or_default() {
             DownloadMode::Normal => Arc::clone(&at_home).chapter.data.clone(),
             DownloadMode::DataSaver => Arc::clone(&at_home).chapter.data_saver.clone(),
@@ -92,27 +136,117 @@ impl ChapterDownload {
         }
         Ok(datas)
     }
-    pub async fn download_element_vec(&self) -> Result<Vec<DownloadElement>> {
-        let file_names = self.build_at_home_urls().await?;
-        let mut datas: Vec<DownloadElement> = Vec::new();
-        for filename in file_names {
-            datas.push(filename.download().await);
+    /// Download the page, switching to a new MangaDex@Home server when it fails.
+    async fn download_page_with_retries<C>(
+        &self,
+        page: &mut AtHomePreDownloadImageData,
+        should_skip: C,
+    ) -> DownloadElement
+    where
+        C: FnMut(&AtHomePreDownloadImageData, &Response) -> bool + Copy,
+    {
+        let mut data = page.download_with_checker(should_skip).await;
+        for _ in 0..self.max_retries() {
+            match &data.1 {
+                Ok(_) | Err(Error::SkippedDownload(_)) => break,
+                Err(_) => {}
+            }
+            match self.refresh_at_home(&page.at_home).await {
+                Ok(at_home) => page.at_home = at_home,
+                Err(e) => return (page.filename.clone(), Err(e)),
+            }
+            data = page.download_with_checker(should_skip).await;
         }
-        Ok(datas)
+        data
+    }
+    /// Download the `index`-th page (starting at 0).
+    ///
+    /// If every attempt failed and `quality_fallback` is enabled,
+    /// the page is downloaded again with the other quality.
+    async fn download_page<C>(
+        &self,
+        index: usize,
+        mut page: AtHomePreDownloadImageData,
+        should_skip: C,
+    ) -> DownloadElement
+    where
+        C: FnMut(&AtHomePreDownloadImageData, &Response) -> bool + Copy,
+    {
+        let data = self
+            .download_page_with_retries(&mut page, should_skip)
+            .await;
+        match &data.1 {
+            Ok(_) | Err(Error::SkippedDownload(_)) => return data,
+            Err(_) if !self.quality_fallback => return data,
+            Err(_) => {}
+        }
+        let quality = match page.quality {
+            DownloadMode::Normal => DownloadMode::DataSaver,
+            DownloadMode::DataSaver => DownloadMode::Normal,
+        };
+        let filenames = match quality {
+            DownloadMode::Normal => &page.at_home.chapter.data,
+            DownloadMode::DataSaver => &page.at_home.chapter.data_saver,
+        };
+        let Some(filename) = filenames.get(index).cloned() else {
+            return data;
+        };
+        let mut fallback = AtHomePreDownloadImageData {
+            filename,
+            quality,
+            ..page
+        };
+        self.download_page_with_retries(&mut fallback, should_skip)
+            .await
+    }
+    pub async fn download_element_vec(&self) -> Result<Vec<DownloadElement>> {
+        let stream_ = self.download_stream().await?;
+        Ok(stream_.map(|(data, _, _)| data).collect().await)
     }
+    /// Download chapter with stream output
+    ///
+    /// The pages are yielded in order with their position (starting at 1) and the number of pages.
     pub async fn download_stream(
         &self,
+    ) -> Result<impl Stream<Item = (DownloadElement, usize, usize)> + '_> {
+        self.download_stream_with_checker(|_, _| false).await
+    }
+    /// Download chapter with stream output
+    ///
+    /// Same as [`ChapterDownload::download_stream`] but the pages are yielded as soon as they are downloaded.
+    /// Use the page position to put them back in order.
+    pub async fn download_stream_unordered(
+        &self,
     ) -> Result<impl Stream<Item = (DownloadElement, usize, usize)> + '_> {
         let file_names = self.build_at_home_urls().await?;
-        let mut index: usize = 0;
         let len = file_names.len();
-        Ok(stream! {
-            for filename in file_names {
-                let data = filename.download().await;
-                index += 1;
-                yield (data, index, len);
-            }
-        })
+        Ok(futures::stream::iter(file_names.into_iter().enumerate())
+            .map(move |(index, filename)| async move {
+                (
+                    self.download_page(index, filename, |_, _| false).await,
+                    index + 1,
+                    len,
+                )
+            })
+            .buffer_unordered(self.concurrency()))
+    }
+    /// Download the chapter into a [`ChapterSink`].
+    ///
+    /// All the pages are downloaded, without checker, and the first page that fails aborts the download.
+    ///
+    /// The sink is called on the blocking thread pool.
+    pub async fn download_to<S: ChapterSink + Send + 'static>(&self, mut sink: S) -> Result<()> {
+        let stream_ = self.download_stream().await?;
+        pin!(stream_);
+        while let Some(((filename, bytes), index, len)) = stream_.next().await {
+            let bytes = bytes?;
+            sink = run_blocking(move || {
+                sink.write_page(index, len, &filename, &bytes)?;
+                Ok(sink)
+            })
+            .await?;
+        }
+        run_blocking(move || sink.finish()).await
     }
     /// Download chapter with stream output
     pub async fn download_stream_with_checker<C>(
@@ -123,18 +257,30 @@ impl ChapterDownload {
         C: FnMut(&AtHomePreDownloadImageData, &Response) -> bool + std::marker::Copy,
     {
         let file_names = self.build_at_home_urls().await?;
-        let mut index: usize = 0;
         let len = file_names.len();
-        Ok(stream! {
-            for filename in file_names {
-                let data = filename.download_with_checker(should_check_).await;
-                index += 1;
-                yield (data, index, len);
-            }
-        })
+        Ok(futures::stream::iter(file_names.into_iter().enumerate())
+            .map(move |(index, filename)| async move {
+                (
+                    self.download_page(index, filename, should_check_).await,
+                    index + 1,
+                    len,
+                )
+            })
+            .buffered(self.concurrency()))
     }
 }
 
+/// Run a call of a [`ChapterSink`] on the blocking thread pool.
+async fn run_blocking<T, F>(call: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(call)
+        .await
+        .map_err(|e| Error::unknow(e.to_string()))?
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{utils::download::chapter::DownloadMode, MangaDexClient};
@@ -147,6 +293,259 @@ mod tests {
     use tokio::pin;
     use tokio_stream::StreamExt;
 
+    async fn mock_chapter(mock_server: &wiremock::MockServer, pages: usize) -> Result<()> {
+        use serde_json::json;
+        use wiremock::matchers::{method, path, path_regex};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let page_filenames: Vec<String> = (1..=pages).map(|page| format!("{page}.jpg")).collect();
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-retry-after", "1698723860")
+                    .insert_header("x-ratelimit-limit", "40")
+                    .insert_header("x-ratelimit-remaining", "39")
+                    .set_body_json(json!({
+                        "result": "ok",
+                        "baseUrl": mock_server.uri(),
+                        "chapter": {
+                            "hash": "somehash",
+                            "data": page_filenames,
+                            "dataSaver": page_filenames,
+                        }
+                    })),
+            )
+            .mount(mock_server)
+            .await;
+        for page in 1..=pages {
+            // The first pages are the slowest so they complete last.
+            Mock::given(method("GET"))
+                .and(path(format!("/data/somehash/{page}.jpg")))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_bytes(page.to_string())
+                        .set_delay(std::time::Duration::from_millis(50 * (pages - page) as u64)),
+                )
+                .expect(1)
+                .mount(mock_server)
+                .await;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_download_keeps_page_order() -> Result<()> {
+        let mock_server = wiremock::MockServer::start().await;
+        mock_chapter(&mock_server, 6).await?;
+        let http_client = crate::HttpClient::builder()
+            .base_url(url::Url::parse(&mock_server.uri())?)
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+
+        let start = std::time::Instant::now();
+        let pages = client
+            .download()
+            .chapter(uuid::Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .concurrency(6_usize)
+            .build()?
+            .download_element_vec()
+            .await?;
+        // Sequentially, this would take 50 * (0 + 1 + ... + 5) = 750 ms.
+        assert!(start.elapsed() < std::time::Duration::from_millis(500));
+
+        let pages = pages
+            .into_iter()
+            .map(|(filename, bytes)| Ok((filename, bytes?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (index, (filename, bytes)) in pages.iter().enumerate() {
+            assert_eq!(filename, &format!("{}.jpg", index + 1));
+            assert_eq!(bytes.as_ref(), (index + 1).to_string().as_bytes());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_to_writes_the_pages_in_a_directory() -> Result<()> {
+        use crate::utils::download::sink::DirectorySink;
+
+        let mock_server = wiremock::MockServer::start().await;
+        mock_chapter(&mock_server, 2).await?;
+        let http_client = crate::HttpClient::builder()
+            .base_url(url::Url::parse(&mock_server.uri())?)
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+
+        let output_dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        client
+            .download()
+            .chapter(uuid::Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .build()?
+            .download_to(DirectorySink::new(&output_dir))
+            .await?;
+        assert_eq!(std::fs::read(output_dir.join("001.jpg"))?, b"1");
+        assert_eq!(std::fs::read(output_dir.join("002.jpg"))?, b"2");
+        assert_eq!(std::fs::read_dir(&output_dir)?.count(), 2);
+
+        std::fs::remove_dir_all(output_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unordered_download_yields_pages_as_they_complete() -> Result<()> {
+        let mock_server = wiremock::MockServer::start().await;
+        mock_chapter(&mock_server, 3).await?;
+        let http_client = crate::HttpClient::builder()
+            .base_url(url::Url::parse(&mock_server.uri())?)
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+
+        let download = client
+            .download()
+            .chapter(uuid::Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .concurrency(3_usize)
+            .build()?;
+        let stream_ = download.download_stream_unordered().await?;
+        pin!(stream_);
+        let mut indexes = Vec::new();
+        while let Some((_, index, len)) = stream_.next().await {
+            assert_eq!(len, 3);
+            indexes.push(index);
+        }
+        assert_eq!(indexes, vec![3, 2, 1]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_pages_are_retried_on_a_new_server() -> Result<()> {
+        use serde_json::json;
+        use wiremock::matchers::{method, path_regex};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        let broken_node = MockServer::start().await;
+        let working_node = MockServer::start().await;
+        let at_home = |base_url: String| {
+            ResponseTemplate::new(200)
+                .insert_header("x-ratelimit-retry-after", "1698723860")
+                .insert_header("x-ratelimit-limit", "40")
+                .insert_header("x-ratelimit-remaining", "39")
+                .set_body_json(json!({
+                    "result": "ok",
+                    "baseUrl": base_url,
+                    "chapter": {
+                        "hash": "somehash",
+                        "data": ["1.jpg", "2.jpg", "3.jpg"],
+                        "dataSaver": ["1.jpg", "2.jpg", "3.jpg"],
+                    }
+                }))
+        };
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(at_home(broken_node.uri()))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(at_home(working_node.uri()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(3)
+            .mount(&broken_node)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/data/somehash/[0-9]\.jpg"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes("page"))
+            .expect(3)
+            .mount(&working_node)
+            .await;
+
+        let http_client = crate::HttpClient::builder()
+            .base_url(url::Url::parse(&mock_server.uri())?)
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+        let pages = client
+            .download()
+            .chapter(uuid::Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .concurrency(3_usize)
+            .build()?
+            .download_element_vec()
+            .await?;
+        assert_eq!(pages.len(), 3);
+        for (_, bytes) in pages {
+            assert_eq!(bytes?.as_ref(), b"page");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_pages_fall_back_to_the_other_quality() -> Result<()> {
+        use serde_json::json;
+        use wiremock::matchers::{method, path, path_regex};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/at-home/server/[0-9a-fA-F-]+"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-retry-after", "1698723860")
+                    .insert_header("x-ratelimit-limit", "40")
+                    .insert_header("x-ratelimit-remaining", "39")
+                    .set_body_json(json!({
+                        "result": "ok",
+                        "baseUrl": mock_server.uri(),
+                        "chapter": {
+                            "hash": "somehash",
+                            "data": ["1.png"],
+                            "dataSaver": ["1.jpg"],
+                        }
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/data/somehash/1.png"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/data-saver/somehash/1.jpg"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes("page"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let http_client = crate::HttpClient::builder()
+            .base_url(url::Url::parse(&mock_server.uri())?)
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+        let mut pages = client
+            .download()
+            .chapter(uuid::Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .max_retries(0_u32)
+            .quality_fallback(true)
+            .build()?
+            .download_element_vec()
+            .await?;
+        let (filename, bytes) = pages.pop().expect("the chapter has one page");
+        assert_eq!(filename, "1.jpg");
+        assert_eq!(bytes?.as_ref(), b"page");
+        Ok(())
+    }
+
     #[tokio::test]
     async fn download_chapter_save() -> Result<()> {
         let output_dir = "./test-outputs/";
@@ -34,8 +34,9 @@ impl AtHomePreDownloadImageData {
         success: bool,
         cached: bool,
     ) {
+        let end = Instant::now();
+        crate::observe::page_downloaded(bytes, success, cached, end.duration_since(start));
         if self.report {
-            let end = Instant::now();
             let _ = AtHomeReport {
                 url: page_url,
                 success,
@@ -61,7 +62,18 @@ impl AtHomePreDownloadImageData {
     pub async fn download(&self) -> DownloadElement {
         self.download_with_checker(|_, _| false).await
     }
-    pub async fn download_with_checker<C>(&self, mut should_skip: C) -> DownloadElement
+    pub async fn download_with_checker<C>(&self, should_skip: C) -> DownloadElement
+    where
+        C: FnMut(&Self, &Response) -> bool,
+    {
+        crate::observe::instrument_page(
+            self.at_home.base_url.host_str().unwrap_or_default(),
+            &self.filename,
+            self.fetch_with_checker(should_skip),
+        )
+        .await
+    }
+    async fn fetch_with_checker<C>(&self, mut should_skip: C) -> DownloadElement
     where
         C: FnMut(&Self, &Response) -> bool,
     {
@@ -78,6 +90,17 @@ impl AtHomePreDownloadImageData {
                 return (self.filename.clone(), Err(Error::RequestError(e)));
             }
         };
+        if !res.status().is_success() {
+            self.report(start, page_url_clone, 0, false, false).await;
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            let error = if status.is_client_error() {
+                Error::ClientError(status.as_u16(), body)
+            } else {
+                Error::ServerError(status.as_u16(), body)
+            };
+            return (self.filename.clone(), Err(error));
+        }
         if should_skip(self, &res) {
             return (
                 self.filename.clone(),
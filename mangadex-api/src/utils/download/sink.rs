@@ -0,0 +1,597 @@
+//! Save the downloaded chapters on the disk.
+//!
+//! A [`ChapterSink`] receives the pages downloaded by [`ChapterDownload::download_to`](super::chapter::ChapterDownload::download_to).
+//!
+//! This crate provides:
+//!
+//! - [`DirectorySink`]: one file per page in a directory,
+//! - [`CbzSink`]: a CBZ archive with a `ComicInfo.xml` file,
+//! - [`EpubSink`]: an EPUB book with one page per image.
+//!
+//! The pages are named after their position, zero-padded (e.g. `001.jpg`),
+//! and written to a temporary file renamed once complete.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api::utils::download::sink::{CbzSink, ComicInfo};
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! let chapter_id = Uuid::new_v4();
+//!
+//! let mut comic_info = ComicInfo::default();
+//! comic_info.series = Some("Some manga".to_string());
+//!
+//! client
+//!     .download()
+//!     .chapter(chapter_id)
+//!     .concurrency(4_usize)
+//!     .build()?
+//!     .download_to(CbzSink::new(format!("{chapter_id}.cbz")).with_comic_info(comic_info))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use mangadex_api_schema::v5::{ChapterAttributes, LocalizedString, MangaAttributes};
+use mangadex_api_types::Language;
+use time::OffsetDateTime;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::Result;
+use crate::atomic_file;
+
+/// A destination for the pages of a chapter.
+///
+/// [`ChapterDownload::download_to`](super::chapter::ChapterDownload::download_to) calls the sink
+/// on the blocking thread pool, so the implementations can write to the disk synchronously.
+pub trait ChapterSink {
+    /// Write the `index`-th page (starting at 1) out of `len`.
+    ///
+    /// `filename` is the name of the page on the MangaDex@Home server.
+    fn write_page(&mut self, index: usize, len: usize, filename: &str, bytes: &[u8]) -> Result<()>;
+
+    /// Called once every page is written.
+    fn finish(self) -> Result<()>;
+}
+
+/// Get the name of the `index`-th page (starting at 1) out of `len`.
+///
+/// The position is zero-padded to at least three digits and the extension of `filename` is kept.
+///
+/// ```rust
+/// use mangadex_api::utils::download::sink::page_file_name;
+///
+/// assert_eq!(page_file_name(7, 24, "x7-4bd3a6f1.png"), "007.png");
+/// assert_eq!(page_file_name(7, 1024, "x7-4bd3a6f1.png"), "0007.png");
+/// ```
+pub fn page_file_name(index: usize, len: usize, filename: &str) -> String {
+    let width = len.to_string().len().max(3);
+    match Path::new(filename).extension() {
+        Some(extension) => format!("{index:0width$}.{}", extension.to_string_lossy()),
+        None => format!("{index:0width$}"),
+    }
+}
+
+fn media_type(file_name: &str) -> &'static str {
+    match Path::new(file_name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write every page in a directory.
+///
+/// The directory is created if needed.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DirectorySink {
+    pub path: PathBuf,
+}
+
+impl DirectorySink {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl ChapterSink for DirectorySink {
+    fn write_page(&mut self, index: usize, len: usize, filename: &str, bytes: &[u8]) -> Result<()> {
+        std::fs::create_dir_all(&self.path)?;
+        let path = self.path.join(page_file_name(index, len, filename));
+        atomic_file::write(&path, bytes)?;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The metadata written in the `ComicInfo.xml` file of a CBZ archive and in the EPUB package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ComicInfo {
+    /// The chapter title.
+    pub title: Option<String>,
+    /// The manga title.
+    pub series: Option<String>,
+    /// The chapter number.
+    pub number: Option<String>,
+    pub volume: Option<String>,
+    pub summary: Option<String>,
+    pub year: Option<u16>,
+    pub writer: Option<String>,
+    pub penciller: Option<String>,
+    /// The manga tags.
+    pub genre: Vec<String>,
+    /// The link to the chapter.
+    pub web: Option<String>,
+    /// The language of the chapter.
+    pub language: Option<Language>,
+}
+
+/// Get the English value of a localized string, or any other value.
+fn localized(value: &LocalizedString, fallback: Language) -> Option<String> {
+    value
+        .get(&Language::English)
+        .or_else(|| value.get(&fallback))
+        .or_else(|| value.values().next())
+        .cloned()
+}
+
+impl ComicInfo {
+    /// Fill the metadata from the attributes of a manga and one of its chapters.
+    pub fn from_attributes(manga: &MangaAttributes, chapter: &ChapterAttributes) -> Self {
+        Self {
+            title: chapter.title.clone().filter(|title| !title.is_empty()),
+            series: localized(&manga.title, manga.original_language),
+            number: chapter.chapter.clone(),
+            volume: chapter.volume.clone(),
+            summary: localized(&manga.description, manga.original_language),
+            year: manga.year,
+            genre: manga
+                .tags
+                .iter()
+                .filter_map(|tag| localized(&tag.attributes.name, Language::English))
+                .collect(),
+            language: Some(chapter.translated_language),
+            ..Default::default()
+        }
+    }
+
+    /// Build the `ComicInfo.xml` file.
+    pub fn to_xml(&self, page_count: usize) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+        );
+        let mut push = |element: &str, value: Option<String>| {
+            if let Some(value) = value {
+                xml.push_str(&format!(
+                    "  <{element}>{}</{element}>\n",
+                    escape_xml(&value)
+                ));
+            }
+        };
+        push("Title", self.title.clone());
+        push("Series", self.series.clone());
+        push("Number", self.number.clone());
+        push("Volume", self.volume.clone());
+        push("Summary", self.summary.clone());
+        push("Year", self.year.map(|year| year.to_string()));
+        push("Writer", self.writer.clone());
+        push("Penciller", self.penciller.clone());
+        push(
+            "Genre",
+            (!self.genre.is_empty()).then(|| self.genre.join(", ")),
+        );
+        push("Web", self.web.clone());
+        push("PageCount", Some(page_count.to_string()));
+        push(
+            "LanguageISO",
+            self.language.map(|language| language.code2().to_string()),
+        );
+        push("Manga", Some("Yes".to_string()));
+        xml.push_str("</ComicInfo>\n");
+        xml
+    }
+}
+
+/// Store a file in the archive.
+///
+/// The files are not compressed: the pages are already compressed images,
+/// which is what most comic readers expect, and the EPUB `mimetype` entry must be stored anyway.
+fn add_file<W: Write + Seek>(zip: &mut ZipWriter<W>, name: &str, bytes: &[u8]) -> Result<()> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(bytes.len() as u64 >= u64::from(u32::MAX));
+    zip.start_file(name, options)
+        .map_err(std::io::Error::from)?;
+    zip.write_all(bytes)?;
+    Ok(())
+}
+
+/// An archive written to a temporary file, removed if it is not finished.
+struct PendingArchive {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    zip: Option<ZipWriter<BufWriter<File>>>,
+}
+
+impl PendingArchive {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            tmp_path: atomic_file::temporary_path(path),
+            zip: None,
+        }
+    }
+
+    fn zip(&mut self) -> Result<&mut ZipWriter<BufWriter<File>>> {
+        if self.zip.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            self.zip = Some(ZipWriter::new(BufWriter::new(File::create(
+                &self.tmp_path,
+            )?)));
+        }
+        Ok(self.zip.as_mut().expect("the archive was just created"))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.zip()?;
+        let zip = self.zip.take().expect("the archive was just created");
+        let result = zip
+            .finish()
+            .map_err(std::io::Error::from)
+            .and_then(|writer| writer.into_inner().map_err(|e| e.into_error()))
+            .and_then(|file| atomic_file::persist(file, &self.tmp_path, &self.path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+        Ok(result?)
+    }
+}
+
+impl Drop for PendingArchive {
+    fn drop(&mut self) {
+        if self.zip.take().is_some() {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+impl std::fmt::Debug for PendingArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingArchive")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Write the pages in a CBZ archive.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CbzSink {
+    archive: PendingArchive,
+    comic_info: Option<ComicInfo>,
+    page_count: usize,
+}
+
+impl CbzSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            archive: PendingArchive::new(path.as_ref()),
+            comic_info: None,
+            page_count: 0,
+        }
+    }
+
+    /// Add a `ComicInfo.xml` file to the archive.
+    pub fn with_comic_info(mut self, comic_info: ComicInfo) -> Self {
+        self.comic_info = Some(comic_info);
+        self
+    }
+}
+
+impl ChapterSink for CbzSink {
+    fn write_page(&mut self, index: usize, len: usize, filename: &str, bytes: &[u8]) -> Result<()> {
+        add_file(
+            self.archive.zip()?,
+            &page_file_name(index, len, filename),
+            bytes,
+        )?;
+        self.page_count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if let Some(comic_info) = &self.comic_info {
+            add_file(
+                self.archive.zip()?,
+                "ComicInfo.xml",
+                comic_info.to_xml(self.page_count).as_bytes(),
+            )?;
+        }
+        self.archive.finish()
+    }
+}
+
+/// Write the pages in an EPUB book.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct EpubSink {
+    archive: PendingArchive,
+    metadata: Option<ComicInfo>,
+    pages: Vec<String>,
+}
+
+impl EpubSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            archive: PendingArchive::new(path.as_ref()),
+            metadata: None,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Use the title and the language of the chapter in the book metadata.
+    pub fn with_metadata(mut self, metadata: ComicInfo) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    fn zip(&mut self) -> Result<&mut ZipWriter<BufWriter<File>>> {
+        let is_new = self.archive.zip.is_none();
+        let zip = self.archive.zip()?;
+        if is_new {
+            // The `mimetype` file must be the first entry of the archive.
+            add_file(zip, "mimetype", b"application/epub+zip")?;
+            add_file(zip, "META-INF/container.xml", EPUB_CONTAINER.as_bytes())?;
+        }
+        Ok(zip)
+    }
+
+    fn title(&self) -> String {
+        let Some(metadata) = &self.metadata else {
+            return self
+                .archive
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+        };
+        let mut title = metadata.series.clone().unwrap_or_default();
+        if let Some(volume) = &metadata.volume {
+            title.push_str(&format!(" Vol. {volume}"));
+        }
+        if let Some(number) = &metadata.number {
+            title.push_str(&format!(" Ch. {number}"));
+        }
+        if let Some(chapter_title) = &metadata.title {
+            title.push_str(&format!(" - {chapter_title}"));
+        }
+        title.trim().trim_start_matches("- ").to_string()
+    }
+
+    fn package(&self) -> String {
+        let metadata = self.metadata.clone().unwrap_or_default();
+        let modified = OffsetDateTime::now_utc();
+        let identifier = metadata
+            .web
+            .clone()
+            .unwrap_or_else(|| format!("urn:mangadex-api:{}", self.title()));
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for (index, page) in self.pages.iter().enumerate() {
+            manifest.push_str(&format!(
+                "    <item id=\"page-{index}\" href=\"page-{index}.xhtml\" media-type=\"application/xhtml+xml\"/>\n    <item id=\"image-{index}\" href=\"images/{page}\" media-type=\"{}\"/>\n",
+                media_type(page)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"page-{index}\"/>\n"));
+        }
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{language}</dc:language>
+    <meta property="dcterms:modified">{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z</meta>
+    <meta property="rendition:layout">pre-paginated</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+            identifier = escape_xml(&identifier),
+            title = escape_xml(&self.title()),
+            language = metadata
+                .language
+                .map(|language| language.code2().to_string())
+                .unwrap_or_else(|| "en".to_string()),
+            year = modified.year(),
+            month = modified.month() as u8,
+            day = modified.day(),
+            hour = modified.hour(),
+            minute = modified.minute(),
+            second = modified.second(),
+        )
+    }
+
+    fn navigation(&self) -> String {
+        let pages: String = (0..self.pages.len())
+            .map(|index| {
+                format!(
+                    "      <li><a href=\"page-{index}.xhtml\">{}</a></li>\n",
+                    index + 1
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>{title}</title></head>
+<body>
+  <nav epub:type="toc">
+    <ol>
+{pages}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+            title = escape_xml(&self.title()),
+        )
+    }
+}
+
+const EPUB_CONTAINER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+impl ChapterSink for EpubSink {
+    fn write_page(&mut self, index: usize, len: usize, filename: &str, bytes: &[u8]) -> Result<()> {
+        let position = self.pages.len();
+        let page = page_file_name(index, len, filename);
+        let zip = self.zip()?;
+        add_file(zip, &format!("OEBPS/images/{page}"), bytes)?;
+        add_file(
+            zip,
+            &format!("OEBPS/page-{position}.xhtml"),
+            format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\">\n<head><title>{index}</title></head>\n<body><img src=\"images/{page}\" alt=\"{index}\"/></body>\n</html>\n"
+            )
+            .as_bytes(),
+        )?;
+        self.pages.push(page);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        let package = self.package();
+        let navigation = self.navigation();
+        let zip = self.zip()?;
+        add_file(zip, "OEBPS/content.opf", package.as_bytes())?;
+        add_file(zip, "OEBPS/nav.xhtml", navigation.as_bytes())?;
+        self.archive.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path::Path;
+
+    use uuid::Uuid;
+    use zip::ZipArchive;
+
+    use super::{CbzSink, ChapterSink, ComicInfo, EpubSink};
+
+    fn read_entry(path: &Path, name: &str) -> anyhow::Result<String> {
+        let mut archive = ZipArchive::new(std::fs::File::open(path)?)?;
+        let mut content = String::new();
+        archive.by_name(name)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn archives_contain_the_pages_and_the_metadata() -> anyhow::Result<()> {
+        let output_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let comic_info = ComicInfo {
+            series: Some("Tom & Jerry".to_string()),
+            number: Some("3".to_string()),
+            ..Default::default()
+        };
+
+        let cbz_path = output_dir.join("chapter.cbz");
+        let mut cbz = CbzSink::new(&cbz_path).with_comic_info(comic_info.clone());
+        cbz.write_page(1, 2, "a1-hash.jpg", b"first page")?;
+        cbz.write_page(2, 2, "a2-hash.png", b"second page")?;
+        cbz.finish()?;
+        let archive = ZipArchive::new(std::fs::File::open(&cbz_path)?)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["001.jpg", "002.png", "ComicInfo.xml"]
+        );
+        assert_eq!(read_entry(&cbz_path, "002.png")?, "second page");
+        let xml = read_entry(&cbz_path, "ComicInfo.xml")?;
+        assert!(xml.contains("<Series>Tom &amp; Jerry</Series>"));
+        assert!(xml.contains("<PageCount>2</PageCount>"));
+        assert!(!output_dir.join("chapter.cbz.tmp").exists());
+
+        let epub_path = output_dir.join("chapter.epub");
+        let mut epub = EpubSink::new(&epub_path).with_metadata(comic_info);
+        epub.write_page(1, 1, "a1-hash.jpg", b"first page")?;
+        epub.finish()?;
+        let mut archive = ZipArchive::new(std::fs::File::open(&epub_path)?)?;
+        let mimetype = archive.by_index(0)?;
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), zip::CompressionMethod::Stored);
+        drop(mimetype);
+        assert_eq!(
+            read_entry(&epub_path, "OEBPS/images/001.jpg")?,
+            "first page"
+        );
+        assert!(
+            read_entry(&epub_path, "OEBPS/content.opf")?
+                .contains("<dc:title>Tom &amp; Jerry Ch. 3</dc:title>")
+        );
+
+        std::fs::remove_dir_all(output_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unfinished_archives_are_removed() -> anyhow::Result<()> {
+        let output_dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let cbz_path = output_dir.join("chapter.cbz");
+        let mut cbz = CbzSink::new(&cbz_path);
+        cbz.write_page(1, 2, "a1-hash.jpg", b"first page")?;
+        assert!(output_dir.join("chapter.cbz.tmp").exists());
+        drop(cbz);
+        assert!(!output_dir.join("chapter.cbz.tmp").exists());
+        assert!(!cbz_path.exists());
+
+        std::fs::remove_dir_all(output_dir)?;
+        Ok(())
+    }
+}
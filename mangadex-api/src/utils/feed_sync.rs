@@ -0,0 +1,509 @@
+//! Incremental synchronization of the chapter feeds.
+//!
+//! A [`FeedSync`] pages a feed ordered by `updatedAt`, starting from the cursor saved by the
+//! previous synchronization, and turns the chapters into [`FeedEvent`]s.
+//! The cursor holds the last `updatedAt` seen and the version of the chapters seen,
+//! so a chapter received twice (at the window boundary or in the next synchronization)
+//! is only reported again when it has a new version.
+//! The chapters last updated more than the [retention](FeedSync::retention) before the
+//! cursor are forgotten when it is saved: a new version of one of them is reported as a
+//! [`FeedEvent::NewChapter`].
+//!
+//! The cursor is saved in a [`CursorStore`] when the stream ends, or fails.
+//! This crate provides:
+//!
+//! - [`FileCursorStore`]: a JSON file,
+//! - [`MemoryCursorStore`]: an in-memory store, useful for tests.
+//!
+//! Any endpoint implementing [`PaginateWindowed`] for chapters can be synchronized:
+//! the followed manga feed, a manga feed, a custom list feed or the chapter list.
+//! The unavailable chapters are only returned with `includeUnavailable`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api::utils::feed_sync::{FeedEvent, FeedSync, FileCursorStore};
+//! use mangadex_api_types::{IncludeUnvailable, Language};
+//! use tokio::pin;
+//! use tokio_stream::StreamExt;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let feed = client
+//!     .user()
+//!     .follows()
+//!     .manga()
+//!     .feed()
+//!     .get()
+//!     .add_translated_language(Language::English)
+//!     .include_unavailable(IncludeUnvailable::Include)
+//!     .build()?;
+//!
+//! let events = FeedSync::new(feed, FileCursorStore::new("feed-cursor.json")).sync();
+//! pin!(events);
+//! while let Some(event) = events.next().await {
+//!     match event? {
+//!         FeedEvent::NewChapter(chapter) => println!("new chapter {}", chapter.id),
+//!         FeedEvent::ChapterUpdated(chapter) => println!("updated chapter {}", chapter.id),
+//!         FeedEvent::ChapterUnavailable(chapter) => println!("removed chapter {}", chapter.id),
+//!         _ => (),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_stream::stream;
+use mangadex_api_schema::v5::ChapterObject;
+use mangadex_api_types::MangaDexDateTime;
+use serde::{Deserialize, Serialize};
+use tokio::pin;
+use tokio_stream::{Stream, StreamExt};
+use uuid::Uuid;
+
+use crate::Result;
+use crate::atomic_file;
+use crate::error::Error;
+use crate::utils::paginate::{PaginateWindowed, WindowField, WindowItem};
+
+/// A change of the feed.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum FeedEvent {
+    /// A chapter seen for the first time.
+    NewChapter(ChapterObject),
+    /// A new version of a chapter already seen.
+    ChapterUpdated(ChapterObject),
+    /// A chapter already seen was removed, and is now unavailable.
+    ChapterUnavailable(ChapterObject),
+}
+
+/// The state of a chapter when it was last seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ChapterVersion {
+    pub version: u32,
+    pub is_unavailable: bool,
+    /// The `updatedAt` of this version.
+    #[serde(default, with = "unix_timestamp")]
+    pub updated_at: Option<MangaDexDateTime>,
+}
+
+impl From<&ChapterObject> for ChapterVersion {
+    fn from(chapter: &ChapterObject) -> Self {
+        Self {
+            version: chapter.attributes.version,
+            is_unavailable: chapter.attributes.is_unavailable,
+            updated_at: Some(chapter.window_key(WindowField::UpdatedAt)),
+        }
+    }
+}
+
+/// The position of a feed synchronization.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct SyncCursor {
+    /// The last `updatedAt` seen.
+    #[serde(default, with = "unix_timestamp")]
+    pub updated_at: Option<MangaDexDateTime>,
+    /// The chapters seen, by id.
+    #[serde(default)]
+    pub chapters: HashMap<Uuid, ChapterVersion>,
+}
+
+impl SyncCursor {
+    /// Record `chapter` and get its event, if it changed since it was last seen.
+    pub fn apply(&mut self, chapter: ChapterObject) -> Option<FeedEvent> {
+        let key = chapter.window_key(WindowField::UpdatedAt);
+        if self.updated_at.is_none_or(|updated_at| updated_at < key) {
+            self.updated_at = Some(key);
+        }
+        let current = ChapterVersion::from(&chapter);
+        match self.chapters.insert(chapter.id, current) {
+            None if current.is_unavailable => None,
+            None => Some(FeedEvent::NewChapter(chapter)),
+            Some(previous) if current.is_unavailable && !previous.is_unavailable => {
+                Some(FeedEvent::ChapterUnavailable(chapter))
+            }
+            Some(previous) if current.version > previous.version => {
+                Some(FeedEvent::ChapterUpdated(chapter))
+            }
+            Some(previous) => {
+                // Keep the latest version if an older one was received.
+                self.chapters.insert(chapter.id, previous);
+                None
+            }
+        }
+    }
+
+    /// Forget the chapters last updated more than `retention` before the cursor.
+    pub fn prune(&mut self, retention: Duration) {
+        let Some(oldest) = self.updated_at.and_then(|updated_at| {
+            let retention = time::Duration::try_from(retention).ok()?;
+            updated_at.as_ref().checked_sub(retention)
+        }) else {
+            return;
+        };
+        self.chapters.retain(|_, chapter| {
+            chapter
+                .updated_at
+                .is_none_or(|updated_at| *updated_at.as_ref() >= oldest)
+        });
+    }
+}
+
+mod unix_timestamp {
+    use mangadex_api_types::MangaDexDateTime;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use time::OffsetDateTime;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<MangaDexDateTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(date) => serializer.serialize_some(&date.as_ref().unix_timestamp()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<MangaDexDateTime>, D::Error> {
+        Option::<i64>::deserialize(deserializer)?
+            .map(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).map(Into::into))
+            .transpose()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A storage for the [`SyncCursor`] of a feed.
+pub trait CursorStore: Send + Sync {
+    /// Load the saved cursor, if any.
+    fn load(&self) -> Result<Option<SyncCursor>>;
+
+    /// Save the cursor, replacing the previous one.
+    fn save(&self, cursor: &SyncCursor) -> Result<()>;
+}
+
+impl Debug for dyn CursorStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CursorStore")
+    }
+}
+
+impl<S: CursorStore + ?Sized> CursorStore for Arc<S> {
+    fn load(&self) -> Result<Option<SyncCursor>> {
+        (**self).load()
+    }
+
+    fn save(&self, cursor: &SyncCursor) -> Result<()> {
+        (**self).save(cursor)
+    }
+}
+
+/// Store the cursor in a JSON file.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FileCursorStore {
+    pub path: PathBuf,
+}
+
+impl FileCursorStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl CursorStore for FileCursorStore {
+    fn load(&self) -> Result<Option<SyncCursor>> {
+        match std::fs::read(&self.path) {
+            Ok(content) => Ok(Some(
+                serde_json::from_slice(&content).map_err(|e| Error::ParseError(e.to_string()))?,
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, cursor: &SyncCursor) -> Result<()> {
+        let content = serde_json::to_vec(cursor).map_err(|e| Error::ParseError(e.to_string()))?;
+        atomic_file::write(&self.path, &content)?;
+        Ok(())
+    }
+}
+
+/// Store the cursor in memory.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct MemoryCursorStore {
+    cursor: Mutex<Option<SyncCursor>>,
+}
+
+impl MemoryCursorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CursorStore for MemoryCursorStore {
+    fn load(&self) -> Result<Option<SyncCursor>> {
+        Ok(self
+            .cursor
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn save(&self, cursor: &SyncCursor) -> Result<()> {
+        *self.cursor.lock().unwrap_or_else(|e| e.into_inner()) = Some(cursor.clone());
+        Ok(())
+    }
+}
+
+/// Synchronizes a chapter feed with the cursor of a [`CursorStore`].
+#[derive(Debug, Clone)]
+pub struct FeedSync<P> {
+    endpoint: P,
+    store: Arc<dyn CursorStore>,
+    retention: Duration,
+}
+
+impl<P> FeedSync<P>
+where
+    P: PaginateWindowed<Item = ChapterObject> + 'static,
+{
+    /// Synchronize `endpoint` with the cursor of `store`.
+    ///
+    /// Without a saved cursor, the synchronization starts from the `updatedAtSince`
+    /// filter of the endpoint, or from the beginning of the feed.
+    pub fn new<S: CursorStore + 'static>(endpoint: P, store: S) -> Self {
+        Self {
+            endpoint,
+            store: Arc::new(store),
+            retention: Self::DEFAULT_RETENTION,
+        }
+    }
+
+    /// How long the chapters are remembered after their last update, by default.
+    pub const DEFAULT_RETENTION: Duration = Duration::from_secs(60 * 60 * 24 * 90);
+
+    /// Remember the chapters for `retention` after their last update, instead of
+    /// [`DEFAULT_RETENTION`](Self::DEFAULT_RETENTION).
+    pub fn retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Yield the changes of the feed since the last synchronization.
+    ///
+    /// The cursor is saved when the stream ends, or after the first error.
+    /// Dropping the stream before its end discards the progress.
+    pub fn sync(&self) -> impl Stream<Item = Result<FeedEvent>> + Send + use<P> {
+        let mut endpoint = self.endpoint.clone();
+        let store = self.store.clone();
+        let retention = self.retention;
+        stream! {
+            let mut cursor = match store.load() {
+                Ok(cursor) => cursor.unwrap_or_default(),
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let since = cursor
+                .updated_at
+                .or(endpoint.window_since(WindowField::UpdatedAt));
+            endpoint.set_window(WindowField::UpdatedAt, since);
+            let chapters = endpoint.into_windowed_stream(WindowField::UpdatedAt);
+            pin!(chapters);
+            while let Some(chapter) = chapters.next().await {
+                match chapter {
+                    Ok(chapter) => {
+                        if let Some(event) = cursor.apply(chapter) {
+                            yield Ok(event);
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        break;
+                    }
+                }
+            }
+            cursor.prune(retention);
+            if let Err(e) = store.save(&cursor) {
+                yield Err(e);
+            }
+        }
+    }
+
+    /// Load the saved cursor.
+    pub fn cursor(&self) -> Result<Option<SyncCursor>> {
+        self.store.load()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use serde_json::json;
+    use time::OffsetDateTime;
+    use tokio_stream::StreamExt;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{
+        ChapterVersion, CursorStore, FeedEvent, FeedSync, FileCursorStore, MemoryCursorStore,
+        SyncCursor,
+    };
+    use crate::{HttpClient, MangaDexClient};
+
+    fn chapter_json(
+        id: Uuid,
+        version: u32,
+        updated_at: &str,
+        unavailable: bool,
+    ) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "chapter",
+            "attributes": {
+                "title": "Chapter",
+                "volume": null,
+                "chapter": "1",
+                "pages": 4,
+                "translatedLanguage": "en",
+                "uploader": null,
+                "externalUrl": null,
+                "version": version,
+                "createdAt": "2021-06-01T00:00:00+00:00",
+                "updatedAt": updated_at,
+                "publishAt": "2021-06-01T00:00:00+00:00",
+                "readableAt": "2021-06-01T00:00:00+00:00",
+                "isUnavailable": unavailable,
+            },
+            "relationships": []
+        })
+    }
+
+    fn feed_json(data: Vec<serde_json::Value>) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "result": "ok",
+            "response": "collection",
+            "data": data,
+            "limit": 100,
+            "offset": 0,
+            "total": data.len()
+        }))
+    }
+
+    #[tokio::test]
+    async fn events_are_emitted_from_the_saved_cursor() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        Mock::given(method("GET"))
+            .and(path("/manga/f9c33607-9180-4ba6-b85c-e4b5faee7192/feed"))
+            .and(query_param_is_missing("updatedAtSince"))
+            .respond_with(feed_json(vec![
+                chapter_json(first, 1, "2021-06-02T00:00:00+00:00", false),
+                chapter_json(second, 1, "2021-06-03T00:00:00+00:00", false),
+            ]))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/manga/f9c33607-9180-4ba6-b85c-e4b5faee7192/feed"))
+            .and(query_param("updatedAtSince", "2021-06-03T00:00:00"))
+            .and(query_param("order[updatedAt]", "asc"))
+            .respond_with(feed_json(vec![
+                chapter_json(second, 1, "2021-06-03T00:00:00+00:00", false),
+                chapter_json(first, 2, "2021-06-04T00:00:00+00:00", true),
+                chapter_json(third, 1, "2021-06-05T00:00:00+00:00", false),
+                chapter_json(second, 2, "2021-06-06T00:00:00+00:00", false),
+            ]))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let store = Arc::new(MemoryCursorStore::new());
+        let sync = FeedSync::new(
+            mangadex_client
+                .manga()
+                .id(Uuid::parse_str("f9c33607-9180-4ba6-b85c-e4b5faee7192")?)
+                .feed()
+                .get()
+                .build()?,
+            store.clone(),
+        );
+
+        let events = sync.sync().collect::<Result<Vec<_>, _>>().await?;
+        assert!(
+            matches!(&events[..], [FeedEvent::NewChapter(a), FeedEvent::NewChapter(b)] if a.id == first && b.id == second)
+        );
+
+        let events = sync.sync().collect::<Result<Vec<_>, _>>().await?;
+        assert!(matches!(
+            &events[..],
+            [
+                FeedEvent::ChapterUnavailable(a),
+                FeedEvent::NewChapter(b),
+                FeedEvent::ChapterUpdated(c),
+            ] if a.id == first && b.id == third && c.id == second
+        ));
+
+        let cursor = store.load()?.unwrap();
+        assert_eq!(
+            cursor.updated_at.map(|date| date.to_string()),
+            Some("2021-06-06T00:00:00+00:00".to_string())
+        );
+        assert_eq!(cursor.chapters[&second].version, 2);
+
+        let file_store =
+            FileCursorStore::new(std::env::temp_dir().join(format!("{}.json", Uuid::new_v4())));
+        file_store.save(&cursor)?;
+        let loaded = file_store.load()?.unwrap();
+        std::fs::remove_file(&file_store.path)?;
+        assert_eq!(loaded.updated_at, cursor.updated_at);
+        assert_eq!(loaded.chapters, cursor.chapters);
+        Ok(())
+    }
+
+    #[test]
+    fn old_chapters_are_pruned() {
+        let chapter = |days: i64| ChapterVersion {
+            version: 1,
+            is_unavailable: false,
+            updated_at: Some((OffsetDateTime::UNIX_EPOCH + time::Duration::days(days)).into()),
+        };
+        let (old, recent) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut cursor = SyncCursor {
+            updated_at: chapter(30).updated_at,
+            chapters: HashMap::from([(old, chapter(0)), (recent, chapter(25))]),
+        };
+
+        cursor.prune(Duration::from_secs(60 * 60 * 24 * 10));
+        assert_eq!(cursor.chapters.keys().collect::<Vec<_>>(), [&recent]);
+    }
+}
@@ -0,0 +1,637 @@
+//! Automatic pagination for the `limit`/`offset` based collection endpoints.
+//!
+//! Every endpoint returning a [`Results`] collection can be turned into a
+//! [`Stream`] that lazily fetches the next page when the current one is exhausted.
+//!
+//! MangaDex refuses any request where `offset + limit` is greater than [`MAX_OFFSET_WINDOW`].
+//! The plain streams stop at that ceiling.
+//! Endpoints supporting `createdAtSince`/`updatedAtSince` also implement [`PaginateWindowed`],
+//! which moves the window forward when the ceiling is reached so a full crawl is possible.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api::utils::paginate::Paginate;
+//! use tokio::pin;
+//! use tokio_stream::StreamExt;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let manga_stream = client
+//!     .manga()
+//!     .get()
+//!     .title("full metal")
+//!     .limit(100u32)
+//!     .build()?
+//!     .into_stream();
+//! pin!(manga_stream);
+//!
+//! while let Some(manga) = manga_stream.next().await {
+//!     println!("{:?}", manga?.id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::future::Future;
+
+use async_stream::stream;
+use mangadex_api_schema::ApiObject;
+use mangadex_api_schema::v5::{
+    ApiClientObject, AuthorObject, ChapterAttributes, ChapterObject, CoverObject, CustomListObject,
+    GroupObject, MangaAttributes, MangaObject, Results, UserObject, UserReportsObject,
+};
+use mangadex_api_types::{
+    ChapterSortOrder, MangaDexDateTime, MangaFeedSortOrder, MangaSortOrder, OrderDirection,
+};
+use tokio_stream::Stream;
+use uuid::Uuid;
+
+use crate::Result;
+use crate::v5::api_client::get::{ListClients, ListClientsBuilder};
+use crate::v5::author::get::{ListAuthor, ListAuthorBuilder};
+use crate::v5::chapter::get::{ListChapter, ListChapterBuilder};
+use crate::v5::cover::get::{ListCover, ListCoverBuilder};
+use crate::v5::custom_list::id::feed::get::{CustomListMangaFeed, CustomListMangaFeedBuilder};
+use crate::v5::manga::draft::get::{ListMangaDrafts, ListMangaDraftsBuilder};
+use crate::v5::manga::get::{ListManga, ListMangaBuilder};
+use crate::v5::manga::id::feed::get::{GetMangaFeed, GetMangaFeedBuilder};
+use crate::v5::report::get::{ListReportsByUser, ListReportsByUserBuilder};
+use crate::v5::scanlation_group::get::{ListGroup, ListGroupBuilder};
+#[allow(deprecated)]
+use crate::v5::user::follows::group::get::{FollowedGroups, FollowedGroupsBuilder};
+#[allow(deprecated)]
+use crate::v5::user::follows::list::get::{GetFollowedCustomLists, GetFollowedCustomListsBuilder};
+#[allow(deprecated)]
+use crate::v5::user::follows::manga::feed::get::{
+    GetFollowedMangaFeed, GetFollowedMangaFeedBuilder,
+};
+#[allow(deprecated)]
+use crate::v5::user::follows::manga::get::{FollowedManga, FollowedMangaBuilder};
+use crate::v5::user::get::{ListUser, ListUserBuilder};
+use crate::v5::user::list::get::{MyCustomLists, MyCustomListsBuilder};
+
+/// The maximum value of `offset + limit` accepted by MangaDex.
+pub const MAX_OFFSET_WINDOW: u32 = 10_000;
+
+/// The maximum `limit` accepted by the collection endpoints.
+pub const MAX_PAGE_LIMIT: u32 = 100;
+
+/// A collection endpoint that can be paginated with `limit` and `offset`.
+pub trait Paginate: Clone + Send + Sync + Sized {
+    type Item: Send;
+
+    fn page_limit(&self) -> Option<u32>;
+
+    fn set_page_limit(&mut self, limit: u32);
+
+    fn page_offset(&self) -> Option<u32>;
+
+    fn set_page_offset(&mut self, offset: u32);
+
+    /// Send the request for the current `limit` and `offset`.
+    fn send_page(&self) -> impl Future<Output = Result<Results<Self::Item>>> + Send;
+
+    /// Yield every item of the collection, starting from the current `offset`.
+    ///
+    /// The page size is the endpoint `limit` (or [`MAX_PAGE_LIMIT`] if not set).
+    /// The stream ends when the collection is exhausted, when an error occured,
+    /// or when the [`MAX_OFFSET_WINDOW`] ceiling is reached.
+    fn into_stream(self) -> impl Stream<Item = Result<Self::Item>> + Send {
+        offset_stream(self, MAX_OFFSET_WINDOW)
+    }
+}
+
+/// The timestamp used to move the pagination window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum WindowField {
+    /// Order by `createdAt` and move the window with `createdAtSince`.
+    #[default]
+    CreatedAt,
+    /// Order by `updatedAt` and move the window with `updatedAtSince`.
+    UpdatedAt,
+}
+
+/// An item that can be located in a [`WindowField`] based window.
+pub trait WindowItem {
+    fn item_id(&self) -> Uuid;
+
+    fn window_key(&self, field: WindowField) -> MangaDexDateTime;
+}
+
+impl WindowItem for ApiObject<MangaAttributes> {
+    fn item_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn window_key(&self, field: WindowField) -> MangaDexDateTime {
+        match field {
+            WindowField::CreatedAt => self.attributes.created_at,
+            WindowField::UpdatedAt => self
+                .attributes
+                .updated_at
+                .unwrap_or(self.attributes.created_at),
+        }
+    }
+}
+
+impl WindowItem for ApiObject<ChapterAttributes> {
+    fn item_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn window_key(&self, field: WindowField) -> MangaDexDateTime {
+        match field {
+            WindowField::CreatedAt => self.attributes.created_at,
+            WindowField::UpdatedAt => self
+                .attributes
+                .updated_at
+                .unwrap_or(self.attributes.created_at),
+        }
+    }
+}
+
+/// A collection endpoint that can be crawled beyond the [`MAX_OFFSET_WINDOW`] ceiling
+/// with the `createdAtSince`/`updatedAtSince` filters.
+pub trait PaginateWindowed: Paginate
+where
+    Self::Item: WindowItem,
+{
+    /// Get the current `createdAtSince` or `updatedAtSince` value.
+    fn window_since(&self, field: WindowField) -> Option<MangaDexDateTime>;
+
+    /// Order the results ascending by `field` and set its `*Since` filter.
+    fn set_window(&mut self, field: WindowField, since: Option<MangaDexDateTime>);
+
+    /// Yield every item of the collection ordered ascending by `field`.
+    ///
+    /// When the [`MAX_OFFSET_WINDOW`] ceiling is hit, the `*Since` filter is set to the last
+    /// received timestamp and the pagination restarts from offset `0`.
+    /// Items already yielded at that boundary timestamp are skipped.
+    ///
+    /// Please note that this overrides the endpoint `order` and `offset`.
+    fn into_windowed_stream(
+        self,
+        field: WindowField,
+    ) -> impl Stream<Item = Result<Self::Item>> + Send {
+        windowed_stream(self, field, MAX_OFFSET_WINDOW)
+    }
+}
+
+fn page_limit_of<P: Paginate>(endpoint: &P) -> u32 {
+    endpoint
+        .page_limit()
+        .unwrap_or(MAX_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT)
+}
+
+fn offset_stream<P: Paginate>(
+    mut endpoint: P,
+    ceiling: u32,
+) -> impl Stream<Item = Result<P::Item>> + Send {
+    let limit = page_limit_of(&endpoint);
+    let mut offset = endpoint.page_offset().unwrap_or(0);
+    stream! {
+        while offset < ceiling {
+            endpoint.set_page_limit(limit.min(ceiling - offset));
+            endpoint.set_page_offset(offset);
+            let page = match endpoint.send_page().await {
+                Ok(page) => page,
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            };
+            let received = page.data.len() as u32;
+            let total = page.total;
+            for item in page.data {
+                yield Ok(item);
+            }
+            offset += received;
+            if received == 0 || offset >= total {
+                break;
+            }
+        }
+    }
+}
+
+fn windowed_stream<P>(
+    mut endpoint: P,
+    field: WindowField,
+    ceiling: u32,
+) -> impl Stream<Item = Result<P::Item>> + Send
+where
+    P: PaginateWindowed,
+    P::Item: WindowItem,
+{
+    let limit = page_limit_of(&endpoint);
+    let mut since = endpoint.window_since(field);
+    // Ids of the items already yielded at the `since` timestamp.
+    let mut boundary_ids: HashSet<Uuid> = HashSet::new();
+    stream! {
+        'windows: loop {
+            endpoint.set_window(field, since);
+            let mut offset: u32 = 0;
+            let mut yielded: usize = 0;
+            let mut last_key: Option<MangaDexDateTime> = None;
+            let mut last_key_ids: HashSet<Uuid> = HashSet::new();
+            while offset < ceiling {
+                endpoint.set_page_limit(limit.min(ceiling - offset));
+                endpoint.set_page_offset(offset);
+                let page = match endpoint.send_page().await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        yield Err(e);
+                        break 'windows;
+                    }
+                };
+                let received = page.data.len() as u32;
+                let total = page.total;
+                for item in page.data {
+                    let id = item.item_id();
+                    let key = item.window_key(field);
+                    if since == Some(key) && boundary_ids.contains(&id) {
+                        continue;
+                    }
+                    if last_key != Some(key) {
+                        last_key = Some(key);
+                        last_key_ids.clear();
+                    }
+                    last_key_ids.insert(id);
+                    yielded += 1;
+                    yield Ok(item);
+                }
+                offset += received;
+                if received == 0 || offset >= total {
+                    break 'windows;
+                }
+            }
+            // The ceiling is reached: move the window to the last seen timestamp
+            if yielded == 0 {
+                break;
+            }
+            if last_key == since {
+                boundary_ids.extend(last_key_ids);
+            } else {
+                boundary_ids = last_key_ids;
+                since = last_key;
+            }
+        }
+    }
+}
+
+macro_rules! paginate {
+    {
+        $(
+            $(#[$send:ident])? $endpoint:ty, $builder:ty => $item:ty;
+        )*
+    } => {
+        $(
+            impl Paginate for $endpoint {
+                type Item = $item;
+
+                fn page_limit(&self) -> Option<u32> {
+                    self.limit
+                }
+
+                fn set_page_limit(&mut self, limit: u32) {
+                    self.limit = Some(limit);
+                }
+
+                fn page_offset(&self) -> Option<u32> {
+                    self.offset
+                }
+
+                fn set_page_offset(&mut self, offset: u32) {
+                    self.offset = Some(offset);
+                }
+
+                paginate! { @send $($send)? }
+            }
+
+            impl $builder {
+                /// Build the request and yield every item of the collection.
+                ///
+                /// See [`Paginate::into_stream`].
+                pub fn paginate(&self) -> Result<impl Stream<Item = Result<$item>> + Send> {
+                    Ok(self.build()?.into_stream())
+                }
+            }
+        )*
+    };
+    { @send } => {
+        async fn send_page(&self) -> Result<Results<Self::Item>> {
+            self.send().await
+        }
+    };
+    { @send rate_limited } => {
+        async fn send_page(&self) -> Result<Results<Self::Item>> {
+            Ok(self.send().await?.body)
+        }
+    };
+}
+
+macro_rules! paginate_windowed {
+    {
+        $(
+            $endpoint:ty, $builder:ty => $item:ty, $order:ident;
+        )*
+    } => {
+        $(
+            impl PaginateWindowed for $endpoint {
+                fn window_since(&self, field: WindowField) -> Option<MangaDexDateTime> {
+                    match field {
+                        WindowField::CreatedAt => self.created_at_since,
+                        WindowField::UpdatedAt => self.updated_at_since,
+                    }
+                }
+
+                fn set_window(&mut self, field: WindowField, since: Option<MangaDexDateTime>) {
+                    match field {
+                        WindowField::CreatedAt => {
+                            self.order = Some($order::CreatedAt(OrderDirection::Ascending));
+                            self.created_at_since = since;
+                        }
+                        WindowField::UpdatedAt => {
+                            self.order = Some($order::UpdatedAt(OrderDirection::Ascending));
+                            self.updated_at_since = since;
+                        }
+                    }
+                }
+            }
+
+            impl $builder {
+                /// Build the request and yield every item of the collection,
+                /// moving the `field` window when the offset ceiling is reached.
+                ///
+                /// See [`PaginateWindowed::into_windowed_stream`].
+                pub fn paginate_windowed(
+                    &self,
+                    field: WindowField,
+                ) -> Result<impl Stream<Item = Result<$item>> + Send> {
+                    Ok(self.build()?.into_windowed_stream(field))
+                }
+            }
+        )*
+    };
+}
+
+paginate! {
+    ListClients, ListClientsBuilder => ApiClientObject;
+    ListAuthor, ListAuthorBuilder => AuthorObject;
+    ListChapter, ListChapterBuilder => ChapterObject;
+    ListCover, ListCoverBuilder => CoverObject;
+    CustomListMangaFeed, CustomListMangaFeedBuilder => ChapterObject;
+    ListMangaDrafts, ListMangaDraftsBuilder => MangaObject;
+    ListManga, ListMangaBuilder => MangaObject;
+    GetMangaFeed, GetMangaFeedBuilder => ChapterObject;
+    #[rate_limited] ListReportsByUser, ListReportsByUserBuilder => UserReportsObject;
+    ListGroup, ListGroupBuilder => GroupObject;
+    ListUser, ListUserBuilder => UserObject;
+    MyCustomLists, MyCustomListsBuilder => CustomListObject;
+}
+
+cfg_custom_list_v2! {
+    use crate::v5::custom_list::id::manga::get::{GetCustomListManga, GetCustomListMangaBuilder};
+    use crate::v5::user::bookmarks::group::get::{BookmarkedGroups, BookmarkedGroupsBuilder};
+    use crate::v5::user::bookmarks::list::get::{BookmarkedCustomLists, BookmarkedCustomListsBuilder};
+
+    paginate! {
+        GetCustomListManga, GetCustomListMangaBuilder => MangaObject;
+        BookmarkedGroups, BookmarkedGroupsBuilder => GroupObject;
+        BookmarkedCustomLists, BookmarkedCustomListsBuilder => CustomListObject;
+    }
+}
+
+paginate_windowed! {
+    ListManga, ListMangaBuilder => MangaObject, MangaSortOrder;
+    ListChapter, ListChapterBuilder => ChapterObject, ChapterSortOrder;
+    GetMangaFeed, GetMangaFeedBuilder => ChapterObject, MangaFeedSortOrder;
+    CustomListMangaFeed, CustomListMangaFeedBuilder => ChapterObject, MangaFeedSortOrder;
+}
+
+/// The follow endpoints, deprecated with the `custom_list_v2` feature.
+#[allow(deprecated)]
+mod follows {
+    use super::*;
+
+    paginate! {
+        FollowedGroups, FollowedGroupsBuilder => GroupObject;
+        GetFollowedCustomLists, GetFollowedCustomListsBuilder => CustomListObject;
+        GetFollowedMangaFeed, GetFollowedMangaFeedBuilder => ChapterObject;
+        FollowedManga, FollowedMangaBuilder => MangaObject;
+    }
+
+    paginate_windowed! {
+        GetFollowedMangaFeed, GetFollowedMangaFeedBuilder => ChapterObject, MangaFeedSortOrder;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::OffsetDateTime;
+    use tokio_stream::StreamExt;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{WindowField, offset_stream, windowed_stream};
+    use crate::{HttpClient, MangaDexClient};
+    use mangadex_api_types::MangaDexDateTime;
+
+    fn chapter_json(id: Uuid, created_at: &MangaDexDateTime) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "chapter",
+            "attributes": {
+                "title": "",
+                "volume": null,
+                "chapter": "1",
+                "pages": 4,
+                "translatedLanguage": "en",
+                "version": 1,
+                "createdAt": created_at.to_string(),
+                "updatedAt": created_at.to_string(),
+                "publishAt": created_at.to_string(),
+                "readableAt": created_at.to_string(),
+            },
+            "relationships": []
+        })
+    }
+
+    fn collection_json(
+        data: Vec<serde_json::Value>,
+        limit: u32,
+        offset: u32,
+        total: u32,
+    ) -> serde_json::Value {
+        json!({
+            "result": "ok",
+            "response": "collection",
+            "data": data,
+            "limit": limit,
+            "offset": offset,
+            "total": total
+        })
+    }
+
+    #[tokio::test]
+    async fn offset_stream_fetches_every_page() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let datetime = MangaDexDateTime::new(&OffsetDateTime::now_utc());
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+
+        for (offset, chunk) in ids.chunks(2).enumerate() {
+            let offset = (offset * 2) as u32;
+            Mock::given(method("GET"))
+                .and(path("/chapter"))
+                .and(query_param("limit", "2"))
+                .and(query_param("offset", offset.to_string()))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(collection_json(
+                        chunk
+                            .iter()
+                            .map(|id| chapter_json(*id, &datetime))
+                            .collect(),
+                        2,
+                        offset,
+                        5,
+                    )),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let chapters = offset_stream(
+            mangadex_client.chapter().get().limit(2u32).build()?,
+            super::MAX_OFFSET_WINDOW,
+        )
+        .collect::<crate::Result<Vec<_>>>()
+        .await?;
+
+        assert_eq!(
+            chapters
+                .iter()
+                .map(|chapter| chapter.id)
+                .collect::<Vec<_>>(),
+            ids
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offset_stream_stops_at_the_ceiling() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let datetime = MangaDexDateTime::new(&OffsetDateTime::now_utc());
+
+        Mock::given(method("GET"))
+            .and(path("/chapter"))
+            .and(query_param("limit", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(collection_json(
+                vec![
+                    chapter_json(Uuid::new_v4(), &datetime),
+                    chapter_json(Uuid::new_v4(), &datetime),
+                ],
+                2,
+                0,
+                50,
+            )))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let chapters = offset_stream(mangadex_client.chapter().get().limit(2u32).build()?, 4)
+            .collect::<crate::Result<Vec<_>>>()
+            .await?;
+
+        assert_eq!(chapters.len(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn windowed_stream_moves_the_window_past_the_ceiling() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let dates: Vec<MangaDexDateTime> = (0..5)
+            .map(|i| {
+                OffsetDateTime::from_unix_timestamp(1_700_000_000 + i).map(MangaDexDateTime::from)
+            })
+            .collect::<Result<_, _>>()?;
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let boundary = serde_json::to_value(dates[3])?;
+        let boundary = boundary.as_str().unwrap_or_default();
+
+        for offset in [0u32, 2] {
+            let range = (offset as usize)..(offset as usize + 2);
+            Mock::given(method("GET"))
+                .and(path("/chapter"))
+                .and(query_param("order[createdAt]", "asc"))
+                .and(query_param("offset", offset.to_string()))
+                .and(query_param_is_missing("createdAtSince"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(collection_json(
+                    range.map(|i| chapter_json(ids[i], &dates[i])).collect(),
+                    2,
+                    offset,
+                    20,
+                )))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/chapter"))
+            .and(query_param("order[createdAt]", "asc"))
+            .and(query_param("offset", "0"))
+            .and(query_param("createdAtSince", boundary))
+            .respond_with(ResponseTemplate::new(200).set_body_json(collection_json(
+                (3..5).map(|i| chapter_json(ids[i], &dates[i])).collect(),
+                2,
+                0,
+                2,
+            )))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let chapters = windowed_stream(
+            mangadex_client.chapter().get().limit(2u32).build()?,
+            WindowField::CreatedAt,
+            4,
+        )
+        .collect::<crate::Result<Vec<_>>>()
+        .await?;
+
+        assert_eq!(
+            chapters
+                .iter()
+                .map(|chapter| chapter.id)
+                .collect::<Vec<_>>(),
+            ids
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,537 @@
+//! Reading progress of the logged-in user.
+//!
+//! [`ReadingProgress`] combines the read markers, the reading status and the reading history
+//! with the aggregate of the manga (its chapters in reading order):
+//!
+//! - [`ReadingProgress::progress`] computes the next unread chapter of a manga,
+//! - [`ReadingProgress::mark_range`] marks a range of chapters read or unread in one request,
+//! - [`ReadingProgress::queue_read`] and [`ReadingProgress::queue_unread`] record the changes
+//!   made offline, sent later by [`ReadingProgress::flush`].
+//!
+//! A chapter number is read when any of its versions (one per scanlation group) is read.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! let reading_progress = client
+//!     .reading_progress()
+//!     .translated_language(Language::English);
+//!
+//! let manga_id = Uuid::new_v4();
+//! reading_progress.mark_range(manga_id, "1", "12", true).await?;
+//!
+//! if let Some(chapter) = reading_progress.progress(manga_id).await?.next_unread {
+//!     println!("next chapter: {}", chapter.chapter);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use mangadex_api_schema::v5::manga_aggregate::ChapterAggregate;
+use mangadex_api_schema::v5::manga_read_markers::MangaReadMarkers;
+use mangadex_api_types::{Language, ReadingStatus};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::{HttpClientRef, MangaDexClient, Result};
+
+/// The reading progress of a manga.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MangaProgress {
+    pub manga_id: Uuid,
+    /// The reading status of the manga, if set.
+    pub status: Option<ReadingStatus>,
+    /// Number of chapter numbers read.
+    pub read: usize,
+    /// Number of chapter numbers of the manga.
+    pub total: usize,
+    /// The last chapter read, in reading order.
+    pub last_read: Option<ChapterAggregate>,
+    /// The chapter after the last one read, or the first chapter if none was read.
+    pub next_unread: Option<ChapterAggregate>,
+}
+
+/// A chapter marked read or unread offline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct QueuedMark {
+    pub manga_id: Uuid,
+    pub chapter_id: Uuid,
+    pub read: bool,
+    #[serde(with = "time::serde::timestamp")]
+    pub queued_at: OffsetDateTime,
+}
+
+/// The outcome of [`ReadingProgress::flush`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct FlushReport {
+    /// The marks sent to MangaDex.
+    pub applied: Vec<QueuedMark>,
+    /// The marks already matching the read markers.
+    pub skipped: Vec<QueuedMark>,
+    /// The chapters marked unread offline but read again since, according to the history.
+    /// The read marker is kept.
+    pub conflicts: Vec<QueuedMark>,
+}
+
+/// Reading progress of the logged-in user.
+///
+/// Get one with [`MangaDexClient::reading_progress`].
+/// Cloning it is cheap, the clones share the offline queue.
+#[derive(Debug, Clone)]
+pub struct ReadingProgress {
+    http_client: HttpClientRef,
+    translated_language: Vec<Language>,
+    update_history: bool,
+    queue: Arc<Mutex<Vec<QueuedMark>>>,
+}
+
+impl ReadingProgress {
+    pub fn new(http_client: HttpClientRef) -> Self {
+        Self {
+            http_client,
+            translated_language: Vec::new(),
+            update_history: true,
+            queue: Default::default(),
+        }
+    }
+
+    /// Only count the chapters translated in `language`.
+    ///
+    /// Every language is counted by default.
+    pub fn translated_language(mut self, language: Language) -> Self {
+        self.translated_language.push(language);
+        self
+    }
+
+    /// Add the chapters marked read to the reading history.
+    ///
+    /// Defaults to `true`.
+    pub fn update_history(mut self, update_history: bool) -> Self {
+        self.update_history = update_history;
+        self
+    }
+
+    fn client(&self) -> MangaDexClient {
+        MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+    }
+
+    /// The chapters of the manga in reading order, from `GET /manga/{id}/aggregate`.
+    pub async fn chapters(&self, manga_id: Uuid) -> Result<Vec<ChapterAggregate>> {
+        Ok(self
+            .client()
+            .manga()
+            .id(manga_id)
+            .aggregate()
+            .get()
+            .translated_language(self.translated_language.clone())
+            .send()
+            .await?
+            .chapters()
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    /// Compute the reading progress of the manga.
+    pub async fn progress(&self, manga_id: Uuid) -> Result<MangaProgress> {
+        let client = self.client();
+        let chapters = self.chapters(manga_id).await?;
+        let read: HashSet<Uuid> = client
+            .manga()
+            .id(manga_id)
+            .read()
+            .get()
+            .send()
+            .await?
+            .data
+            .into_iter()
+            .collect();
+        let status = client
+            .manga()
+            .id(manga_id)
+            .status()
+            .get()
+            .send()
+            .await?
+            .status;
+
+        let is_read = |chapter: &ChapterAggregate| {
+            read.contains(&chapter.id) || chapter.others.iter().any(|id| read.contains(id))
+        };
+        let last_read = chapters.iter().rposition(is_read);
+        Ok(MangaProgress {
+            manga_id,
+            status,
+            read: chapters.iter().filter(|chapter| is_read(chapter)).count(),
+            total: chapters.len(),
+            last_read: last_read.map(|index| chapters[index].clone()),
+            next_unread: chapters
+                .get(last_read.map_or(0, |index| index + 1))
+                .cloned(),
+        })
+    }
+
+    /// Mark the chapters from `from` to `to` (both included) read or unread,
+    /// with every version of them.
+    ///
+    /// The chapter numbers are located in the aggregate of the manga,
+    /// the range follows its reading order.
+    pub async fn mark_range(&self, manga_id: Uuid, from: &str, to: &str, read: bool) -> Result<()> {
+        let chapters = self.chapters(manga_id).await?;
+        let position = |number: &str| {
+            chapters
+                .iter()
+                .position(|chapter| chapter.chapter == number)
+                .ok_or_else(|| Error::UnknownChapter(number.to_string()))
+        };
+        let (from, to) = (position(from)?, position(to)?);
+        let ids: Vec<Uuid> = chapters[from.min(to)..=from.max(to)]
+            .iter()
+            .flat_map(|chapter| std::iter::once(chapter.id).chain(chapter.others.iter().copied()))
+            .collect();
+        if read {
+            self.send_marks(manga_id, ids, Vec::new()).await
+        } else {
+            self.send_marks(manga_id, Vec::new(), ids).await
+        }
+    }
+
+    async fn send_marks(&self, manga_id: Uuid, read: Vec<Uuid>, unread: Vec<Uuid>) -> Result<()> {
+        self.client()
+            .manga()
+            .id(manga_id)
+            .read()
+            .post()
+            .chapter_ids_read(read)
+            .chapter_ids_unread(unread)
+            .update_history(self.update_history)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Queue the chapter to be marked read by [`ReadingProgress::flush`].
+    ///
+    /// This replaces a previous mark of the chapter.
+    pub fn queue_read(&self, manga_id: Uuid, chapter_id: Uuid) {
+        self.queue(manga_id, chapter_id, true);
+    }
+
+    /// Queue the chapter to be marked unread by [`ReadingProgress::flush`].
+    ///
+    /// This replaces a previous mark of the chapter.
+    pub fn queue_unread(&self, manga_id: Uuid, chapter_id: Uuid) {
+        self.queue(manga_id, chapter_id, false);
+    }
+
+    fn queue(&self, manga_id: Uuid, chapter_id: Uuid, read: bool) {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.retain(|mark| mark.chapter_id != chapter_id);
+        queue.push(QueuedMark {
+            manga_id,
+            chapter_id,
+            read,
+            queued_at: OffsetDateTime::now_utc(),
+        });
+    }
+
+    /// The marks waiting to be flushed, in queue order.
+    ///
+    /// Save them to restore them with [`ReadingProgress::restore`] after a restart.
+    pub fn pending(&self) -> Vec<QueuedMark> {
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Queue marks saved from [`ReadingProgress::pending`].
+    ///
+    /// The marks of the chapters already queued are ignored.
+    pub fn restore<I: IntoIterator<Item = QueuedMark>>(&self, marks: I) {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut restored: Vec<QueuedMark> = marks
+            .into_iter()
+            .filter(|mark| {
+                !queue
+                    .iter()
+                    .any(|queued| queued.chapter_id == mark.chapter_id)
+            })
+            .collect();
+        restored.append(&mut queue);
+        *queue = restored;
+    }
+
+    /// Send the queued marks, with one request per manga.
+    ///
+    /// The marks already matching the read markers are skipped.
+    /// An unread mark of a chapter read again after it was queued is a conflict:
+    /// the read marker is kept.
+    /// If a request fails, the marks not sent yet are queued again.
+    pub async fn flush(&self) -> Result<FlushReport> {
+        let pending =
+            std::mem::take(&mut *self.queue.lock().unwrap_or_else(PoisonError::into_inner));
+        if pending.is_empty() {
+            return Ok(FlushReport::default());
+        }
+        let (read, history) = match self.server_state(&pending).await {
+            Ok(state) => state,
+            Err(e) => {
+                self.restore(pending);
+                return Err(e);
+            }
+        };
+
+        let mut by_manga: Vec<(Uuid, Vec<QueuedMark>)> = Vec::new();
+        for mark in pending {
+            match by_manga
+                .iter_mut()
+                .find(|(manga_id, _)| *manga_id == mark.manga_id)
+            {
+                Some((_, marks)) => marks.push(mark),
+                None => by_manga.push((mark.manga_id, vec![mark])),
+            }
+        }
+
+        let mut report = FlushReport::default();
+        let mut remaining = by_manga.into_iter();
+        while let Some((manga_id, marks)) = remaining.next() {
+            let mut to_send = Vec::new();
+            for mark in &marks {
+                let read_since = history
+                    .get(&mark.chapter_id)
+                    .is_some_and(|read_date| *read_date > mark.queued_at);
+                if !mark.read && read_since {
+                    report.conflicts.push(*mark);
+                } else if mark.read == read.contains(&mark.chapter_id) {
+                    report.skipped.push(*mark);
+                } else {
+                    to_send.push(*mark);
+                }
+            }
+            if to_send.is_empty() {
+                continue;
+            }
+            let (read_ids, unread_ids): (Vec<&QueuedMark>, _) =
+                to_send.iter().partition(|mark| mark.read);
+            let result = self
+                .send_marks(
+                    manga_id,
+                    read_ids.iter().map(|mark| mark.chapter_id).collect(),
+                    unread_ids.iter().map(|mark| mark.chapter_id).collect(),
+                )
+                .await;
+            if let Err(e) = result {
+                self.restore(
+                    to_send
+                        .into_iter()
+                        .chain(remaining.flat_map(|(_, marks)| marks)),
+                );
+                return Err(e);
+            }
+            report.applied.append(&mut to_send);
+        }
+        Ok(report)
+    }
+
+    /// The read chapters of the queued manga and the read dates of the history.
+    async fn server_state(
+        &self,
+        pending: &[QueuedMark],
+    ) -> Result<(HashSet<Uuid>, HashMap<Uuid, OffsetDateTime>)> {
+        let client = self.client();
+        let mut manga_ids: Vec<Uuid> = pending.iter().map(|mark| mark.manga_id).collect();
+        manga_ids.sort();
+        manga_ids.dedup();
+        let read = match client
+            .manga()
+            .read()
+            .get()
+            .manga_ids(manga_ids)
+            .grouped(true)
+            .send()
+            .await?
+        {
+            MangaReadMarkers::Grouped(markers) => markers.data.into_values().flatten().collect(),
+            MangaReadMarkers::Ungrouped(markers) => markers.data.into_iter().collect(),
+            _ => HashSet::new(),
+        };
+        let history = client
+            .user()
+            .history()
+            .get()
+            .send()
+            .await?
+            .ratings
+            .into_iter()
+            .map(|entry| (entry.chapter_id, *entry.read_date.as_ref()))
+            .collect();
+        Ok((read, history))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::OffsetDateTime;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{body_json, method, path, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::QueuedMark;
+    use crate::v5::AuthTokens;
+    use crate::{HttpClient, MangaDexClient};
+
+    async fn client(mock_server: &MockServer) -> anyhow::Result<MangaDexClient> {
+        Ok(MangaDexClient::new_with_http_client(
+            HttpClient::builder()
+                .base_url(Url::parse(&mock_server.uri())?)
+                .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                    session: "sessiontoken".to_string(),
+                    refresh: "refreshtoken".to_string(),
+                }))
+                .build()?,
+        ))
+    }
+
+    #[tokio::test]
+    async fn next_unread_follows_the_aggregate() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let mangadex_client = client(&mock_server).await?;
+        let manga_id = Uuid::new_v4();
+        let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+
+        Mock::given(method("GET"))
+            .and(path(format!("/manga/{manga_id}/aggregate")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "volumes": {
+                    "1": {
+                        "volume": "1",
+                        "count": 3,
+                        "chapters": {
+                            "1": {"chapter": "1", "id": ids[0], "others": [], "count": 1},
+                            "2": {"chapter": "2", "id": ids[1], "others": [ids[3]], "count": 2},
+                            "3": {"chapter": "3", "id": ids[2], "others": [], "count": 1},
+                        }
+                    }
+                }
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/manga/{manga_id}/read")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "data": [ids[3]]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/manga/{manga_id}/status")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "status": "reading"
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path_regex(r"/manga/[0-9a-fA-F-]+/read"))
+            .and(body_json(
+                json!({"chapterIdsRead": [ids[1], ids[3], ids[2]]}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let reading_progress = mangadex_client.reading_progress();
+        let progress = reading_progress.progress(manga_id).await?;
+        assert_eq!((progress.read, progress.total), (1, 3));
+        assert_eq!(progress.last_read.map(|chapter| chapter.id), Some(ids[1]));
+        assert_eq!(progress.next_unread.map(|chapter| chapter.id), Some(ids[2]));
+        assert_eq!(
+            progress.status,
+            Some(mangadex_api_types::ReadingStatus::Reading)
+        );
+
+        reading_progress
+            .mark_range(manga_id, "3", "2", true)
+            .await?;
+        assert!(
+            reading_progress
+                .mark_range(manga_id, "1", "4", true)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn queued_marks_are_flushed_with_conflicts() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let mangadex_client = client(&mock_server).await?;
+        let manga_id = Uuid::new_v4();
+        let (read, already_read, read_again) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        Mock::given(method("GET"))
+            .and(path("/manga/read"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "data": {manga_id.to_string(): [already_read, read_again]}
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/user/history"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "ratings": [{"chapterId": read_again, "readDate": "2024-01-02T00:00:00+00:00"}]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/manga/{manga_id}/read")))
+            .and(body_json(json!({"chapterIdsRead": [read]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let reading_progress = mangadex_client.reading_progress();
+        reading_progress.queue_unread(manga_id, read);
+        reading_progress.queue_read(manga_id, read);
+        reading_progress.queue_read(manga_id, already_read);
+        reading_progress.restore([QueuedMark {
+            manga_id,
+            chapter_id: read_again,
+            read: false,
+            queued_at: OffsetDateTime::from_unix_timestamp(1_704_067_200)?,
+        }]);
+        assert_eq!(reading_progress.pending().len(), 3);
+
+        let report = reading_progress.flush().await?;
+        assert_eq!(report.applied.len(), 1);
+        assert_eq!(report.applied[0].chapter_id, read);
+        assert_eq!(report.skipped[0].chapter_id, already_read);
+        assert_eq!(report.conflicts[0].chapter_id, read_again);
+        assert!(reading_progress.pending().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+//! Resolve many ids at once with the `ids` filters of the collection endpoints.
+//!
+//! The ids are de-duplicated and split in chunks of [`MAX_IDS_PER_REQUEST`],
+//! one request is sent per chunk.
+//! The chunks are sent one after another, or [`Resolver::concurrency`] at a time,
+//! through the [`RateLimiter`](crate::rate_limit::RateLimiter) of the client if one is set.
+//! The ids that did not come back (deleted, unpublished or unknown) are reported in
+//! [`Resolved::missing`].
+//!
+//! Every content rating is requested for the manga and the chapters,
+//! so the default content rating filter of the API does not hide any of them.
+//!
+//! [`Resolver::hydrate`] fills the relationships that were not expanded with the `includes[]`
+//! parameter, so the typed accessors like `manga.authors()` or `chapter.manga()` return them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::ReferenceExpansionResource;
+//! use uuid::Uuid;
+//!
+//! # async fn run(manga_ids: Vec<Uuid>) -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let manga = client
+//!     .resolve()
+//!     .include(ReferenceExpansionResource::CoverArt)
+//!     .concurrency(2)
+//!     .manga(manga_ids)
+//!     .await?;
+//!
+//! for (id, manga) in &manga.found {
+//!     println!("{id}: {:?}", manga.attributes.title);
+//! }
+//! println!("not found: {:?}", manga.missing);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use futures::{StreamExt, TryStreamExt};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_schema::v5::statistics::manga::MangaStatistics;
+use mangadex_api_schema::v5::{
+    AuthorObject, ChapterObject, CoverObject, GroupObject, MangaObject, RelatedAttributes,
+    Relationship,
+};
+use mangadex_api_types::{ContentRating, ReferenceExpansionResource, RelationshipType};
+use uuid::Uuid;
+
+use crate::{HttpClientRef, MangaDexClient, Result};
+
+/// The maximum number of ids sent in one request.
+///
+/// This is the maximum `limit` of the collection endpoints,
+/// and keeps the query string under the length accepted by MangaDex.
+pub const MAX_IDS_PER_REQUEST: usize = 100;
+
+const ALL_CONTENT_RATINGS: [ContentRating; 4] = [
+    ContentRating::Safe,
+    ContentRating::Suggestive,
+    ContentRating::Erotica,
+    ContentRating::Pornographic,
+];
+
+/// The relationship types filled by [`Resolver::hydrate`].
+const HYDRATED_TYPES: [RelationshipType; 6] = [
+    RelationshipType::Manga,
+    RelationshipType::Chapter,
+    RelationshipType::CoverArt,
+    RelationshipType::Author,
+    RelationshipType::Artist,
+    RelationshipType::ScanlationGroup,
+];
+
+/// The outcome of a batch resolution.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Resolved<T> {
+    /// The resolved objects, by id.
+    pub found: HashMap<Uuid, T>,
+    /// The requested ids that did not come back, in the requested order.
+    pub missing: Vec<Uuid>,
+}
+
+impl<T> Resolved<T> {
+    /// `true` if every requested id was resolved.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// Resolves many ids of the same type with as few requests as possible.
+///
+/// Get one with [`MangaDexClient::resolve`].
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    http_client: HttpClientRef,
+    includes: Vec<ReferenceExpansionResource>,
+    concurrency: usize,
+}
+
+impl Resolver {
+    pub fn new(http_client: HttpClientRef) -> Self {
+        Self {
+            http_client,
+            includes: Vec::new(),
+            concurrency: 1,
+        }
+    }
+
+    /// Expand a relationship of the resolved objects.
+    ///
+    /// The includes are sent as is, they must be valid for the resolved type.
+    pub fn include(mut self, include: ReferenceExpansionResource) -> Self {
+        self.includes.push(include);
+        self
+    }
+
+    /// Number of chunks requested at the same time.
+    ///
+    /// Defaults to `1`, which sends the chunks one after another.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn client(&self) -> MangaDexClient {
+        MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+    }
+
+    /// Resolve manga with `GET /manga`.
+    pub async fn manga<I>(&self, ids: I) -> Result<Resolved<MangaObject>>
+    where
+        I: IntoIterator<Item = Uuid>,
+    {
+        self.resolve(ids, |chunk| async move {
+            Ok(self
+                .client()
+                .manga()
+                .get()
+                .manga_ids(chunk.clone())
+                .limit(chunk.len() as u32)
+                .content_rating(ALL_CONTENT_RATINGS.to_vec())
+                .includes(self.includes.clone())
+                .send()
+                .await?
+                .data
+                .into_iter()
+                .map(|manga| (manga.id, manga))
+                .collect())
+        })
+        .await
+    }
+
+    /// Resolve chapters with `GET /chapter`.
+    pub async fn chapters<I>(&self, ids: I) -> Result<Resolved<ChapterObject>>
+    where
+        I: IntoIterator<Item = Uuid>,
+    {
+        self.resolve(ids, |chunk| async move {
+            Ok(self
+                .client()
+                .chapter()
+                .get()
+                .chapter_ids(chunk.clone())
+                .limit(chunk.len() as u32)
+                .content_rating(ALL_CONTENT_RATINGS.to_vec())
+                .includes(self.includes.clone())
+                .send()
+                .await?
+                .data
+                .into_iter()
+                .map(|chapter| (chapter.id, chapter))
+                .collect())
+        })
+        .await
+    }
+
+    /// Resolve authors with `GET /author`.
+    pub async fn authors<I>(&self, ids: I) -> Result<Resolved<AuthorObject>>
+    where
+        I: IntoIterator<Item = Uuid>,
+    {
+        self.resolve(ids, |chunk| async move {
+            Ok(self
+                .client()
+                .author()
+                .get()
+                .author_ids(chunk.clone())
+                .limit(chunk.len() as u32)
+                .includes(self.includes.clone())
+                .send()
+                .await?
+                .data
+                .into_iter()
+                .map(|author| (author.id, author))
+                .collect())
+        })
+        .await
+    }
+
+    /// Resolve cover arts with `GET /cover`.
+    pub async fn covers<I>(&self, ids: I) -> Result<Resolved<CoverObject>>
+    where
+        I: IntoIterator<Item = Uuid>,
+    {
+        self.resolve(ids, |chunk| async move {
+            Ok(self
+                .client()
+                .cover()
+                .get()
+                .cover_ids(chunk.clone())
+                .limit(chunk.len() as u32)
+                .includes(self.includes.clone())
+                .send()
+                .await?
+                .data
+                .into_iter()
+                .map(|cover| (cover.id, cover))
+                .collect())
+        })
+        .await
+    }
+
+    /// Resolve scanlation groups with `GET /group`.
+    pub async fn groups<I>(&self, ids: I) -> Result<Resolved<GroupObject>>
+    where
+        I: IntoIterator<Item = Uuid>,
+    {
+        self.resolve(ids, |chunk| async move {
+            Ok(self
+                .client()
+                .scanlation_group()
+                .get()
+                .group_ids(chunk.clone())
+                .limit(chunk.len() as u32)
+                .includes(self.includes.clone())
+                .send()
+                .await?
+                .data
+                .into_iter()
+                .map(|group| (group.id, group))
+                .collect())
+        })
+        .await
+    }
+
+    /// Resolve the statistics of manga with `GET /statistics/manga`.
+    ///
+    /// The includes are not used.
+    pub async fn manga_statistics<I>(&self, ids: I) -> Result<Resolved<MangaStatistics>>
+    where
+        I: IntoIterator<Item = Uuid>,
+    {
+        self.resolve(ids, |chunk| async move {
+            Ok(self
+                .client()
+                .statistics()
+                .manga()
+                .get()
+                .manga(chunk)
+                .send()
+                .await?
+                .statistics
+                .into_iter()
+                .collect())
+        })
+        .await
+    }
+
+    /// Fetch the attributes of the relationships of `objects` that were not expanded.
+    ///
+    /// The manga, chapter, cover art, author, artist and scanlation group relationships are
+    /// resolved with one batch per type, the others are left as is.
+    /// Returns the ids of the relationships that did not come back.
+    ///
+    /// The includes of the resolver are not used.
+    pub async fn hydrate<'a, A, I>(&self, objects: I) -> Result<Vec<Uuid>>
+    where
+        A: 'a,
+        I: IntoIterator<Item = &'a mut ApiObject<A>>,
+    {
+        let mut relationships: Vec<&mut Relationship> = objects
+            .into_iter()
+            .flat_map(|object| object.relationships.iter_mut())
+            .filter(|rel| rel.attributes.is_none() && HYDRATED_TYPES.contains(&rel.type_))
+            .collect();
+        let ids_of = |types: &[RelationshipType]| -> Vec<Uuid> {
+            relationships
+                .iter()
+                .filter(|rel| types.contains(&rel.type_))
+                .map(|rel| rel.id)
+                .collect()
+        };
+        let resolver = Self {
+            includes: Vec::new(),
+            ..self.clone()
+        };
+        let mut attributes = HashMap::new();
+        attributes.extend(
+            resolver
+                .manga(ids_of(&[RelationshipType::Manga]))
+                .await?
+                .found
+                .into_iter()
+                .map(|(id, manga)| (id, RelatedAttributes::Manga(manga.attributes))),
+        );
+        attributes.extend(
+            resolver
+                .chapters(ids_of(&[RelationshipType::Chapter]))
+                .await?
+                .found
+                .into_iter()
+                .map(|(id, chapter)| (id, RelatedAttributes::Chapter(chapter.attributes))),
+        );
+        attributes.extend(
+            resolver
+                .covers(ids_of(&[RelationshipType::CoverArt]))
+                .await?
+                .found
+                .into_iter()
+                .map(|(id, cover)| (id, RelatedAttributes::CoverArt(cover.attributes))),
+        );
+        attributes.extend(
+            resolver
+                .authors(ids_of(&[
+                    RelationshipType::Author,
+                    RelationshipType::Artist,
+                ]))
+                .await?
+                .found
+                .into_iter()
+                .map(|(id, author)| (id, RelatedAttributes::Author(author.attributes))),
+        );
+        attributes.extend(
+            resolver
+                .groups(ids_of(&[RelationshipType::ScanlationGroup]))
+                .await?
+                .found
+                .into_iter()
+                .map(|(id, group)| (id, RelatedAttributes::ScanlationGroup(group.attributes))),
+        );
+
+        let mut missing = Vec::new();
+        for rel in relationships.iter_mut() {
+            match attributes.get(&rel.id) {
+                Some(attributes) => rel.attributes = Some(attributes.clone()),
+                None if !missing.contains(&rel.id) => missing.push(rel.id),
+                None => (),
+            }
+        }
+        Ok(missing)
+    }
+
+    async fn resolve<I, T, F, Fut>(&self, ids: I, fetch: F) -> Result<Resolved<T>>
+    where
+        I: IntoIterator<Item = Uuid>,
+        F: Fn(Vec<Uuid>) -> Fut,
+        Fut: Future<Output = Result<Vec<(Uuid, T)>>>,
+    {
+        let mut seen = HashSet::new();
+        let ids: Vec<Uuid> = ids.into_iter().filter(|id| seen.insert(*id)).collect();
+        let requested = &seen;
+        let found = futures::stream::iter(ids.chunks(MAX_IDS_PER_REQUEST))
+            .map(|chunk| fetch(chunk.to_vec()))
+            .buffer_unordered(self.concurrency)
+            .try_fold(HashMap::new(), |mut found, objects| async move {
+                found.extend(objects.into_iter().filter(|(id, _)| requested.contains(id)));
+                Ok(found)
+            })
+            .await?;
+        let missing = ids
+            .into_iter()
+            .filter(|id| !found.contains_key(id))
+            .collect();
+        Ok(Resolved { found, missing })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_schema::ApiObject;
+    use mangadex_api_schema::v5::{MangaAttributes, Relationship};
+    use mangadex_api_types::RelationshipType;
+    use serde_json::json;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    use super::MAX_IDS_PER_REQUEST;
+    use crate::{HttpClient, MangaDexClient};
+
+    fn author_json(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "author",
+            "attributes": {
+                "name": "Some Author",
+                "imageUrl": null,
+                "biography": {},
+                "twitter": null,
+                "pixiv": null,
+                "melonBook": null,
+                "fanBox": null,
+                "booth": null,
+                "nicoVideo": null,
+                "skeb": null,
+                "fantia": null,
+                "tumblr": null,
+                "youtube": null,
+                "weibo": null,
+                "naver": null,
+                "website": null,
+                "version": 1,
+                "createdAt": "2021-04-19T21:59:45+00:00",
+                "updatedAt": "2021-04-19T21:59:45+00:00",
+            },
+            "relationships": []
+        })
+    }
+
+    #[tokio::test]
+    async fn ids_are_chunked_and_missing_ones_reported() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let mut ids: Vec<Uuid> = (0..MAX_IDS_PER_REQUEST + 10)
+            .map(|_| Uuid::new_v4())
+            .collect();
+        let missing = ids[3];
+        ids.push(ids[0]);
+
+        // Answers with every requested author but `missing`.
+        Mock::given(method("GET"))
+            .and(path("/author"))
+            .respond_with(move |req: &Request| {
+                let data: Vec<_> = req
+                    .url
+                    .query_pairs()
+                    .filter(|(key, id)| key.starts_with("ids[") && *id != missing.to_string())
+                    .map(|(_, id)| author_json(&id))
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(json!({
+                    "result": "ok",
+                    "response": "collection",
+                    "data": data,
+                    "limit": MAX_IDS_PER_REQUEST,
+                    "offset": 0,
+                    "total": data.len()
+                }))
+            })
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let authors = mangadex_client
+            .resolve()
+            .concurrency(2)
+            .authors(ids.clone())
+            .await?;
+
+        assert_eq!(authors.found.len(), MAX_IDS_PER_REQUEST + 9);
+        assert_eq!(authors.missing, vec![missing]);
+        assert!(!authors.is_complete());
+        assert_eq!(authors.found[&ids[0]].id, ids[0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn relationships_are_hydrated() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let (author_id, artist_id, creator_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut manga = ApiObject::new(
+            Uuid::new_v4(),
+            RelationshipType::Manga,
+            MangaAttributes::default(),
+        );
+        for (id, type_) in [
+            (author_id, RelationshipType::Author),
+            (artist_id, RelationshipType::Artist),
+            (creator_id, RelationshipType::Creator),
+        ] {
+            let mut relationship = Relationship::default();
+            relationship.id = id;
+            relationship.type_ = type_;
+            manga.relationships.push(relationship);
+        }
+
+        Mock::given(method("GET"))
+            .and(path("/author"))
+            .and(query_param("ids[0]", author_id.to_string()))
+            .and(query_param("ids[1]", artist_id.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "collection",
+                "data": [author_json(&author_id.to_string())],
+                "limit": 2,
+                "offset": 0,
+                "total": 1
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let missing = mangadex_client.resolve().hydrate([&mut manga]).await?;
+
+        assert_eq!(missing, vec![artist_id]);
+        assert_eq!(
+            manga.authors().first().map(|author| author.id),
+            Some(author_id)
+        );
+        assert!(manga.artists().is_empty());
+        assert!(
+            manga.find_relationships(RelationshipType::Creator)[0]
+                .attributes
+                .is_none()
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,18 @@
+mod chapter;
+mod progress;
+mod validation;
+
 use uuid::Uuid;
 
 use crate::MangaDexClient;
 
+pub use chapter::{ChapterUpload, ChapterUploadBuilder, MAX_FILES_PER_BATCH};
+pub use progress::{UploadProgress, UploadedPage};
+pub use validation::{
+    ImageFormat, ImageInfo, ImageProcessor, MAX_DIMENSION, MAX_FILE_SIZE, MAX_FILES_PER_SESSION,
+    MAX_SESSION_SIZE, process_pages, validate_page, validate_pages,
+};
+
 /// An Enum for handling [`check_session`] errors
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -18,14 +29,8 @@ pub enum CheckSessionError {
 pub async fn check_session(client: &MangaDexClient) -> Result<(), CheckSessionError> {
     match client.upload().get().send().await {
         Ok(i) => Err(CheckSessionError::AlreadyExists(i.body.data.id)),
-        Err(e) => {
-            if let crate::error::Error::Api(error) = &e {
-                if error.errors.iter().any(|er| er.status == 404) {
-                    return Ok(());
-                }
-            }
-            Err(CheckSessionError::MangadexApiError(e))
-        }
+        Err(e) if e.is_not_found() => Ok(()),
+        Err(e) => Err(CheckSessionError::MangadexApiError(e)),
     }
 }
 
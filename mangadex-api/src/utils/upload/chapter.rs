@@ -0,0 +1,530 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use derive_builder::Builder;
+use mangadex_api_schema::error::MangaDexErrorResponse_;
+use mangadex_api_schema::v5::ChapterData;
+use mangadex_api_types::RelationshipType;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::utils::download::sink::page_file_name;
+use crate::v5::upload::upload_session_id::commit::post::ChapterDraft;
+use crate::v5::upload::upload_session_id::post::UploadImage;
+use crate::{HttpClientRef, MangaDexClient, Result};
+
+use super::UploadProgress;
+use super::abandon_session;
+use super::validation::{ImageProcessor, process_pages, validate_pages};
+
+/// The maximum number of files sent in one `POST /upload/{id}` request.
+pub const MAX_FILES_PER_BATCH: usize = 10;
+/// Upload a chapter: start an upload session, upload the pages and commit it.
+///
+/// The pages are uploaded by batches of [`MAX_FILES_PER_BATCH`] files.
+/// The files rejected by MangaDex are sent again up to `max_retries` times.
+///
+/// The pages are checked with [`validate_pages`] before starting the session.
+/// Set a `processor` to normalise the pages that would be rejected.
+///
+/// If `progress_path` is set, the upload progress is saved there after each batch
+/// so a crashed upload can be resumed by sending the same pages again.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::path::PathBuf;
+///
+/// use mangadex_api::MangaDexClient;
+/// use mangadex_api::v5::upload::upload_session_id::commit::post::ChapterDraft;
+/// use mangadex_api::v5::upload::upload_session_id::post::UploadImage;
+/// use mangadex_api_types::Language;
+/// use uuid::Uuid;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let client = MangaDexClient::default();
+///
+/// let mut chapter = ChapterDraft::new(Language::English);
+/// chapter.chapter = Some("1".to_string());
+///
+/// let chapter = client
+///     .upload_chapter(Uuid::new_v4())
+///     .add_group(Uuid::new_v4())
+///     .add_page(UploadImage::try_from(PathBuf::from("pages/01.png"))?)
+///     .add_page(UploadImage::try_from(PathBuf::from("pages/02.png"))?)
+///     .chapter(chapter)
+///     .terms_accepted(true)
+///     .progress_path("upload-progress.json")
+///     .build()?
+///     .send()
+///     .await?;
+///
+/// println!("uploaded chapter: {}", chapter.data.id);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    setter(into, strip_option),
+    pattern = "owned",
+    build_fn(error = "crate::error::BuilderError")
+)]
+#[non_exhaustive]
+pub struct ChapterUpload {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    manga_id: Uuid,
+    /// Scanlation groups of the chapter.
+    #[builder(setter(each = "add_group"), default)]
+    groups: Vec<Uuid>,
+    /// The pages, in order.
+    #[builder(setter(each = "add_page"))]
+    pages: Vec<UploadImage>,
+    chapter: ChapterDraft,
+    #[builder(default)]
+    terms_accepted: bool,
+    /// The file where the upload progress is saved.
+    #[builder(default)]
+    progress_path: Option<PathBuf>,
+    /// Number of times the rejected files are sent again.
+    ///
+    /// Defaults to `2`.
+    #[builder(default)]
+    max_retries: Option<u32>,
+    /// Abandon the current upload session if it is not the one being resumed.
+    ///
+    /// Otherwise, [`Error::UploadSessionAlreadyExists`] is returned.
+    #[builder(default)]
+    abandon_existing_session: bool,
+    #[builder(setter(custom), default)]
+    processor: Option<Arc<dyn ImageProcessor>>,
+}
+
+impl ChapterUploadBuilder {
+    /// Normalise the pages that MangaDex would reject before uploading them.
+    pub fn processor<P: ImageProcessor + 'static>(mut self, processor: P) -> Self {
+        self.processor = Some(Some(Arc::new(processor)));
+        self
+    }
+}
+
+impl ChapterUpload {
+    /// Get the pages to upload, normalised by the `processor` and validated.
+    pub fn prepare_pages(&self) -> Result<Vec<UploadImage>> {
+        let pages = match &self.processor {
+            Some(processor) => process_pages(&self.pages, processor)?,
+            None => self.pages.clone(),
+        };
+        validate_pages(&pages)?;
+        Ok(pages)
+    }
+
+    /// The names of the files sent to MangaDex, unique in the session.
+    fn filenames(pages: &[UploadImage]) -> Vec<String> {
+        let len = pages.len();
+        pages
+            .iter()
+            .enumerate()
+            .map(|(index, page)| page_file_name(index + 1, len, &page.filename))
+            .collect()
+    }
+
+    fn save_progress(&self, progress: &UploadProgress) -> Result<()> {
+        match &self.progress_path {
+            Some(path) => progress.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Resume the current upload session if it matches the saved progress, or start a new one.
+    async fn start_session(
+        &self,
+        client: &MangaDexClient,
+        pages: &[UploadImage],
+    ) -> Result<UploadProgress> {
+        let filenames = Self::filenames(pages);
+        let saved = match &self.progress_path {
+            Some(path) => UploadProgress::load(path)?,
+            None => None,
+        };
+        let current = match client.upload().get().send().await {
+            Ok(res) => Some(res.body.data),
+            Err(e) if e.is_not_found() => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(session) = current {
+            if let Some(mut progress) = saved.filter(|progress| {
+                progress.session_id == session.id
+                    && progress.is_for(filenames.iter().map(String::as_str))
+            }) {
+                let session_files: HashSet<Uuid> = session
+                    .relationships
+                    .iter()
+                    .filter(|relationship| {
+                        relationship.type_ == RelationshipType::UploadSessionFile
+                    })
+                    .map(|relationship| relationship.id)
+                    .collect();
+                progress.retain_uploaded(&session_files);
+                return Ok(progress);
+            }
+            if !self.abandon_existing_session {
+                return Err(Error::UploadSessionAlreadyExists(session.id));
+            }
+            abandon_session(session.id, client).await?;
+        }
+        let session = client
+            .upload()
+            .begin()
+            .post()
+            .manga_id(self.manga_id)
+            .groups(self.groups.clone())
+            .send()
+            .await?
+            .body
+            .data;
+        let progress = UploadProgress::new(session.id, filenames);
+        self.save_progress(&progress)?;
+        Ok(progress)
+    }
+
+    /// Upload the pending pages, sending the rejected files again.
+    async fn upload_pages(
+        &self,
+        client: &MangaDexClient,
+        pages: &[UploadImage],
+        progress: &mut UploadProgress,
+    ) -> Result<()> {
+        let mut last_error: Option<Error> = None;
+        for _ in 0..=self.max_retries.unwrap_or(2) {
+            let pending = progress.pending();
+            if pending.is_empty() {
+                return Ok(());
+            }
+            for batch in pending.chunks(MAX_FILES_PER_BATCH) {
+                let files: Vec<UploadImage> = batch
+                    .iter()
+                    .map(|index| UploadImage {
+                        filename: progress.pages[*index].filename.clone(),
+                        data: pages[*index].data.clone(),
+                    })
+                    .collect();
+                let res = match client
+                    .upload()
+                    .upload_session_id(progress.session_id)
+                    .post()
+                    .files(files)
+                    .send()
+                    .await
+                {
+                    Ok(res) => res.body,
+                    Err(e) => {
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                for file in res.data {
+                    progress.set_uploaded(&file.attributes.original_file_name, file.id);
+                }
+                if !res.errors.is_empty() {
+                    let mut error = MangaDexErrorResponse_::default();
+                    error.errors = res.errors;
+                    last_error = Some(Error::Api(error));
+                }
+                self.save_progress(progress)?;
+            }
+        }
+        if progress.pending().is_empty() {
+            return Ok(());
+        }
+        Err(last_error
+            .unwrap_or_else(|| Error::unknow("some pages were not returned by the upload session")))
+    }
+
+    /// Upload the chapter and get the created chapter.
+    pub async fn send(&self) -> Result<ChapterData> {
+        let pages = self.prepare_pages()?;
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut progress = self.start_session(&client, &pages).await?;
+        self.upload_pages(&client, &pages, &mut progress).await?;
+        let page_order = progress
+            .page_order()
+            .ok_or_else(|| Error::unknow("some pages were not returned by the upload session"))?;
+
+        let mut commit = client
+            .upload()
+            .upload_session_id(progress.session_id)
+            .commit()
+            .post()
+            .page_order(page_order)
+            .volume(self.chapter.volume.clone())
+            .chapter(self.chapter.chapter.clone())
+            .title(self.chapter.title.clone())
+            .translated_language(self.chapter.translated_language)
+            .external_url(self.chapter.external_url.clone())
+            .terms_accepted(self.terms_accepted);
+        if let Some(publish_at) = self.chapter.publish_at {
+            commit = commit.publish_at(publish_at);
+        }
+        let chapter = commit.send().await?.body;
+        if let Some(path) = &self.progress_path {
+            UploadProgress::remove(path)?;
+        }
+        Ok(chapter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use mangadex_api_types::{Language, MangaDexDateTime};
+    use serde_json::json;
+    use time::OffsetDateTime;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{body_partial_json, method, path, path_regex};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    use crate::utils::upload::UploadProgress;
+    use crate::utils::upload::validation::tests::png;
+    use crate::v5::AuthTokens;
+    use crate::v5::upload::upload_session_id::commit::post::ChapterDraft;
+    use crate::v5::upload::upload_session_id::post::UploadImage;
+    use crate::{HttpClient, MangaDexClient};
+
+    fn rate_limited(response: ResponseTemplate) -> ResponseTemplate {
+        response
+            .insert_header("x-ratelimit-retry-after", "1698723860")
+            .insert_header("x-ratelimit-limit", "40")
+            .insert_header("x-ratelimit-remaining", "39")
+    }
+
+    /// Accept the uploaded files, giving them an id from their page number,
+    /// but reject `003.png` the first time.
+    #[derive(Default)]
+    struct UploadResponder {
+        rejected: AtomicBool,
+    }
+
+    impl Respond for UploadResponder {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let body = String::from_utf8_lossy(&request.body);
+            let mut data = Vec::new();
+            let mut errors = Vec::new();
+            for filename in body
+                .split("filename=\"")
+                .skip(1)
+                .filter_map(|part| part.split('"').next())
+            {
+                if filename == "003.png" && !self.rejected.swap(true, Ordering::SeqCst) {
+                    errors.push(json!({
+                        "id": Uuid::new_v4(),
+                        "status": 400,
+                        "title": "Invalid file",
+                        "detail": "The file could not be processed"
+                    }));
+                    continue;
+                }
+                let page: u128 = filename[..3].parse().unwrap_or_default();
+                data.push(json!({
+                    "id": Uuid::from_u128(page),
+                    "type": "upload_session_file",
+                    "attributes": {
+                        "originalFileName": filename,
+                        "fileHash": "e199c7d73af7a58e8a4d0263f03db660",
+                        "fileSize": 4,
+                        "mimeType": "image/png",
+                        "source": "local",
+                        "version": 1,
+                    },
+                    "relationships": []
+                }));
+            }
+            rate_limited(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "errors": errors,
+                "data": data,
+            })))
+        }
+    }
+
+    fn session(session_id: Uuid, files: &[Uuid]) -> serde_json::Value {
+        let datetime = MangaDexDateTime::new(&OffsetDateTime::now_utc());
+        json!({
+            "result": "ok",
+            "response": "entity",
+            "data": {
+                "id": session_id,
+                "type": "upload_session",
+                "attributes": {
+                    "isCommitted": false,
+                    "isProcessed": false,
+                    "isDeleted": false,
+                    "version": 1,
+                    "createdAt": datetime.to_string(),
+                    "updatedAt": datetime.to_string(),
+                },
+                "relationships": files
+                    .iter()
+                    .map(|id| json!({"id": id, "type": "upload_session_file"}))
+                    .collect::<Vec<_>>()
+            }
+        })
+    }
+
+    async fn mock_commit(mock_server: &MockServer, page_order: Vec<Uuid>) {
+        let datetime = MangaDexDateTime::new(&OffsetDateTime::now_utc());
+        Mock::given(method("POST"))
+            .and(path_regex(r"/upload/[0-9a-fA-F-]+/commit"))
+            .and(body_partial_json(json!({ "pageOrder": page_order })))
+            .respond_with(rate_limited(ResponseTemplate::new(200).set_body_json(
+                json!({
+                    "result": "ok",
+                    "response": "entity",
+                    "data": {
+                        "id": Uuid::new_v4(),
+                        "type": "chapter",
+                        "attributes": {
+                            "title": null,
+                            "volume": null,
+                            "chapter": "1",
+                            "pages": page_order.len(),
+                            "translatedLanguage": "en",
+                            "version": 1,
+                            "createdAt": datetime.to_string(),
+                            "updatedAt": datetime.to_string(),
+                            "publishAt": datetime.to_string(),
+                            "readableAt": datetime.to_string(),
+                        },
+                        "relationships": [],
+                    }
+                }),
+            )))
+            .expect(1)
+            .mount(mock_server)
+            .await;
+    }
+
+    fn client(mock_server: &MockServer) -> anyhow::Result<MangaDexClient> {
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .build()?;
+        Ok(MangaDexClient::new_with_http_client(http_client))
+    }
+
+    fn pages(len: usize) -> Vec<UploadImage> {
+        (1..=len)
+            .map(|page| UploadImage {
+                filename: format!("page {page}.png"),
+                data: png(800, 1200),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn upload_chapter_batches_and_retries_the_pages() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let session_id = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                "result": "error",
+                "errors": [{
+                    "id": Uuid::new_v4(),
+                    "status": 404,
+                    "title": "Not found",
+                    "detail": "No upload session"
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/begin"))
+            .respond_with(rate_limited(
+                ResponseTemplate::new(200).set_body_json(session(session_id, &[])),
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(UploadResponder::default())
+            // 2 batches, then the rejected page.
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+        mock_commit(&mock_server, (1..=12).map(Uuid::from_u128).collect()).await;
+
+        let mut pages = client(&mock_server)?
+            .upload_chapter(Uuid::new_v4())
+            .chapter(ChapterDraft::new(Language::English))
+            .terms_accepted(true);
+        for page in self::pages(12) {
+            pages = pages.add_page(page);
+        }
+        pages.build()?.send().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_chapter_resumes_the_saved_session() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let session_id = Uuid::new_v4();
+        let progress_path = std::env::temp_dir().join(format!("{}.json", Uuid::new_v4()));
+        let mut progress = UploadProgress::new(
+            session_id,
+            ["001.png", "002.png", "003.png"].map(String::from),
+        );
+        progress.set_uploaded("001.png", Uuid::from_u128(1));
+        // Lost by the server, so it must be sent again.
+        progress.set_uploaded("002.png", Uuid::new_v4());
+        progress.save(&progress_path)?;
+
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(rate_limited(
+                ResponseTemplate::new(200)
+                    .set_body_json(session(session_id, &[Uuid::from_u128(1)])),
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/begin"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(UploadResponder {
+                rejected: AtomicBool::new(true),
+            })
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mock_commit(&mock_server, (1..=3).map(Uuid::from_u128).collect()).await;
+
+        let mut builder = client(&mock_server)?
+            .upload_chapter(Uuid::new_v4())
+            .chapter(ChapterDraft::new(Language::English))
+            .progress_path(progress_path.clone())
+            .terms_accepted(true);
+        for page in pages(3) {
+            builder = builder.add_page(page);
+        }
+        builder.build()?.send().await?;
+        assert!(!progress_path.exists());
+
+        Ok(())
+    }
+}
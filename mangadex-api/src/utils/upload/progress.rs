@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::Result;
+use crate::atomic_file;
+
+/// A page of an [`UploadProgress`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct UploadedPage {
+    /// The name of the file sent to MangaDex.
+    pub filename: String,
+    /// The Upload Session File ID, if the page is uploaded.
+    pub file_id: Option<Uuid>,
+}
+
+/// The state of a chapter upload, saved to resume it after a crash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct UploadProgress {
+    pub session_id: Uuid,
+    pub pages: Vec<UploadedPage>,
+}
+
+impl UploadProgress {
+    pub fn new<I: IntoIterator<Item = String>>(session_id: Uuid, filenames: I) -> Self {
+        Self {
+            session_id,
+            pages: filenames
+                .into_iter()
+                .map(|filename| UploadedPage {
+                    filename,
+                    file_id: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Check if the progress was saved for these pages.
+    pub fn is_for<'a, I: IntoIterator<Item = &'a str>>(&self, filenames: I) -> bool {
+        let mut filenames = filenames.into_iter();
+        self.pages
+            .iter()
+            .all(|page| filenames.next() == Some(page.filename.as_str()))
+            && filenames.next().is_none()
+    }
+
+    /// Forget the pages whose file is no longer in the upload session.
+    pub fn retain_uploaded(&mut self, session_files: &HashSet<Uuid>) {
+        for page in &mut self.pages {
+            if page.file_id.is_some_and(|id| !session_files.contains(&id)) {
+                page.file_id = None;
+            }
+        }
+    }
+
+    /// Get the position (starting at 0) of the pages that are not uploaded yet.
+    pub fn pending(&self) -> Vec<usize> {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| page.file_id.is_none())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Set the Upload Session File ID of the page sent with `filename`.
+    pub fn set_uploaded(&mut self, filename: &str, file_id: Uuid) -> bool {
+        match self.pages.iter_mut().find(|page| page.filename == filename) {
+            Some(page) => {
+                page.file_id = Some(file_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the Upload Session File IDs in the page order, if every page is uploaded.
+    pub fn page_order(&self) -> Option<Vec<Uuid>> {
+        self.pages.iter().map(|page| page.file_id).collect()
+    }
+
+    /// Load the progress saved in a JSON file, if any.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(content) => {
+                Ok(Some(serde_json::from_slice(&content).map_err(|e| {
+                    crate::error::Error::ParseError(e.to_string())
+                })?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save the progress in a JSON file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_vec_pretty(self)
+            .map_err(|e| crate::error::Error::ParseError(e.to_string()))?;
+        atomic_file::write(path, &content)?;
+        Ok(())
+    }
+
+    /// Remove the progress file.
+    pub fn remove<P: AsRef<Path>>(path: P) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use crate::Result;
+use crate::error::Error;
+use crate::v5::upload::upload_session_id::post::UploadImage;
+
+/// The maximum size of an uploaded file.
+pub const MAX_FILE_SIZE: usize = 20 * 1024 * 1024;
+/// The maximum number of files in an upload session.
+pub const MAX_FILES_PER_SESSION: usize = 500;
+/// The maximum size of all the files of an upload session.
+pub const MAX_SESSION_SIZE: usize = 150 * 1024 * 1024;
+/// The maximum width and height of an uploaded image, in pixels.
+pub const MAX_DIMENSION: u32 = 10_000;
+
+/// An image format recognized from the first bytes of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    /// Not accepted by MangaDex, it must be re-encoded.
+    WebP,
+}
+
+impl ImageFormat {
+    /// Recognize the format from the magic bytes.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(Self::WebP)
+        } else {
+            None
+        }
+    }
+
+    /// Check if MangaDex accepts this format.
+    pub fn is_accepted(&self) -> bool {
+        !matches!(self, Self::WebP)
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Jpeg => &["jpg", "jpeg"],
+            Self::Png => &["png"],
+            Self::Gif => &["gif"],
+            Self::WebP => &["webp"],
+        }
+    }
+}
+
+/// What was read from the header of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// The size of the file, in bytes.
+    pub size: usize,
+}
+
+impl ImageInfo {
+    /// Read the format and the dimensions of an image without decoding it.
+    pub fn read(data: &[u8]) -> Option<Self> {
+        let format = ImageFormat::sniff(data)?;
+        let (width, height) = match format {
+            ImageFormat::Png => (be_u32(data, 16)?, be_u32(data, 20)?),
+            ImageFormat::Gif => (le_u16(data, 6)? as u32, le_u16(data, 8)? as u32),
+            ImageFormat::Jpeg => jpeg_dimensions(data)?,
+            ImageFormat::WebP => webp_dimensions(data)?,
+        };
+        Some(Self {
+            format,
+            width,
+            height,
+            size: data.len(),
+        })
+    }
+
+    /// Check if the image exceeds the MangaDex limits.
+    pub fn exceeds_limits(&self) -> bool {
+        self.size > MAX_FILE_SIZE || self.width > MAX_DIMENSION || self.height > MAX_DIMENSION
+    }
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn le_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u24(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+/// Read the dimensions from the Start Of Frame segment.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    loop {
+        while *data.get(offset)? != 0xFF {
+            offset += 1;
+        }
+        while *data.get(offset)? == 0xFF {
+            offset += 1;
+        }
+        let marker = *data.get(offset)?;
+        offset += 1;
+        match marker {
+            // Markers without a segment.
+            0x01 | 0xD0..=0xD9 => continue,
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = be_u16(data, offset + 3)?;
+                let width = be_u16(data, offset + 5)?;
+                return Some((width as u32, height as u32));
+            }
+            _ => offset += be_u16(data, offset)? as usize,
+        }
+    }
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8X" => Some((le_u24(data, 24)? + 1, le_u24(data, 27)? + 1)),
+        b"VP8 " => Some((
+            (le_u16(data, 26)? & 0x3FFF) as u32,
+            (le_u16(data, 28)? & 0x3FFF) as u32,
+        )),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        _ => None,
+    }
+}
+
+/// Check that a page is an image accepted by MangaDex.
+///
+/// The format is read from the content, and must match the file extension.
+pub fn validate_page(page: &UploadImage) -> Result<ImageInfo> {
+    let invalid = |reason: String| Error::InvalidImage(page.filename.clone(), reason);
+    let info = ImageInfo::read(&page.data)
+        .ok_or_else(|| invalid(String::from("not a JPEG, PNG or GIF image")))?;
+    if !info.format.is_accepted() {
+        return Err(invalid(format!(
+            "{:?} images are not accepted",
+            info.format
+        )));
+    }
+    let extension = page
+        .filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    if !info.format.extensions().contains(&extension.as_str()) {
+        return Err(invalid(format!(
+            "the extension doesn't match the {:?} format",
+            info.format
+        )));
+    }
+    if info.size > MAX_FILE_SIZE {
+        return Err(invalid(format!(
+            "the file weighs more than {MAX_FILE_SIZE} bytes"
+        )));
+    }
+    if info.width > MAX_DIMENSION || info.height > MAX_DIMENSION {
+        return Err(invalid(format!(
+            "the image is larger than {MAX_DIMENSION}x{MAX_DIMENSION} pixels"
+        )));
+    }
+    Ok(info)
+}
+
+/// Check the pages of a chapter against the MangaDex limits.
+pub fn validate_pages(pages: &[UploadImage]) -> Result<()> {
+    if pages.is_empty() {
+        return Err(Error::RequestBuilderError(String::from(
+            "a chapter needs at least one page",
+        )));
+    }
+    if pages.len() > MAX_FILES_PER_SESSION {
+        return Err(Error::RequestBuilderError(format!(
+            "a chapter can't have more than {MAX_FILES_PER_SESSION} pages"
+        )));
+    }
+    let mut total_size: usize = 0;
+    for page in pages {
+        total_size += validate_page(page)?.size;
+    }
+    if total_size > MAX_SESSION_SIZE {
+        return Err(Error::RequestBuilderError(format!(
+            "the pages can't weigh more than {MAX_SESSION_SIZE} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Normalise the pages that MangaDex would reject.
+///
+/// This crate doesn't decode images: implement this trait with an image library
+/// to re-encode the pages or split the tall webtoon strips.
+pub trait ImageProcessor: Send + Sync {
+    /// Get the pages replacing `page`, in order.
+    ///
+    /// This is only called for the pages in a format not accepted by MangaDex
+    /// or exceeding the size or dimension limits.
+    fn process(&self, page: UploadImage, info: &ImageInfo) -> Result<Vec<UploadImage>>;
+}
+
+impl<P: ImageProcessor + ?Sized> ImageProcessor for std::sync::Arc<P> {
+    fn process(&self, page: UploadImage, info: &ImageInfo) -> Result<Vec<UploadImage>> {
+        (**self).process(page, info)
+    }
+}
+
+impl std::fmt::Debug for dyn ImageProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ImageProcessor")
+    }
+}
+
+/// Run the `processor` on the pages that need it.
+pub fn process_pages<P: ImageProcessor + ?Sized>(
+    pages: &[UploadImage],
+    processor: &P,
+) -> Result<Vec<UploadImage>> {
+    let mut processed = Vec::with_capacity(pages.len());
+    for page in pages {
+        match ImageInfo::read(&page.data) {
+            Some(info) if !info.format.is_accepted() || info.exceeds_limits() => {
+                processed.extend(processor.process(page.clone(), &info)?);
+            }
+            _ => processed.push(page.clone()),
+        }
+    }
+    Ok(processed)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{ImageFormat, ImageInfo, ImageProcessor, process_pages, validate_page};
+    use crate::error::Error;
+    use crate::v5::upload::upload_session_id::post::UploadImage;
+
+    pub(crate) fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn image_headers_are_read() {
+        assert_eq!(
+            ImageInfo::read(&png(800, 1200)).map(|info| (info.format, info.width, info.height)),
+            Some((ImageFormat::Png, 800, 1200))
+        );
+
+        let gif = b"GIF89a\x20\x03\xb0\x04\0\0\0".to_vec();
+        assert_eq!(
+            ImageInfo::read(&gif).map(|info| (info.width, info.height)),
+            Some((800, 1200))
+        );
+
+        // SOI, an APP0 segment, then a baseline SOF0 segment.
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x04,
+            0xB0, 0x03, 0x20, 0x03,
+        ];
+        assert_eq!(
+            ImageInfo::read(&jpeg).map(|info| (info.format, info.width, info.height)),
+            Some((ImageFormat::Jpeg, 800, 1200))
+        );
+
+        assert!(ImageInfo::read(b"not an image").is_none());
+    }
+
+    #[test]
+    fn invalid_pages_are_rejected() {
+        let page = |filename: &str, data: Vec<u8>| UploadImage {
+            filename: filename.to_string(),
+            data,
+        };
+        assert!(validate_page(&page("01.png", png(800, 1200))).is_ok());
+        assert!(matches!(
+            validate_page(&page("01.jpg", png(800, 1200))),
+            Err(Error::InvalidImage(..))
+        ));
+        assert!(matches!(
+            validate_page(&page("01.png", b"corrupted".to_vec())),
+            Err(Error::InvalidImage(..))
+        ));
+        assert!(matches!(
+            validate_page(&page("01.png", png(800, 24000))),
+            Err(Error::InvalidImage(..))
+        ));
+    }
+
+    /// Split the tall images in two.
+    struct Splitter;
+
+    impl ImageProcessor for Splitter {
+        fn process(&self, page: UploadImage, info: &ImageInfo) -> crate::Result<Vec<UploadImage>> {
+            let (stem, _) = page.filename.rsplit_once('.').unwrap_or_default();
+            Ok((1..=2)
+                .map(|part| UploadImage {
+                    filename: format!("{stem}-{part}.png"),
+                    data: png(info.width, info.height / 2),
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn tall_pages_are_processed() -> crate::Result<()> {
+        let pages = vec![
+            UploadImage {
+                filename: "01.png".to_string(),
+                data: png(800, 1200),
+            },
+            UploadImage {
+                filename: "02.png".to_string(),
+                data: png(800, 16000),
+            },
+        ];
+        let pages = process_pages(&pages, &Splitter)?;
+        let filenames: Vec<&str> = pages.iter().map(|page| page.filename.as_str()).collect();
+        assert_eq!(filenames, ["01.png", "02-1.png", "02-2.png"]);
+        for page in &pages {
+            validate_page(page)?;
+        }
+        Ok(())
+    }
+}
@@ -35,6 +35,12 @@ use tokio::sync::RwLock;
 
 #[cfg(feature = "utils")]
 use crate::utils::download::DownloadBuilder;
+#[cfg(feature = "utils")]
+use crate::utils::reading_progress::ReadingProgress;
+#[cfg(feature = "utils")]
+use crate::utils::resolve::Resolver;
+#[cfg(feature = "utils")]
+use crate::utils::upload::ChapterUploadBuilder;
 use crate::v5::api_client::ApiClientEndpoint;
 use crate::v5::at_home::AtHomeBuilder;
 use crate::v5::auth::AuthBuilder;
@@ -144,7 +150,11 @@ impl MangaDexClient {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new_with_http_client(http_client: HttpClient) -> Self {
+    pub fn new_with_http_client(mut http_client: HttpClient) -> Self {
+        // A corrupted or unreadable token store shouldn't prevent the client creation,
+        // the error is logged and available with `HttpClient::get_token_store_error`.
+        let result = http_client.load_from_token_store();
+        http_client.record_token_store_result(result);
         Self::new_with_http_client_ref(create_ref_counted_http_client(http_client))
     }
 
@@ -158,17 +168,23 @@ impl MangaDexClient {
     }
 
     pub async fn set_auth_tokens(&self, auth_tokens: &AuthTokens) -> Result<()> {
-        let mut client = self.http_client.write().await;
-
-        client.set_auth_tokens(auth_tokens);
-        Ok(())
+        // The token store is written after releasing the lock, on the blocking thread pool.
+        let snapshot = {
+            let mut client = self.http_client.write().await;
+            client.replace_auth_tokens(Some(auth_tokens.clone()));
+            client.token_store_snapshot()
+        };
+        crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await
     }
 
     pub async fn clear_auth_tokens(&self) -> Result<()> {
-        let mut client = self.http_client.write().await;
-
-        client.clear_auth_tokens();
-        Ok(())
+        // The token store is written after releasing the lock, on the blocking thread pool.
+        let snapshot = {
+            let mut client = self.http_client.write().await;
+            client.replace_auth_tokens(None);
+            client.token_store_snapshot()
+        };
+        crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await
     }
     pub async fn get_auth_tokens(&self) -> Result<AuthTokens> {
         let client = &self.http_client.read().await;
@@ -354,6 +370,27 @@ impl MangaDexClient {
         pub fn download(&self) -> DownloadBuilder {
             DownloadBuilder::new(self.http_client.clone())
         }
+
+        /// Get a builder to upload a chapter of the manga.
+        pub fn upload_chapter(&self, manga_id: uuid::Uuid) -> ChapterUploadBuilder {
+            ChapterUploadBuilder::default()
+                .http_client(self.http_client.clone())
+                .manga_id(manga_id)
+        }
+
+        /// Get a resolver fetching many manga, chapters, authors... by id.
+        ///
+        /// See [`crate::utils::resolve`].
+        pub fn resolve(&self) -> Resolver {
+            Resolver::new(self.http_client.clone())
+        }
+
+        /// Get the reading progress manager of the logged-in user.
+        ///
+        /// See [`crate::utils::reading_progress`].
+        pub fn reading_progress(&self) -> ReadingProgress {
+            ReadingProgress::new(self.http_client.clone())
+        }
     }
 
     pub fn forums(&self) -> ForumsEndpoint {
@@ -367,16 +404,40 @@ impl MangaDexClient {
     }
     cfg_oauth! {
         pub async fn set_client_info(&self, client_info: &ClientInfo) -> Result<()> {
-            let mut client = self.http_client.write().await;
-            client.set_client_info(client_info);
-            Ok(())
+            let snapshot = {
+                let mut client = self.http_client.write().await;
+                client.replace_client_info(Some(client_info.clone()));
+                client.token_store_snapshot()
+            };
+            crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await
         }
     }
     cfg_oauth! {
         pub async fn clear_client_info(&self) -> Result<()> {
-            let mut client = self.http_client.write().await;
-            client.clear_client_info();
-            Ok(())
+            let snapshot = {
+                let mut client = self.http_client.write().await;
+                client.replace_client_info(None);
+                client.token_store_snapshot()
+            };
+            crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await
+        }
+    }
+    cfg_oauth! {
+        /// End the session on the server with the `logout` endpoint, then forget the tokens.
+        ///
+        /// The tokens are forgotten even if the request fails.
+        pub async fn logout(&self) -> Result<()> {
+            let res = self.oauth().logout().send().await;
+            self.clear_auth_tokens().await?;
+            res
+        }
+    }
+    cfg_oauth! {
+        /// Decode the claims of the access token stored in the client.
+        ///
+        /// The token signature is not verified.
+        pub async fn get_token_claims(&self) -> Result<crate::v5::oauth::claims::AccessTokenClaims> {
+            crate::v5::oauth::claims::decode(&self.get_auth_tokens().await?.session)
         }
     }
     pub async fn get_reqwest_client(&self) -> reqwest::Client {
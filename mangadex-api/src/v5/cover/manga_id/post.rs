@@ -129,11 +129,7 @@ impl Endpoint for UploadCover {
 
 impl UploadCover {
     pub async fn send(&self) -> Result<Limited<<Self as Endpoint>::Response>> {
-        self.http_client
-            .read()
-            .await
-            .send_request_with_rate_limit(self)
-            .await
+        crate::http_client::send_with_rate_limit(&self.http_client, self).await
     }
 }
 
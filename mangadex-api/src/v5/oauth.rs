@@ -1,8 +1,19 @@
+pub mod authorization_code;
+pub mod claims;
 pub mod login;
+pub mod logout;
+pub mod pkce;
 pub mod refresh_token;
+pub mod revoke;
+pub mod userinfo;
 
+use authorization_code::ExchangeCodeBuilder;
 use login::RetriveTokensBuilder;
+use logout::LogoutBuilder;
+use pkce::AuthorizationRequestBuilder;
 use refresh_token::RefreshTokensBuilder;
+use revoke::RevokeTokenBuilder;
+use userinfo::GetUserInfoBuilder;
 
 use crate::HttpClientRef;
 
@@ -14,6 +25,11 @@ create_endpoint_node! {
     #[methods] {
         login() -> RetriveTokensBuilder;
         refresh() -> RefreshTokensBuilder;
+        authorize() -> AuthorizationRequestBuilder;
+        exchange_code() -> ExchangeCodeBuilder;
+        logout() -> LogoutBuilder;
+        revoke() -> RevokeTokenBuilder;
+        userinfo() -> GetUserInfoBuilder;
     }
 }
 
@@ -24,6 +40,22 @@ impl OAuthBuiderMethods for OAuthBuider {
     fn refresh(&self) -> RefreshTokensBuilder {
         RefreshTokensBuilder::default().http_client(<&Self as Into<HttpClientRef>>::into(self))
     }
+    fn authorize(&self) -> AuthorizationRequestBuilder {
+        AuthorizationRequestBuilder::default()
+            .http_client(<&Self as Into<HttpClientRef>>::into(self))
+    }
+    fn exchange_code(&self) -> ExchangeCodeBuilder {
+        ExchangeCodeBuilder::default().http_client(<&Self as Into<HttpClientRef>>::into(self))
+    }
+    fn logout(&self) -> LogoutBuilder {
+        LogoutBuilder::default().http_client(<&Self as Into<HttpClientRef>>::into(self))
+    }
+    fn revoke(&self) -> RevokeTokenBuilder {
+        RevokeTokenBuilder::default().http_client(<&Self as Into<HttpClientRef>>::into(self))
+    }
+    fn userinfo(&self) -> GetUserInfoBuilder {
+        GetUserInfoBuilder::default().http_client(<&Self as Into<HttpClientRef>>::into(self))
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
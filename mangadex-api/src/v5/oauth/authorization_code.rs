@@ -0,0 +1,135 @@
+//! Builder for the OAuth authorization code exchange.
+//!
+//! <https://api.mangadex.org/docs/02-authentication/>
+//!
+//! The code is obtained with the [Authorization Code flow](super::pkce).
+//! Public clients don't have a secret: leave the `client_secret` of their [`ClientInfo`] empty.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::v5::MangaDexClient;
+//! use mangadex_api_schema::v5::oauth::ClientInfo;
+//! use url::Url;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! client.set_client_info(&non_exhaustive::non_exhaustive!(ClientInfo {
+//!     client_id: "someClientId".to_string(),
+//!     client_secret: String::new(),
+//! })).await?;
+//!
+//! let tokens = client
+//!     .oauth()
+//!     .exchange_code()
+//!     .code("someCode")
+//!     .code_verifier("someVerifier")
+//!     .redirect_uri(Url::parse("http://127.0.0.1:8080/callback")?)
+//!     .send()
+//!     .await?;
+//!
+//! println!("tokens: {:?}", tokens);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ClientInfo`]: mangadex_api_schema::v5::oauth::ClientInfo
+
+use derive_builder::Builder;
+use mangadex_api_schema::v5::oauth::OAuthTokenResponse;
+use mangadex_api_types::oauth::GrantTypeSupported;
+use reqwest::Method;
+use serde::Serialize;
+use url::Url;
+
+use crate::Result;
+use crate::v5::HttpClientRef;
+
+/// Exchange an authorization code for the session tokens.
+///
+/// Makes a request to `POST https://auth.mangadex.org/realms/mangadex/protocol/openid-connect/token`.
+#[cfg_attr(
+    feature = "deserializable-endpoint",
+    derive(serde::Deserialize, getset::Getters, getset::Setters)
+)]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    setter(into, strip_option),
+    build_fn(error = "crate::error::BuilderError")
+)]
+#[non_exhaustive]
+pub struct ExchangeCode {
+    /// This should never be set manually as this is only for internal use.
+    #[doc(hidden)]
+    #[cfg_attr(feature = "deserializable-endpoint", serde(skip))]
+    #[builder(pattern = "immutable")]
+    #[cfg_attr(feature = "deserializable-endpoint", getset(set = "pub", get = "pub"))]
+    pub http_client: HttpClientRef,
+
+    /// The code sent to the redirect URI.
+    pub code: String,
+
+    /// The PKCE verifier of the authorization request.
+    pub code_verifier: String,
+
+    /// The redirect URI of the authorization request.
+    pub redirect_uri: Url,
+}
+
+#[derive(Clone, Serialize)]
+struct ExchangeCodeBody {
+    grant_type: GrantTypeSupported,
+    code: String,
+    code_verifier: String,
+    redirect_uri: Url,
+    client_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    client_secret: String,
+}
+
+impl ExchangeCode {
+    pub async fn send(&mut self) -> Result<OAuthTokenResponse> {
+        let res = {
+            let client = self.http_client.read().await;
+            let client_info = client
+                .get_client_info()
+                .ok_or(crate::error::Error::MissingClientInfo)?;
+            let params = ExchangeCodeBody {
+                grant_type: GrantTypeSupported::AuthorizationCode,
+                code: self.code.to_owned(),
+                code_verifier: self.code_verifier.to_owned(),
+                redirect_uri: self.redirect_uri.to_owned(),
+                client_id: client_info.client_id.to_owned(),
+                client_secret: client_info.client_secret.to_owned(),
+            };
+            let res = client
+                .client
+                .request(
+                    Method::POST,
+                    client
+                        .auth_url
+                        .join("/realms/mangadex/protocol/openid-connect/token")?,
+                )
+                .form(&params)
+                .send()
+                .await?;
+            if res.status().is_client_error() || res.status().is_server_error() {
+                return Err(super::OAuthError::handle_resp(res).await);
+            }
+            res.json::<OAuthTokenResponse>().await?
+        };
+        let snapshot = {
+            let mut client = self.http_client.write().await;
+            client.set_oauth_tokens(&res);
+            client.token_store_snapshot()
+        };
+        crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await?;
+        Ok(res)
+    }
+}
+
+builder_send! {
+    #[builder] ExchangeCodeBuilder,
+    OAuthTokenResponse
+}
@@ -0,0 +1,61 @@
+//! Decoding of the access token claims.
+//!
+//! The access tokens are JWTs signed by the MangaDex auth server.
+//! Their signature is **not** verified here: only use the claims for display and scheduling,
+//! such as refreshing the session before [`AccessTokenClaims::exp`].
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+pub use mangadex_api_schema::v5::oauth::{AccessTokenClaims, RealmAccess};
+
+use crate::Result;
+use crate::error::Error;
+
+/// Decode the claims of an access token.
+pub fn decode(access_token: &str) -> Result<AccessTokenClaims> {
+    let payload = access_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| Error::ParseError("the access token is not a JWT".to_string()))?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| Error::ParseError(format!("invalid access token payload: {e}")))?;
+    serde_json::from_slice(&payload)
+        .map_err(|e| Error::ParseError(format!("invalid access token claims: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use serde_json::json;
+
+    use super::decode;
+
+    #[test]
+    fn access_token_claims_are_decoded() -> anyhow::Result<()> {
+        let payload = json!({
+            "exp": 1698723860,
+            "iat": 1698722960,
+            "iss": "https://auth.mangadex.org/realms/mangadex",
+            "sub": "f6a8d1b5-8b0e-4d3b-9f4e-3f8c1d1a2b3c",
+            "azp": "someClientId",
+            "scope": "openid groups profile",
+            "preferred_username": "myusername",
+            "realm_access": {"roles": ["ROLE_USER", "ROLE_MEMBER"]},
+            "roles": ["ROLE_USER"]
+        });
+        let token = format!(
+            "eyJhbGciOiJSUzI1NiJ9.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        );
+
+        let claims = decode(&token)?;
+
+        assert_eq!(claims.exp, 1698723860);
+        assert_eq!(claims.sub, "f6a8d1b5-8b0e-4d3b-9f4e-3f8c1d1a2b3c");
+        assert!(claims.has_role("ROLE_MEMBER"));
+        assert!(decode("sessiontoken").is_err());
+        Ok(())
+    }
+}
@@ -35,13 +35,9 @@
 
 use derive_builder::Builder;
 use mangadex_api_schema::v5::oauth::OAuthTokenResponse;
-use mangadex_api_schema::v5::AuthTokens;
 use mangadex_api_types::oauth::GrantTypeSupported;
 use reqwest::Method;
 use serde::Serialize;
-#[cfg(not(test))]
-use url::Url;
-
 use crate::v5::HttpClientRef;
 use crate::Result;
 use mangadex_api_types::{Password, Username};
@@ -95,24 +91,12 @@ impl RetriveTokens {
                 client_id: client_info.client_id.to_owned(),
                 client_secret: client_info.client_secret.to_owned(),
             };
-            #[cfg(test)]
             let res = client
                 .client
                 .request(
                     Method::POST,
                     client
-                        .base_url
-                        .join("/realms/mangadex/protocol/openid-connect/token")?,
-                )
-                .form(&params)
-                .send()
-                .await?;
-            #[cfg(not(test))]
-            let res = client
-                .client
-                .request(
-                    Method::POST,
-                    Url::parse(crate::AUTH_URL)?
+                        .auth_url
                         .join("/realms/mangadex/protocol/openid-connect/token")?,
                 )
                 .form(&params)
@@ -123,11 +107,12 @@ impl RetriveTokens {
             }
             res.json::<OAuthTokenResponse>().await?
         };
-        {
-            let auth_tokens: AuthTokens = From::from(res.clone());
+        let snapshot = {
             let mut client = self.http_client.write().await;
-            client.set_auth_tokens(&auth_tokens);
+            client.set_oauth_tokens(&res);
+            client.token_store_snapshot()
         };
+        crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await?;
         Ok(res)
     }
 }
@@ -157,6 +142,7 @@ mod tests {
         let mock_server = MockServer::start().await;
         let http_client: HttpClient = HttpClient::builder()
             .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
             .build()?;
         let mangadex_client = MangaDexClient::new_with_http_client(http_client);
 
@@ -224,6 +210,7 @@ mod tests {
         let mock_server = MockServer::start().await;
         let http_client: HttpClient = HttpClient::builder()
             .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
             .build()?;
         let mangadex_client = MangaDexClient::new_with_http_client(http_client);
 
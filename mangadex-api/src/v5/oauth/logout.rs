@@ -0,0 +1,138 @@
+//! Builder for the OpenID Connect logout endpoint.
+//!
+//! <https://api.mangadex.org/docs/02-authentication/>
+//!
+//! It ends the session on the server: its access and refresh tokens can't be used anymore.
+//! The tokens stored in the client are kept, use
+//! [`MangaDexClient::logout`](crate::MangaDexClient::logout) to also forget them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::v5::MangaDexClient;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! client.oauth().logout().send().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use derive_builder::Builder;
+use reqwest::Method;
+use serde::Serialize;
+
+use crate::Result;
+use crate::v5::HttpClientRef;
+
+/// End the session of the refresh token stored in the client.
+///
+/// Makes a request to `POST https://auth.mangadex.org/realms/mangadex/protocol/openid-connect/logout`.
+#[cfg_attr(
+    feature = "deserializable-endpoint",
+    derive(serde::Deserialize, getset::Getters, getset::Setters)
+)]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    setter(into, strip_option),
+    build_fn(error = "crate::error::BuilderError")
+)]
+#[non_exhaustive]
+pub struct Logout {
+    /// This should never be set manually as this is only for internal use.
+    #[doc(hidden)]
+    #[cfg_attr(feature = "deserializable-endpoint", serde(skip))]
+    #[builder(pattern = "immutable")]
+    #[cfg_attr(feature = "deserializable-endpoint", getset(set = "pub", get = "pub"))]
+    pub http_client: HttpClientRef,
+}
+
+#[derive(Clone, Serialize)]
+struct LogoutBody {
+    refresh_token: String,
+    client_id: String,
+    /// Empty for the public clients.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    client_secret: String,
+}
+
+impl Logout {
+    pub async fn send(&mut self) -> Result<()> {
+        let client = self.http_client.read().await;
+        let client_info = client
+            .get_client_info()
+            .ok_or(crate::error::Error::MissingClientInfo)?;
+        let auth_tokens = client
+            .get_tokens()
+            .ok_or(crate::error::Error::MissingTokens)?;
+        let params = LogoutBody {
+            refresh_token: auth_tokens.refresh.to_owned(),
+            client_id: client_info.client_id.to_owned(),
+            client_secret: client_info.client_secret.to_owned(),
+        };
+        let res = client
+            .client
+            .request(
+                Method::POST,
+                client
+                    .auth_url
+                    .join("/realms/mangadex/protocol/openid-connect/logout")?,
+            )
+            .form(&params)
+            .send()
+            .await?;
+        if res.status().is_client_error() || res.status().is_server_error() {
+            return Err(super::OAuthError::handle_resp(res).await);
+        }
+        Ok(())
+    }
+}
+
+builder_send! {
+    #[builder] LogoutBuilder,
+    ()
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_schema::v5::oauth::ClientInfo;
+    use url::Url;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::v5::AuthTokens;
+    use crate::{HttpClient, MangaDexClient};
+
+    #[tokio::test]
+    async fn logout_ends_the_session_and_forgets_the_tokens() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
+            .client_info(non_exhaustive::non_exhaustive!(ClientInfo {
+                client_id: "someClientId".to_string(),
+                client_secret: "someClientSecret".to_string(),
+            }))
+            .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("POST"))
+            .and(path("/realms/mangadex/protocol/openid-connect/logout"))
+            .and(body_string_contains("refresh_token=refreshtoken"))
+            .and(body_string_contains("client_id=someClientId"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        mangadex_client.logout().await?;
+
+        assert!(mangadex_client.get_auth_tokens().await.is_err());
+        Ok(())
+    }
+}
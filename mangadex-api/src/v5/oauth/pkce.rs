@@ -0,0 +1,430 @@
+//! Authorization Code flow with PKCE, for the public clients.
+//!
+//! <https://api.mangadex.org/docs/02-authentication/>
+//!
+//! 1. [`authorize`](super::OAuthBuiderMethods::authorize) prepares an [`AuthorizationSession`]
+//!    with a random state and PKCE verifier.
+//! 2. The user opens [`AuthorizationSession::url`] and logs in.
+//!    The browser is then redirected to the redirect URI with a `code` and the `state`.
+//! 3. [`AuthorizationSession::exchange`] checks the state and exchanges the code for the tokens,
+//!    which are stored in the client.
+//!
+//! With the `oauth-listener` feature, `RedirectListener` receives the redirect on a local port,
+//! for desktop apps and tests.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::v5::MangaDexClient;
+//! use mangadex_api_schema::v5::oauth::ClientInfo;
+//! use url::Url;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! client.set_client_info(&non_exhaustive::non_exhaustive!(ClientInfo {
+//!     client_id: "somePublicClientId".to_string(),
+//!     client_secret: String::new(),
+//! })).await?;
+//!
+//! let session = client
+//!     .oauth()
+//!     .authorize()
+//!     .redirect_uri(Url::parse("http://127.0.0.1:8080/callback")?)
+//!     .prepare()
+//!     .await?;
+//!
+//! println!("Open {} to log in, then paste the URL you are redirected to", session.url);
+//! let mut redirect = String::new();
+//! std::io::stdin().read_line(&mut redirect)?;
+//! let tokens = session
+//!     .exchange(&client, &Url::parse(redirect.trim())?)
+//!     .await?;
+//!
+//! println!("tokens: {:?}", tokens);
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use derive_builder::Builder;
+use mangadex_api_schema::v5::oauth::OAuthTokenResponse;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "oauth-listener")]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(feature = "oauth-listener")]
+use tokio::net::TcpListener;
+use url::Url;
+
+use crate::error::Error;
+use crate::v5::HttpClientRef;
+use crate::{MangaDexClient, Result};
+
+/// The path of the MangaDex authorization endpoint.
+const AUTHORIZE_PATH: &str = "/realms/mangadex/protocol/openid-connect/auth";
+
+/// A PKCE verifier with its `S256` challenge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    /// The challenge method sent in the authorization URL.
+    pub const METHOD: &'static str = "S256";
+
+    /// Generate a random verifier.
+    ///
+    /// Fails with [`Error::Io`] if the system random number generator is unavailable.
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_verifier(random_string()?))
+    }
+
+    /// Compute the challenge of `verifier`.
+    pub fn from_verifier<S: Into<String>>(verifier: S) -> Self {
+        let verifier = verifier.into();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        Self {
+            verifier,
+            challenge,
+        }
+    }
+}
+
+/// 32 random bytes, encoded in base64url.
+fn random_string() -> Result<String> {
+    let mut bytes = [0_u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| std::io::Error::other(e.to_string()))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Prepare an authorization request.
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    setter(into, strip_option),
+    build_fn(error = "crate::error::BuilderError")
+)]
+#[non_exhaustive]
+pub struct AuthorizationRequest {
+    /// This should never be set manually as this is only for internal use.
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    pub http_client: HttpClientRef,
+
+    /// Where the browser is redirected after the login.
+    ///
+    /// It must be registered for the client.
+    pub redirect_uri: Url,
+
+    /// Space separated scopes, `openid` by default.
+    #[builder(default = "\"openid\".to_string()")]
+    pub scope: String,
+
+    /// A random state is generated if not set.
+    #[builder(default)]
+    pub state: Option<String>,
+
+    /// A random verifier is generated if not set.
+    #[builder(default)]
+    pub pkce: Option<Pkce>,
+}
+
+impl AuthorizationRequest {
+    /// Build the authorization URL of the client.
+    ///
+    /// The client ID is taken from the client info.
+    pub async fn prepare(&self) -> Result<AuthorizationSession> {
+        let client = self.http_client.read().await;
+        let client_info = client.get_client_info().ok_or(Error::MissingClientInfo)?;
+        let state = match &self.state {
+            Some(state) => state.clone(),
+            None => random_string()?,
+        };
+        let pkce = match &self.pkce {
+            Some(pkce) => pkce.clone(),
+            None => Pkce::new()?,
+        };
+        let mut url = client.auth_url.join(AUTHORIZE_PATH)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &client_info.client_id)
+            .append_pair("redirect_uri", self.redirect_uri.as_str())
+            .append_pair("scope", &self.scope)
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &pkce.challenge)
+            .append_pair("code_challenge_method", Pkce::METHOD);
+        Ok(AuthorizationSession {
+            url,
+            state,
+            pkce,
+            redirect_uri: self.redirect_uri.clone(),
+        })
+    }
+}
+
+impl AuthorizationRequestBuilder {
+    pub async fn prepare(&self) -> Result<AuthorizationSession> {
+        self.build()?.prepare().await
+    }
+}
+
+/// A pending authorization, to keep until the redirect is received.
+///
+/// It can be serialized to be kept in a web session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct AuthorizationSession {
+    /// The URL to open in the browser.
+    pub url: Url,
+    pub state: String,
+    pub pkce: Pkce,
+    pub redirect_uri: Url,
+}
+
+impl AuthorizationSession {
+    /// Get the authorization code from the redirect URL.
+    ///
+    /// Fails with [`Error::AuthorizationError`] if the state doesn't match
+    /// or the redirect reports an error.
+    pub fn code(&self, redirect: &Url) -> Result<String> {
+        let param = |name: &str| {
+            redirect
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or_default();
+            return Err(Error::AuthorizationError(
+                format!("{error} {description}").trim_end().to_string(),
+            ));
+        }
+        if param("state").as_deref() != Some(self.state.as_str()) {
+            return Err(Error::AuthorizationError(
+                "the state of the redirect doesn't match the request".to_string(),
+            ));
+        }
+        param("code")
+            .ok_or_else(|| Error::AuthorizationError("the redirect has no code".to_string()))
+    }
+
+    /// Exchange the code of the redirect URL for the tokens, and store them in the client.
+    pub async fn exchange(
+        &self,
+        client: &MangaDexClient,
+        redirect: &Url,
+    ) -> Result<OAuthTokenResponse> {
+        client
+            .oauth()
+            .exchange_code()
+            .code(self.code(redirect)?)
+            .code_verifier(self.pkce.verifier.clone())
+            .redirect_uri(self.redirect_uri.clone())
+            .send()
+            .await
+    }
+}
+
+/// Receives the authorization redirect on a local port.
+#[cfg(feature = "oauth-listener")]
+#[cfg_attr(docsrs, doc(cfg(feature = "oauth-listener")))]
+#[derive(Debug)]
+pub struct RedirectListener {
+    listener: TcpListener,
+    redirect_uri: Url,
+}
+
+#[cfg(feature = "oauth-listener")]
+impl RedirectListener {
+    /// Listen on `127.0.0.1:port`, or a random port if `port` is `0`.
+    ///
+    /// The redirect URI is `http://127.0.0.1:{port}/callback`.
+    pub async fn bind(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let port = listener.local_addr()?.port();
+        let redirect_uri = Url::parse(&format!("http://127.0.0.1:{port}/callback"))?;
+        Ok(Self {
+            listener,
+            redirect_uri,
+        })
+    }
+
+    pub fn redirect_uri(&self) -> &Url {
+        &self.redirect_uri
+    }
+
+    /// Wait for the redirect and return its URL.
+    ///
+    /// The browser gets a page telling the user to go back to the application.
+    /// The requests to other paths, like `/favicon.ico`, get a `404 Not Found` and are ignored.
+    pub async fn accept(self) -> Result<Url> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            let mut request_line = String::new();
+            BufReader::new(&mut stream)
+                .read_line(&mut request_line)
+                .await?;
+            let url = request_line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|target| self.redirect_uri.join(target).ok())
+                .filter(|url| url.path() == self.redirect_uri.path());
+            let (status, body) = match url {
+                Some(_) => ("200 OK", "You are logged in, you can close this window."),
+                None => ("404 Not Found", "Not found."),
+            };
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {status}\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+            stream.shutdown().await?;
+            if let Some(url) = url {
+                return Ok(url);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_schema::v5::oauth::ClientInfo;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::HttpClient;
+
+    #[test]
+    fn pkce_challenge_is_the_sha256_of_the_verifier() -> anyhow::Result<()> {
+        // From RFC 7636, appendix B.
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+        assert_eq!(
+            pkce.challenge,
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_ne!(Pkce::new()?.verifier, Pkce::new()?.verifier);
+        Ok(())
+    }
+
+    #[cfg(feature = "oauth-listener")]
+    #[tokio::test]
+    async fn redirect_listener_ignores_other_paths() -> anyhow::Result<()> {
+        let listener = RedirectListener::bind(0).await?;
+        let favicon = listener.redirect_uri().join("/favicon.ico")?;
+        let mut redirect = listener.redirect_uri().clone();
+        redirect.query_pairs_mut().append_pair("code", "someCode");
+
+        let accepted = tokio::spawn(listener.accept());
+        assert_eq!(
+            reqwest::get(favicon).await?.status(),
+            reqwest::StatusCode::NOT_FOUND
+        );
+        assert!(reqwest::get(redirect.clone()).await?.status().is_success());
+        assert_eq!(accepted.await??, redirect);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn authorization_code_flow_stores_the_tokens() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
+            .client_info(non_exhaustive::non_exhaustive!(ClientInfo {
+                client_id: "somePublicClientId".to_string(),
+                client_secret: String::new(),
+            }))
+            .build()?;
+        let client = MangaDexClient::new_with_http_client(http_client);
+
+        let session = client
+            .oauth()
+            .authorize()
+            .redirect_uri(Url::parse("http://127.0.0.1:8080/callback")?)
+            .prepare()
+            .await?;
+
+        let mut redirect = session.redirect_uri.clone();
+        redirect
+            .query_pairs_mut()
+            .append_pair("code", "someCode")
+            .append_pair("state", &session.state);
+        Mock::given(method("GET"))
+            .and(path(AUTHORIZE_PATH))
+            .and(query_param("client_id", "somePublicClientId"))
+            .and(query_param(
+                "code_challenge",
+                session.pkce.challenge.as_str(),
+            ))
+            .and(query_param("code_challenge_method", "S256"))
+            .respond_with(ResponseTemplate::new(302).insert_header("location", redirect.as_str()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/realms/mangadex/protocol/openid-connect/token"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("code=someCode"))
+            .and(body_string_contains(format!(
+                "code_verifier={}",
+                session.pkce.verifier
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "sessiontoken",
+                "expires_in": 900,
+                "refresh_expires_in": 2414162,
+                "refresh_token": "refreshtoken",
+                "token_type": "Bearer",
+                "not-before-policy": 0,
+                "session_state": "c176499d-6e8d-4ddf-ad59-6d922be66431",
+                "scope": "openid",
+                "client_type": "public"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // The "browser" is sent to the redirect URI.
+        let browser = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let res = browser.get(session.url.clone()).send().await?;
+        let redirect = Url::parse(
+            res.headers()
+                .get(reqwest::header::LOCATION)
+                .expect("the authorization redirects")
+                .to_str()?,
+        )?;
+
+        let mut forged = session.redirect_uri.clone();
+        forged
+            .query_pairs_mut()
+            .append_pair("code", "someCode")
+            .append_pair("state", "forged");
+        assert!(matches!(
+            session.code(&forged),
+            Err(Error::AuthorizationError(_))
+        ));
+
+        session.exchange(&client, &redirect).await?;
+        assert_eq!(
+            client
+                .get_http_client()
+                .read()
+                .await
+                .get_tokens()
+                .map(|tokens| tokens.session.as_str()),
+            Some("sessiontoken")
+        );
+        Ok(())
+    }
+}
@@ -49,15 +49,13 @@
 
 use derive_builder::Builder;
 use mangadex_api_schema::v5::oauth::OAuthTokenResponse;
-use mangadex_api_schema::v5::AuthTokens;
 use mangadex_api_types::oauth::GrantTypeSupported;
 use reqwest::Method;
 use serde::Serialize;
-#[cfg(not(test))]
-use url::Url;
+use time::OffsetDateTime;
 
 use crate::v5::HttpClientRef;
-use crate::Result;
+use crate::{HttpClient, Result};
 
 /// Log into an account.
 ///
@@ -86,6 +84,8 @@ struct RefreshTokenBody {
     grant_type: GrantTypeSupported,
     refresh_token: String,
     client_id: String,
+    /// Empty for the public clients.
+    #[serde(skip_serializing_if = "String::is_empty")]
     client_secret: String,
 }
 
@@ -93,55 +93,94 @@ impl RefreshTokens {
     pub async fn send(&mut self) -> Result<OAuthTokenResponse> {
         let res = {
             let client = self.http_client.read().await;
-            let client_info = client
-                .get_client_info()
-                .ok_or(crate::error::Error::MissingClientInfo)?;
-            let auth_tokens = client
-                .get_tokens()
-                .ok_or(crate::error::Error::MissingTokens)?;
-            let params = RefreshTokenBody {
-                grant_type: GrantTypeSupported::RefreshToken,
-                refresh_token: auth_tokens.refresh.to_owned(),
-                client_id: client_info.client_id.to_owned(),
-                client_secret: client_info.client_secret.to_owned(),
-            };
-            #[cfg(test)]
-            let res = client
-                .client
-                .request(
-                    Method::POST,
-                    client
-                        .base_url
-                        .join("/realms/mangadex/protocol/openid-connect/token")?,
-                )
-                .form(&params)
-                .send()
-                .await?;
-            #[cfg(not(test))]
-            let res = client
-                .client
-                .request(
-                    Method::POST,
-                    Url::parse(crate::AUTH_URL)?
-                        .join("/realms/mangadex/protocol/openid-connect/token")?,
-                )
-                .form(&params)
-                .send()
-                .await?;
-            if res.status().is_client_error() || res.status().is_server_error() {
-                return Err(super::OAuthError::handle_resp(res).await);
-            }
-            res.json::<OAuthTokenResponse>().await?
+            request_new_tokens(&client).await?
         };
-        {
-            let auth_tokens: AuthTokens = From::from(res.clone());
+        let snapshot = {
             let mut client = self.http_client.write().await;
-            client.set_auth_tokens(&auth_tokens);
+            client.set_oauth_tokens(&res);
+            client.token_store_snapshot()
         };
+        crate::http_client::persist_token_snapshot(&self.http_client, snapshot).await?;
         Ok(res)
     }
 }
 
+/// The session is refreshed when it expires in less than this duration.
+const REFRESH_MARGIN: time::Duration = time::Duration::seconds(60);
+
+async fn request_new_tokens(client: &HttpClient) -> Result<OAuthTokenResponse> {
+    let client_info = client
+        .get_client_info()
+        .ok_or(crate::error::Error::MissingClientInfo)?;
+    let auth_tokens = client
+        .get_tokens()
+        .ok_or(crate::error::Error::MissingTokens)?;
+    let params = RefreshTokenBody {
+        grant_type: GrantTypeSupported::RefreshToken,
+        refresh_token: auth_tokens.refresh.to_owned(),
+        client_id: client_info.client_id.to_owned(),
+        client_secret: client_info.client_secret.to_owned(),
+    };
+    let res = client
+        .client
+        .request(
+            Method::POST,
+            client
+                .auth_url
+                .join("/realms/mangadex/protocol/openid-connect/token")?,
+        )
+        .form(&params)
+        .send()
+        .await?;
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(super::OAuthError::handle_resp(res).await);
+    }
+    Ok(res.json::<OAuthTokenResponse>().await?)
+}
+
+/// Refresh the session if the auto refresh is enabled and the session is about to expire.
+pub(crate) async fn refresh_if_expiring(http_client: &HttpClientRef) -> Result<()> {
+    let session = {
+        let client = http_client.read().await;
+        match (client.get_tokens(), client.get_session_expiration()) {
+            (Some(tokens), Some(expiration))
+                if client.is_auto_refresh_enabled()
+                    && expiration - REFRESH_MARGIN <= OffsetDateTime::now_utc() =>
+            {
+                tokens.session.clone()
+            }
+            _ => return Ok(()),
+        }
+    };
+    refresh_session(http_client, &session).await
+}
+
+/// Refresh the session unless another task already replaced `stale_session`.
+///
+/// The write lock is held during the refresh so concurrent refreshes are done only once.
+/// The new tokens are persisted after the lock is released,
+/// a failure is kept in the client instead of failing the request.
+pub(crate) async fn refresh_session(
+    http_client: &HttpClientRef,
+    stale_session: &str,
+) -> Result<()> {
+    let snapshot = {
+        let mut client = http_client.write().await;
+        if client
+            .get_tokens()
+            .is_some_and(|tokens| tokens.session != stale_session)
+        {
+            return Ok(());
+        }
+        let res = request_new_tokens(&client).await?;
+        client.set_oauth_tokens(&res);
+        client.token_store_snapshot()
+    };
+    // The error is logged and kept in the client by `persist_token_snapshot`.
+    let _ = crate::http_client::persist_token_snapshot(http_client, snapshot).await;
+    Ok(())
+}
+
 builder_send! {
     #[builder] RefreshTokensBuilder,
     OAuthTokenResponse
@@ -162,10 +201,11 @@ mod tests {
     use serde_urlencoded::to_string;
 
     #[tokio::test]
-    async fn refresh_token_fires_a_request_to_base_url() -> anyhow::Result<()> {
+    async fn refresh_token_fires_a_request_to_auth_url() -> anyhow::Result<()> {
         let mock_server = MockServer::start().await;
         let http_client: HttpClient = HttpClient::builder()
             .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
             .build()?;
         let mangadex_client = MangaDexClient::new_with_http_client(http_client);
 
@@ -219,4 +259,124 @@ mod tests {
 
         Ok(())
     }
+
+    fn token_response(session: &str) -> serde_json::Value {
+        json!({
+            "access_token": session,
+            "expires_in": 900,
+            "refresh_expires_in": 2414162,
+            "refresh_token": "newrefreshtoken",
+            "token_type": "Bearer",
+            "not-before-policy": 0,
+            "session_state": "c176499d-6e8d-4ddf-ad59-6d922be66431",
+            "scope": "groups email profile",
+            "client_type": "personal"
+        })
+    }
+
+    fn user_me_response() -> serde_json::Value {
+        json!({
+            "result": "ok",
+            "response": "entity",
+            "data": {
+                "id": "a3219a4f-73c0-4213-8730-05985130539a",
+                "type": "user",
+                "attributes": {
+                    "username": "myusername",
+                    "roles": ["ROLE_MEMBER"],
+                    "version": 1,
+                },
+                "relationships": []
+            }
+        })
+    }
+
+    fn auto_refresh_client(mock_server: &MockServer) -> anyhow::Result<HttpClient> {
+        Ok(HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
+            .client_info(non_exhaustive::non_exhaustive!(ClientInfo {
+                client_id: "someClientId".to_string(),
+                client_secret: "someClientSecret".to_string(),
+            }))
+            .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                session: "expiredsession".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .auto_refresh(true)
+            .build()?)
+    }
+
+    #[tokio::test]
+    async fn auto_refresh_retries_once_after_unauthorized() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let mangadex_client =
+            MangaDexClient::new_with_http_client(auto_refresh_client(&mock_server)?);
+
+        Mock::given(method("POST"))
+            .and(path(r"/realms/mangadex/protocol/openid-connect/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(token_response("newsession")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(r"/user/me"))
+            .and(header("Authorization", "Bearer expiredsession"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1..=4)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(r"/user/me"))
+            .and(header("Authorization", "Bearer newsession"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(user_me_response()))
+            .expect(4)
+            .mount(&mock_server)
+            .await;
+
+        // Concurrent requests only trigger one refresh.
+        let requests = (0..4).map(|_| {
+            let mangadex_client = mangadex_client.clone();
+            tokio::spawn(async move { mangadex_client.user().me().get().send().await })
+        });
+        for request in requests {
+            request.await??;
+        }
+
+        let client = mangadex_client.http_client.read().await;
+        assert_eq!(
+            client.get_tokens().map(|tokens| tokens.session.as_str()),
+            Some("newsession")
+        );
+        assert!(client.get_session_expiration().is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn auto_refresh_refreshes_before_expiration() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let mut http_client = auto_refresh_client(&mock_server)?;
+        http_client.set_session_expiration(time::OffsetDateTime::now_utc());
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("POST"))
+            .and(path(r"/realms/mangadex/protocol/openid-connect/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(token_response("newsession")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(r"/user/me"))
+            .and(header("Authorization", "Bearer newsession"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(user_me_response()))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        mangadex_client.user().me().get().send().await?;
+        mangadex_client.user().me().get().send().await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,114 @@
+//! Builder for the OAuth token revocation endpoint.
+//!
+//! <https://datatracker.ietf.org/doc/html/rfc7009>
+//!
+//! Revoking a refresh token also revokes the access tokens issued with it.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::v5::MangaDexClient;
+//! use mangadex_api::v5::oauth::revoke::TokenTypeHint;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! client
+//!     .oauth()
+//!     .revoke()
+//!     .token("someRefreshToken")
+//!     .token_type_hint(TokenTypeHint::RefreshToken)
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use derive_builder::Builder;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+use crate::v5::HttpClientRef;
+
+/// The type of the revoked token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum TokenTypeHint {
+    AccessToken,
+    RefreshToken,
+}
+
+/// Revoke a token.
+///
+/// Makes a request to `POST https://auth.mangadex.org/realms/mangadex/protocol/openid-connect/revoke`.
+#[cfg_attr(
+    feature = "deserializable-endpoint",
+    derive(serde::Deserialize, getset::Getters, getset::Setters)
+)]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    setter(into, strip_option),
+    build_fn(error = "crate::error::BuilderError")
+)]
+#[non_exhaustive]
+pub struct RevokeToken {
+    /// This should never be set manually as this is only for internal use.
+    #[doc(hidden)]
+    #[cfg_attr(feature = "deserializable-endpoint", serde(skip))]
+    #[builder(pattern = "immutable")]
+    #[cfg_attr(feature = "deserializable-endpoint", getset(set = "pub", get = "pub"))]
+    pub http_client: HttpClientRef,
+
+    pub token: String,
+
+    #[builder(default)]
+    pub token_type_hint: Option<TokenTypeHint>,
+}
+
+#[derive(Clone, Serialize)]
+struct RevokeTokenBody {
+    token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type_hint: Option<TokenTypeHint>,
+    client_id: String,
+    /// Empty for the public clients.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    client_secret: String,
+}
+
+impl RevokeToken {
+    pub async fn send(&mut self) -> Result<()> {
+        let client = self.http_client.read().await;
+        let client_info = client
+            .get_client_info()
+            .ok_or(crate::error::Error::MissingClientInfo)?;
+        let params = RevokeTokenBody {
+            token: self.token.to_owned(),
+            token_type_hint: self.token_type_hint,
+            client_id: client_info.client_id.to_owned(),
+            client_secret: client_info.client_secret.to_owned(),
+        };
+        let res = client
+            .client
+            .request(
+                Method::POST,
+                client
+                    .auth_url
+                    .join("/realms/mangadex/protocol/openid-connect/revoke")?,
+            )
+            .form(&params)
+            .send()
+            .await?;
+        if res.status().is_client_error() || res.status().is_server_error() {
+            return Err(super::OAuthError::handle_resp(res).await);
+        }
+        Ok(())
+    }
+}
+
+builder_send! {
+    #[builder] RevokeTokenBuilder,
+    ()
+}
@@ -0,0 +1,120 @@
+//! Builder for the OpenID Connect user info endpoint.
+//!
+//! <https://openid.net/specs/openid-connect-core-1_0.html#UserInfo>
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::v5::MangaDexClient;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let user_info = client.oauth().userinfo().send().await?;
+//!
+//! println!("logged in as {:?}", user_info.preferred_username);
+//! # Ok(())
+//! # }
+//! ```
+
+use derive_builder::Builder;
+use mangadex_api_schema::v5::oauth::UserInfo;
+use reqwest::Method;
+
+use crate::Result;
+use crate::v5::HttpClientRef;
+
+/// Get the user info of the session stored in the client.
+///
+/// Makes a request to `GET https://auth.mangadex.org/realms/mangadex/protocol/openid-connect/userinfo`.
+#[cfg_attr(
+    feature = "deserializable-endpoint",
+    derive(serde::Deserialize, getset::Getters, getset::Setters)
+)]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    setter(into, strip_option),
+    build_fn(error = "crate::error::BuilderError")
+)]
+#[non_exhaustive]
+pub struct GetUserInfo {
+    /// This should never be set manually as this is only for internal use.
+    #[doc(hidden)]
+    #[cfg_attr(feature = "deserializable-endpoint", serde(skip))]
+    #[builder(pattern = "immutable")]
+    #[cfg_attr(feature = "deserializable-endpoint", getset(set = "pub", get = "pub"))]
+    pub http_client: HttpClientRef,
+}
+
+impl GetUserInfo {
+    pub async fn send(&mut self) -> Result<UserInfo> {
+        let client = self.http_client.read().await;
+        let auth_tokens = client
+            .get_tokens()
+            .ok_or(crate::error::Error::MissingTokens)?;
+        let res = client
+            .client
+            .request(
+                Method::GET,
+                client
+                    .auth_url
+                    .join("/realms/mangadex/protocol/openid-connect/userinfo")?,
+            )
+            .bearer_auth(&auth_tokens.session)
+            .send()
+            .await?;
+        if res.status().is_client_error() || res.status().is_server_error() {
+            return Err(super::OAuthError::handle_resp(res).await);
+        }
+        Ok(res.json::<UserInfo>().await?)
+    }
+}
+
+builder_send! {
+    #[builder] GetUserInfoBuilder,
+    UserInfo
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::v5::AuthTokens;
+    use crate::{HttpClient, MangaDexClient};
+
+    #[tokio::test]
+    async fn userinfo_fires_a_request_to_auth_url() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
+            .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("GET"))
+            .and(path("/realms/mangadex/protocol/openid-connect/userinfo"))
+            .and(header("authorization", "Bearer sessiontoken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "sub": "f6a8d1b5-8b0e-4d3b-9f4e-3f8c1d1a2b3c",
+                "email_verified": true,
+                "preferred_username": "myusername",
+                "roles": ["ROLE_USER"]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let user_info = mangadex_client.oauth().userinfo().send().await?;
+
+        assert_eq!(user_info.preferred_username.as_deref(), Some("myusername"));
+        assert_eq!(user_info.roles, vec!["ROLE_USER".to_string()]);
+        Ok(())
+    }
+}
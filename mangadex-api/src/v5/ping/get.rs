@@ -60,12 +60,7 @@ endpoint! {
 
 impl Ping {
     pub async fn send(&self) -> Result<String> {
-        let res = self
-            .http_client
-            .read()
-            .await
-            .send_request_without_deserializing(self)
-            .await?;
+        let res = crate::http_client::send_with_checks(&self.http_client, self).await?;
 
         let response_body = res.text().await?;
         if response_body.as_str() == "pong" {
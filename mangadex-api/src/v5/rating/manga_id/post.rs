@@ -83,7 +83,7 @@ impl CreateUpdateMangaRating {
     pub async fn send(&mut self) -> Result<NoData> {
         self.rating = self.rating.clamp(1, 10);
 
-        let res = self.http_client.read().await.send_request(self).await??;
+        let res = crate::http_client::send(&self.http_client, self).await??;
 
         Ok(res)
     }
@@ -103,11 +103,7 @@ impl Endpoint for DeleteImages {
 
 impl DeleteImages {
     pub async fn send(&self) -> Result<Limited<NoData>> {
-        self.http_client
-            .read()
-            .await
-            .send_request_with_rate_limit(self)
-            .await
+        crate::http_client::send_with_rate_limit(&self.http_client, self).await
     }
 }
 
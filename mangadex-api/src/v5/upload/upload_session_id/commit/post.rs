@@ -100,6 +100,19 @@ pub struct ChapterDraft {
     pub publish_at: Option<MangaDexDateTime>,
 }
 
+impl ChapterDraft {
+    pub fn new(translated_language: Language) -> Self {
+        Self {
+            volume: None,
+            chapter: None,
+            title: None,
+            translated_language,
+            external_url: None,
+            publish_at: None,
+        }
+    }
+}
+
 #[cfg_attr(feature = "deserializable-endpoint", derive(serde::Deserialize))]
 /// Custom request builder to handle nested struct.
 #[derive(Debug, Serialize, Clone, Default)]
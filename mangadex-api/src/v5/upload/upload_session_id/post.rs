@@ -170,12 +170,7 @@ impl Endpoint for UploadImages {
 
 impl UploadImages {
     pub async fn send(&self) -> Result<Limited<UploadSessionFileDataObject>> {
-        let res = self
-            .http_client
-            .read()
-            .await
-            .send_request_with_rate_limit(self)
-            .await?;
+        let res = crate::http_client::send_with_rate_limit(&self.http_client, self).await?;
 
         Ok(res)
     }
@@ -77,12 +77,7 @@ pub struct IsBookmarkingGroup {
 
 impl IsBookmarkingGroup {
     pub async fn send(&mut self) -> Result<IsFollowingResponse> {
-        let res = self
-            .http_client
-            .read()
-            .await
-            .send_request_without_deserializing(self)
-            .await?;
+        let res = crate::http_client::send_with_checks(&self.http_client, self).await?;
 
         match res.status() {
             reqwest::StatusCode::OK => Ok(non_exhaustive::non_exhaustive!(IsFollowingResponse {
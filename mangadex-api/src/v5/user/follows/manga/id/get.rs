@@ -84,12 +84,7 @@ pub struct IsFollowingManga {
 
 impl IsFollowingManga {
     pub async fn send(&mut self) -> Result<IsFollowingResponse> {
-        let res = self
-            .http_client
-            .read()
-            .await
-            .send_request_without_deserializing(self)
-            .await?;
+        let res = crate::http_client::send_with_checks(&self.http_client, self).await?;
 
         match res.status() {
             reqwest::StatusCode::OK => Ok(non_exhaustive::non_exhaustive!(IsFollowingResponse {
@@ -269,6 +264,72 @@ mod tests {
             _ => panic!("did not get Error::Api"),
         }
 
+        Ok(())
+    }
+    #[cfg(feature = "oauth")]
+    #[tokio::test]
+    async fn expired_session_is_refreshed() -> anyhow::Result<()> {
+        use mangadex_api_schema::v5::oauth::ClientInfo;
+        use wiremock::matchers::path;
+
+        let mock_server = MockServer::start().await;
+        let http_client: HttpClient = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_url(Url::parse(&mock_server.uri())?)
+            .client_info(non_exhaustive::non_exhaustive!(ClientInfo {
+                client_id: "someClientId".to_string(),
+                client_secret: "someClientSecret".to_string(),
+            }))
+            .auth_tokens(non_exhaustive::non_exhaustive!(AuthTokens {
+                session: "expiredsession".to_string(),
+                refresh: "refreshtoken".to_string(),
+            }))
+            .auto_refresh(true)
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        Mock::given(method("POST"))
+            .and(path(r"/realms/mangadex/protocol/openid-connect/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "newsession",
+                "expires_in": 900,
+                "refresh_expires_in": 2414162,
+                "refresh_token": "refreshtoken",
+                "token_type": "Bearer",
+                "not-before-policy": 0,
+                "session_state": "c176499d-6e8d-4ddf-ad59-6d922be66431",
+                "scope": "groups email profile",
+                "client_type": "personal"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/user/follows/manga/[0-9a-fA-F-]+"))
+            .and(header("Authorization", "Bearer expiredsession"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/user/follows/manga/[0-9a-fA-F-]+"))
+            .and(header("Authorization", "Bearer newsession"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let res = mangadex_client
+            .user()
+            .follows()
+            .manga()
+            .id(Uuid::new_v4())
+            .get()
+            .send()
+            .await?;
+
+        assert!(res.is_following);
+
         Ok(())
     }
 }